@@ -230,6 +230,9 @@ where
     /// Provides an API similar to `Stream`, except that it cannot end.
     pub fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<ListenersEvent<TTrans>> {
         // We remove each element from `listeners` one by one and add them back.
+        // A listener that produced an event is added back last, so that the
+        // listeners are polled in a round-robin fashion and a busy listener
+        // cannot starve the others.
         let mut remaining = self.listeners.len();
         while let Some(mut listener) = self.listeners.pop_back() {
             let mut listener_project = listener.as_mut().project();
@@ -483,4 +486,60 @@ mod tests {
             }
         });
     }
+
+    #[test]
+    fn busy_listeners_are_polled_fairly() {
+        // Tests that a listener that is always ready does not starve the
+        // other listeners.
+        use std::collections::HashMap;
+
+        #[derive(Clone)]
+        struct DummyTrans;
+        impl transport::Transport for DummyTrans {
+            type Output = ();
+            type Error = std::io::Error;
+            type Listener = Pin<Box<dyn Stream<Item = Result<ListenerEvent<Self::ListenerUpgrade, std::io::Error>, std::io::Error>>>>;
+            type ListenerUpgrade = Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>>>>;
+            type Dial = Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>>>>;
+
+            fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, transport::TransportError<Self::Error>> {
+                Ok(Box::pin(stream::unfold(addr, |addr| async move {
+                    let event = ListenerEvent::Upgrade {
+                        upgrade: Box::pin(future::ok(())) as Self::ListenerUpgrade,
+                        local_addr: addr.clone(),
+                        remote_addr: "/memory/0".parse().unwrap(),
+                    };
+                    Some((Ok(event), addr))
+                })))
+            }
+
+            fn dial(self, _: Multiaddr) -> Result<Self::Dial, transport::TransportError<Self::Error>> {
+                panic!()
+            }
+
+            fn address_translation(&self, _: &Multiaddr, _: &Multiaddr) -> Option<Multiaddr> { None }
+        }
+
+        async_std::task::block_on(async move {
+            let addr1: Multiaddr = "/memory/1".parse().unwrap();
+            let addr2: Multiaddr = "/memory/2".parse().unwrap();
+
+            let mut listeners = ListenersStream::new(DummyTrans);
+            listeners.listen_on(addr1.clone()).unwrap();
+            listeners.listen_on(addr2.clone()).unwrap();
+
+            let mut incoming = HashMap::new();
+            for _ in 0..10 {
+                match listeners.next().await.unwrap() {
+                    ListenersEvent::Incoming { local_addr, .. } => {
+                        *incoming.entry(local_addr).or_insert(0) += 1;
+                    }
+                    _ => panic!()
+                }
+            }
+
+            assert_eq!(incoming.get(&addr1), Some(&5));
+            assert_eq!(incoming.get(&addr2), Some(&5));
+        });
+    }
 }
@@ -640,6 +640,19 @@ impl<TInEvent, TOutEvent, THandler, TTransErr, THandlerErr>
                             })
                         }
 
+                        // Check total established connection limit.
+                        if let Err(e) = self.counters.check_max_established_total() {
+                            let connected = entry.remove();
+                            return Poll::Ready(PoolEvent::PendingConnectionError {
+                                id,
+                                endpoint: connected.endpoint,
+                                error: PendingConnectionError::ConnectionLimit(e),
+                                handler: None,
+                                peer,
+                                pool: self
+                            })
+                        }
+
                         // Check per-peer established connection limit.
                         let current = num_peer_established(&self.established, &entry.connected().peer_id);
                         if let Err(e) = self.counters.check_max_established_per_peer(current) {
@@ -996,6 +1009,10 @@ impl ConnectionCounters {
         }
     }
 
+    fn check_max_established_total(&self) -> Result<(), ConnectionLimit> {
+        Self::check(self.num_established(), self.limits.max_established_total)
+    }
+
     fn check_max_established_per_peer(&self, current: u32) -> Result<(), ConnectionLimit> {
         Self::check(current, self.limits.max_established_per_peer)
     }
@@ -1031,6 +1048,7 @@ pub struct ConnectionLimits {
     max_established_incoming: Option<u32>,
     max_established_outgoing: Option<u32>,
     max_established_per_peer: Option<u32>,
+    max_established_total: Option<u32>,
 }
 
 impl ConnectionLimits {
@@ -1064,6 +1082,13 @@ impl ConnectionLimits {
         self.max_established_per_peer = limit;
         self
     }
+
+    /// Configures the maximum number of concurrent established connections across
+    /// all peers, regardless of direction (incoming or outgoing).
+    pub fn with_max_established_total(mut self, limit: Option<u32>) -> Self {
+        self.max_established_total = limit;
+        self
+    }
 }
 
 /// Information about a former established connection to a peer
@@ -271,6 +271,11 @@ where
         self.pool.iter_pending_incoming()
     }
 
+    /// Returns an iterator for information on all pending outgoing connections.
+    pub fn outgoing_info(&self) -> impl Iterator<Item = OutgoingInfo<'_>> {
+        self.pool.iter_pending_outgoing()
+    }
+
     /// Returns the list of addresses we're currently dialing without knowing the `PeerId` of.
     pub fn unknown_dials(&self) -> impl Iterator<Item = &Multiaddr> {
         self.pool.iter_pending_outgoing()
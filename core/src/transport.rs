@@ -33,9 +33,12 @@ use std::{error::Error, fmt};
 pub mod and_then;
 pub mod choice;
 pub mod dummy;
+pub mod filter;
+pub mod instrumented;
 pub mod map;
 pub mod map_err;
 pub mod memory;
+pub mod rate_limit;
 pub mod timeout;
 pub mod upgrade;
 
@@ -194,6 +197,22 @@ pub trait Transport {
         and_then::AndThen::new(self, f)
     }
 
+    /// Rejects the connections for which `allow_raw_connection` returns
+    /// `false`, given the [`ConnectedPoint`] of each dial and each incoming
+    /// connection.
+    ///
+    /// Rejected dials fail immediately, rejected incoming connections are
+    /// dropped. Applied before [`Transport::upgrade`], no protocol upgrade,
+    /// in particular no security handshake, is performed on rejected
+    /// connections.
+    fn filter<F>(self, allow_raw_connection: F) -> filter::Filter<Self, F>
+    where
+        Self: Sized,
+        F: Fn(&ConnectedPoint) -> bool + Clone
+    {
+        filter::Filter::new(self, allow_raw_connection)
+    }
+
     /// Begins a series of protocol upgrades via an
     /// [`upgrade::Builder`](upgrade::Builder).
     fn upgrade(self, version: upgrade::Version) -> upgrade::Builder<Self>
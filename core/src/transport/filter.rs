@@ -0,0 +1,289 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Transports rejecting connections based on their addresses.
+//!
+//! Applied to a `Transport` before [`Transport::upgrade`], rejected
+//! connections are dropped before any protocol upgrade, in particular before
+//! the security handshake, is performed on them.
+
+use crate::{
+    ConnectedPoint,
+    Multiaddr,
+    Transport,
+    transport::{TransportError, ListenerEvent},
+};
+use futures::prelude::*;
+use std::{error, fmt, pin::Pin, task::Context, task::Poll};
+
+/// Decides whether a dial or an incoming connection is allowed by a
+/// [`Filter`], given its [`ConnectedPoint`].
+///
+/// Implemented for all `Fn(&ConnectedPoint) -> bool`.
+pub trait AllowConnection {
+    /// Returns `false` if the connection is to be rejected.
+    fn allow(&self, point: &ConnectedPoint) -> bool;
+}
+
+impl<F> AllowConnection for F
+where
+    F: Fn(&ConnectedPoint) -> bool,
+{
+    fn allow(&self, point: &ConnectedPoint) -> bool {
+        self(point)
+    }
+}
+
+/// See [`Transport::filter`].
+#[derive(Debug, Copy, Clone)]
+pub struct Filter<InnerTrans, F> {
+    inner: InnerTrans,
+    allow_raw_connection: F,
+}
+
+impl<InnerTrans, F> Filter<InnerTrans, F> {
+    pub(crate) fn new(inner: InnerTrans, allow_raw_connection: F) -> Self {
+        Filter { inner, allow_raw_connection }
+    }
+}
+
+impl<InnerTrans, F> Transport for Filter<InnerTrans, F>
+where
+    InnerTrans: Transport,
+    InnerTrans::Error: 'static,
+    F: AllowConnection + Clone,
+{
+    type Output = InnerTrans::Output;
+    type Error = FilterError<InnerTrans::Error>;
+    type Listener = FilterListener<InnerTrans::Listener, F>;
+    type ListenerUpgrade = FilterFuture<InnerTrans::ListenerUpgrade>;
+    type Dial = FilterFuture<InnerTrans::Dial>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let listener = self.inner.listen_on(addr)
+            .map_err(|err| err.map(FilterError::Other))?;
+        Ok(FilterListener {
+            inner: listener,
+            allow_raw_connection: self.allow_raw_connection,
+        })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let point = ConnectedPoint::Dialer { address: addr.clone() };
+        if !self.allow_raw_connection.allow(&point) {
+            log::debug!("Rejected dialing {}.", addr);
+            return Err(TransportError::Other(FilterError::Rejected))
+        }
+        let dial = self.inner.dial(addr)
+            .map_err(|err| err.map(FilterError::Other))?;
+        Ok(FilterFuture { inner: Some(dial) })
+    }
+
+    fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.inner.address_translation(server, observed)
+    }
+}
+
+// TODO: can be removed and replaced with an `impl Stream` once impl Trait is fully stable
+//       in Rust (https://github.com/rust-lang/rust/issues/34511)
+#[pin_project::pin_project]
+#[derive(Debug)]
+pub struct FilterListener<InnerStream, F> {
+    #[pin]
+    inner: InnerStream,
+    allow_raw_connection: F,
+}
+
+impl<InnerStream, O, E, F> Stream for FilterListener<InnerStream, F>
+where
+    InnerStream: Stream<Item = Result<ListenerEvent<O, E>, E>>,
+    F: AllowConnection,
+{
+    type Item = Result<ListenerEvent<FilterFuture<O>, FilterError<E>>, FilterError<E>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        let event = match this.inner.poll_next(cx) {
+            Poll::Ready(Some(Ok(event))) => event,
+            Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(FilterError::Other(err)))),
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let event = match event {
+            ListenerEvent::Upgrade { upgrade, local_addr, remote_addr } => {
+                let point = ConnectedPoint::Listener {
+                    local_addr: local_addr.clone(),
+                    send_back_addr: remote_addr.clone(),
+                };
+                // Dropping the upgrade of a rejected connection closes it
+                // before anything is read from or written to it.
+                let upgrade = if this.allow_raw_connection.allow(&point) {
+                    Some(upgrade)
+                } else {
+                    log::debug!("Rejected incoming connection from {}.", remote_addr);
+                    None
+                };
+                ListenerEvent::Upgrade {
+                    upgrade: FilterFuture { inner: upgrade },
+                    local_addr,
+                    remote_addr,
+                }
+            }
+            ListenerEvent::NewAddress(a) => ListenerEvent::NewAddress(a),
+            ListenerEvent::AddressExpired(a) => ListenerEvent::AddressExpired(a),
+            ListenerEvent::Error(e) => ListenerEvent::Error(FilterError::Other(e)),
+        };
+
+        Poll::Ready(Some(Ok(event)))
+    }
+}
+
+/// Wraps around a connection setup `Future`, failing immediately with
+/// [`FilterError::Rejected`] if the connection has been rejected.
+// TODO: can be replaced with `impl Future` once `impl Trait` are fully stable in Rust
+//       (https://github.com/rust-lang/rust/issues/34511)
+#[pin_project::pin_project]
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct FilterFuture<InnerFut> {
+    #[pin]
+    inner: Option<InnerFut>,
+}
+
+impl<InnerFut, O, E> Future for FilterFuture<InnerFut>
+where
+    InnerFut: Future<Output = Result<O, E>>,
+{
+    type Output = Result<O, FilterError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().inner.as_pin_mut() {
+            Some(inner) => inner.poll(cx).map_err(FilterError::Other),
+            None => Poll::Ready(Err(FilterError::Rejected)),
+        }
+    }
+}
+
+/// Error that can be produced by the `Filter` layer.
+#[derive(Debug)]
+pub enum FilterError<TErr> {
+    /// The connection has been rejected by the filter.
+    Rejected,
+    /// Other kind of error.
+    Other(TErr),
+}
+
+impl<TErr> fmt::Display for FilterError<TErr>
+where TErr: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::Rejected => write!(f, "Connection rejected by filter"),
+            FilterError::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<TErr> error::Error for FilterError<TErr>
+where TErr: error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FilterError::Rejected => None,
+            FilterError::Other(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{either::EitherError, transport::MemoryTransport};
+    use std::{io, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+
+    /// Checks whether `prefix` is a prefix of `addr`.
+    fn starts_with(addr: &Multiaddr, prefix: &Multiaddr) -> bool {
+        let mut addr = addr.iter();
+        prefix.iter().all(|p| addr.next() == Some(p))
+    }
+
+    fn random_memory_addr() -> Multiaddr {
+        format!("/memory/{}", rand::random::<u64>().saturating_add(1)).parse().unwrap()
+    }
+
+    #[test]
+    fn rejects_dial_to_address_prefix() {
+        let blocked = random_memory_addr();
+        let allowed = random_memory_addr();
+
+        let transport = {
+            let blocked = blocked.clone();
+            MemoryTransport::default()
+                .filter(move |point| !starts_with(point.get_remote_address(), &blocked))
+        };
+
+        let mut listener = MemoryTransport::default().listen_on(blocked.clone()).unwrap();
+        let _listener = MemoryTransport::default().listen_on(allowed.clone()).unwrap();
+
+        match transport.clone().dial(blocked) {
+            Err(TransportError::Other(FilterError::Rejected)) => {}
+            _ => panic!("Dial to blocked address not rejected."),
+        }
+        assert!(transport.dial(allowed).is_ok());
+
+        // The rejected dial never reached the listener.
+        while let Some(Some(event)) = listener.next().now_or_never() {
+            assert!(!matches!(event.unwrap(), ListenerEvent::Upgrade { .. }));
+        }
+    }
+
+    #[test]
+    fn rejects_incoming_before_upgrade() {
+        let upgrades = Arc::new(AtomicUsize::new(0));
+        let transport = {
+            let upgrades = upgrades.clone();
+            MemoryTransport::default()
+                .filter(|point| point.is_dialer())
+                .and_then(move |conn, _| {
+                    upgrades.fetch_add(1, Ordering::SeqCst);
+                    future::ok::<_, io::Error>(conn)
+                })
+        };
+
+        let addr = random_memory_addr();
+        let mut listener = transport.listen_on(addr.clone()).unwrap();
+        let dial = MemoryTransport::default().dial(addr).unwrap();
+
+        let listen = async move {
+            while let Some(event) = listener.next().await {
+                if let ListenerEvent::Upgrade { upgrade, .. } = event.unwrap() {
+                    return upgrade.await
+                }
+            }
+            panic!("Listener closed.")
+        };
+        let (result, _) = futures::executor::block_on(future::join(listen, dial));
+
+        assert!(matches!(result, Err(EitherError::A(FilterError::Rejected))));
+        assert_eq!(upgrades.load(Ordering::SeqCst), 0);
+    }
+}
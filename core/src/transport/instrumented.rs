@@ -0,0 +1,299 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Transports recording how long it takes to set up connections.
+//!
+//! The connection setup includes all protocol upgrades applied on the
+//! underlying `Transport`.
+
+use crate::{Multiaddr, Transport, connection::Endpoint, transport::{TransportError, ListenerEvent}};
+use futures::prelude::*;
+use parking_lot::Mutex;
+use std::{pin::Pin, sync::Arc, task::Context, task::Poll, time::Duration};
+use wasm_timer::Instant;
+
+/// The number of buckets of a [`Histogram`], the last one holding all
+/// samples exceeding the upper bound of the second to last one.
+const NUM_BUCKETS: usize = 17;
+
+/// An `Instrumented` is a `Transport` that wraps another `Transport` and
+/// records the time taken by all successful inbound and outbound connection
+/// setups in [`Timings`].
+///
+/// Clones of an `Instrumented` transport share the same [`Timings`], so that
+/// it can be wrapped at any point of a transport stack, e.g. before boxing it.
+#[derive(Debug, Clone)]
+pub struct Instrumented<InnerTrans> {
+    inner: InnerTrans,
+    timings: Arc<Mutex<Timings>>,
+}
+
+impl<InnerTrans> Instrumented<InnerTrans> {
+    /// Wraps around a `Transport` to record the time taken by its connection
+    /// setups.
+    pub fn new(trans: InnerTrans) -> Self {
+        Instrumented {
+            inner: trans,
+            timings: Arc::new(Mutex::new(Timings::default())),
+        }
+    }
+
+    /// Returns a snapshot of the timings recorded so far.
+    pub fn timings(&self) -> Timings {
+        self.timings.lock().clone()
+    }
+}
+
+impl<InnerTrans> Transport for Instrumented<InnerTrans>
+where
+    InnerTrans: Transport,
+{
+    type Output = InnerTrans::Output;
+    type Error = InnerTrans::Error;
+    type Listener = InstrumentedListener<InnerTrans::Listener>;
+    type ListenerUpgrade = InstrumentedFuture<InnerTrans::ListenerUpgrade>;
+    type Dial = InstrumentedFuture<InnerTrans::Dial>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let listener = self.inner.listen_on(addr)?;
+        Ok(InstrumentedListener {
+            inner: listener,
+            timings: self.timings,
+        })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let dial = self.inner.dial(addr)?;
+        Ok(InstrumentedFuture::new(dial, Endpoint::Dialer, self.timings))
+    }
+
+    fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.inner.address_translation(server, observed)
+    }
+}
+
+/// Timings of the connection setups of an [`Instrumented`] transport.
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    dial: Histogram,
+    listener_upgrade: Histogram,
+}
+
+impl Timings {
+    /// Durations of successful dials, from calling [`Transport::dial`] until
+    /// the dial future resolved.
+    pub fn dial(&self) -> &Histogram {
+        &self.dial
+    }
+
+    /// Durations of successful listener upgrades, from the listener producing
+    /// a [`ListenerEvent::Upgrade`] until the upgrade future resolved.
+    pub fn listener_upgrade(&self) -> &Histogram {
+        &self.listener_upgrade
+    }
+
+    fn histogram_mut(&mut self, endpoint: Endpoint) -> &mut Histogram {
+        match endpoint {
+            Endpoint::Dialer => &mut self.dial,
+            Endpoint::Listener => &mut self.listener_upgrade,
+        }
+    }
+}
+
+/// A histogram of durations.
+///
+/// Samples are counted in buckets whose upper bounds double from one bucket
+/// to the next, starting at one millisecond.
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    buckets: [u64; NUM_BUCKETS],
+    count: u64,
+    sum: Duration,
+}
+
+impl Histogram {
+    /// The number of recorded samples.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The sum of all recorded samples.
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Iterates over the buckets of the histogram, yielding the inclusive
+    /// upper bound of each bucket together with the number of samples in it.
+    ///
+    /// The last bucket has no upper bound.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        self.buckets.iter().enumerate().map(|(i, n)| (Self::upper_bound(i), *n))
+    }
+
+    fn record(&mut self, sample: Duration) {
+        let bucket = (0 .. NUM_BUCKETS - 1)
+            .find(|&i| sample <= Duration::from_millis(1 << i))
+            .unwrap_or(NUM_BUCKETS - 1);
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum += sample;
+    }
+
+    fn upper_bound(bucket: usize) -> Option<Duration> {
+        if bucket + 1 < NUM_BUCKETS {
+            Some(Duration::from_millis(1 << bucket))
+        } else {
+            None
+        }
+    }
+}
+
+// TODO: can be removed and replaced with an `impl Stream` once impl Trait is fully stable
+//       in Rust (https://github.com/rust-lang/rust/issues/34511)
+#[pin_project::pin_project]
+pub struct InstrumentedListener<InnerStream> {
+    #[pin]
+    inner: InnerStream,
+    timings: Arc<Mutex<Timings>>,
+}
+
+impl<InnerStream, O, E> Stream for InstrumentedListener<InnerStream>
+where
+    InnerStream: Stream<Item = Result<ListenerEvent<O, E>, E>>,
+{
+    type Item = Result<ListenerEvent<InstrumentedFuture<O>, E>, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        let event = match this.inner.poll_next(cx) {
+            Poll::Ready(Some(Ok(event))) => event,
+            Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let timings = this.timings.clone();
+        let event = event.map(move |upgrade| {
+            InstrumentedFuture::new(upgrade, Endpoint::Listener, timings)
+        });
+
+        Poll::Ready(Some(Ok(event)))
+    }
+}
+
+/// Wraps around a connection setup `Future`, recording the time it took to
+/// resolve successfully.
+// TODO: can be replaced with `impl Future` once `impl Trait` are fully stable in Rust
+//       (https://github.com/rust-lang/rust/issues/34511)
+#[pin_project::pin_project]
+#[must_use = "futures do nothing unless polled"]
+pub struct InstrumentedFuture<InnerFut> {
+    #[pin]
+    inner: InnerFut,
+    endpoint: Endpoint,
+    started: Instant,
+    timings: Arc<Mutex<Timings>>,
+}
+
+impl<InnerFut> InstrumentedFuture<InnerFut> {
+    fn new(inner: InnerFut, endpoint: Endpoint, timings: Arc<Mutex<Timings>>) -> Self {
+        InstrumentedFuture {
+            inner,
+            endpoint,
+            started: Instant::now(),
+            timings,
+        }
+    }
+}
+
+impl<InnerFut, O, E> Future for InstrumentedFuture<InnerFut>
+where
+    InnerFut: Future<Output = Result<O, E>>,
+{
+    type Output = Result<O, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        let output = futures::ready!(this.inner.poll(cx));
+        if output.is_ok() {
+            this.timings.lock()
+                .histogram_mut(*this.endpoint)
+                .record(this.started.elapsed());
+        }
+
+        Poll::Ready(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MemoryTransport;
+
+    #[test]
+    fn records_dial_and_listener_upgrade() {
+        let addr: Multiaddr = format!("/memory/{}", rand::random::<u64>().saturating_add(1))
+            .parse()
+            .unwrap();
+
+        let listener_transport = Instrumented::new(MemoryTransport::default());
+        let dialer_transport = Instrumented::new(MemoryTransport::default());
+
+        let mut listener = listener_transport.clone().listen_on(addr.clone()).unwrap();
+        let listen = async move {
+            while let Some(event) = listener.next().await {
+                if let ListenerEvent::Upgrade { upgrade, .. } = event.unwrap() {
+                    upgrade.await.unwrap();
+                    return;
+                }
+            }
+        };
+        let dial = dialer_transport.clone().dial(addr).unwrap();
+
+        futures::executor::block_on(future::join(listen, dial)).1.unwrap();
+
+        let timings = dialer_transport.timings();
+        assert_eq!(timings.dial().count(), 1);
+        assert_eq!(timings.dial().buckets().map(|(_, n)| n).sum::<u64>(), 1);
+        assert_eq!(timings.listener_upgrade().count(), 0);
+
+        let timings = listener_transport.timings();
+        assert_eq!(timings.dial().count(), 0);
+        assert_eq!(timings.listener_upgrade().count(), 1);
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        let mut histogram = Histogram::default();
+        histogram.record(Duration::from_micros(500));
+        histogram.record(Duration::from_millis(3));
+        histogram.record(Duration::from_secs(3600));
+
+        let buckets = histogram.buckets().collect::<Vec<_>>();
+        assert_eq!(buckets.len(), NUM_BUCKETS);
+        assert_eq!(buckets[0], (Some(Duration::from_millis(1)), 1));
+        assert_eq!(buckets[2], (Some(Duration::from_millis(4)), 1));
+        assert_eq!(buckets[NUM_BUCKETS - 1], (None, 1));
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.sum(), Duration::from_micros(3600_003_500));
+    }
+}
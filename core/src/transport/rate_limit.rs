@@ -0,0 +1,199 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Transports limiting the rate at which upgrades of incoming connections
+//! are begun.
+//!
+//! Listener upgrades, e.g. the security handshake, only make progress when
+//! they are polled. Incoming connections exceeding the rate are dropped
+//! before their upgrade is polled, so a `HandshakeRateLimit` can be applied
+//! on top of a fully upgraded transport.
+
+use crate::{
+    ConnectedPoint,
+    Multiaddr,
+    Transport,
+    transport::{TransportError, filter::{AllowConnection, Filter, FilterError, FilterFuture, FilterListener}},
+};
+use parking_lot::Mutex;
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+use wasm_timer::Instant;
+
+/// The window within which at most the configured number of listener
+/// upgrades are begun.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// A `HandshakeRateLimit` is a `Transport` that wraps another `Transport`
+/// and rejects incoming connections with [`FilterError::Rejected`] once more
+/// than a given number of listener upgrades were begun within the last second.
+///
+/// Clones of a `HandshakeRateLimit` transport share the same limit, which
+/// therefore applies across all listeners of the transport.
+#[derive(Debug, Clone)]
+pub struct HandshakeRateLimit<InnerTrans> {
+    inner: Filter<InnerTrans, HandshakeRateLimiter>,
+}
+
+impl<InnerTrans> HandshakeRateLimit<InnerTrans> {
+    /// Wraps around a `Transport` to begin at most `max_per_second` listener
+    /// upgrades per second.
+    pub fn new(trans: InnerTrans, max_per_second: u32) -> Self {
+        let limiter = HandshakeRateLimiter {
+            inner: Arc::new(Mutex::new(RateLimiter::new(max_per_second))),
+        };
+        HandshakeRateLimit {
+            inner: Filter::new(trans, limiter),
+        }
+    }
+}
+
+impl<InnerTrans> Transport for HandshakeRateLimit<InnerTrans>
+where
+    InnerTrans: Transport,
+    InnerTrans::Error: 'static,
+{
+    type Output = InnerTrans::Output;
+    type Error = FilterError<InnerTrans::Error>;
+    type Listener = FilterListener<InnerTrans::Listener, HandshakeRateLimiter>;
+    type ListenerUpgrade = FilterFuture<InnerTrans::ListenerUpgrade>;
+    type Dial = FilterFuture<InnerTrans::Dial>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        self.inner.listen_on(addr)
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.inner.dial(addr)
+    }
+
+    fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.inner.address_translation(server, observed)
+    }
+}
+
+/// The [`AllowConnection`] of a [`HandshakeRateLimit`], allowing all dials
+/// and incoming connections within the rate limit.
+#[derive(Debug, Clone)]
+pub struct HandshakeRateLimiter {
+    inner: Arc<Mutex<RateLimiter>>,
+}
+
+impl AllowConnection for HandshakeRateLimiter {
+    fn allow(&self, point: &ConnectedPoint) -> bool {
+        match point {
+            ConnectedPoint::Dialer { .. } => true,
+            ConnectedPoint::Listener { send_back_addr, .. } => {
+                if self.inner.lock().try_start(Instant::now()) {
+                    return true
+                }
+                log::debug!("Rate limit reached for incoming connection from {}.", send_back_addr);
+                false
+            }
+        }
+    }
+}
+
+/// Keeps track of the listener upgrades begun within the last [`WINDOW`].
+#[derive(Debug)]
+struct RateLimiter {
+    max_per_window: u32,
+    started: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    fn new(max_per_window: u32) -> Self {
+        RateLimiter {
+            max_per_window,
+            started: VecDeque::new(),
+        }
+    }
+
+    /// Records the beginning of an upgrade, returning `false` without
+    /// recording it if the limit is reached.
+    fn try_start(&mut self, now: Instant) -> bool {
+        while let Some(started) = self.started.front() {
+            if now.duration_since(*started) < WINDOW {
+                break
+            }
+            self.started.pop_front();
+        }
+        if self.started.len() >= self.max_per_window as usize {
+            return false
+        }
+        self.started.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{ListenerEvent, MemoryTransport};
+    use futures::prelude::*;
+    use std::{io, sync::atomic::{AtomicUsize, Ordering}};
+
+    #[test]
+    fn caps_upgrades_per_second() {
+        let upgrades = Arc::new(AtomicUsize::new(0));
+        let transport = {
+            let upgrades = upgrades.clone();
+            let inner = MemoryTransport::default()
+                .and_then(move |conn, _| {
+                    upgrades.fetch_add(1, Ordering::SeqCst);
+                    future::ok::<_, io::Error>(conn)
+                });
+            HandshakeRateLimit::new(inner, 2)
+        };
+
+        let addr: Multiaddr = format!("/memory/{}", rand::random::<u64>().saturating_add(1))
+            .parse().unwrap();
+        let mut listener = transport.listen_on(addr.clone()).unwrap();
+        let dials = (0..5)
+            .map(|_| MemoryTransport::default().dial(addr.clone()).unwrap())
+            .collect::<Vec<_>>();
+
+        let listen = async move {
+            let mut results = Vec::new();
+            while results.len() < 5 {
+                let event = listener.next().await.expect("Listener closed.").unwrap();
+                if let ListenerEvent::Upgrade { upgrade, .. } = event {
+                    results.push(upgrade.await)
+                }
+            }
+            results
+        };
+        let (results, _) = futures::executor::block_on(future::join(listen, future::join_all(dials)));
+
+        let rejected = results.iter()
+            .filter(|r| matches!(r, Err(FilterError::Rejected)))
+            .count();
+        assert_eq!(rejected, 3);
+        assert_eq!(upgrades.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn limit_recovers_after_window() {
+        let mut limiter = RateLimiter::new(1);
+        let now = Instant::now();
+        assert!(limiter.try_start(now));
+        assert!(!limiter.try_start(now + WINDOW / 2));
+        assert!(limiter.try_start(now + WINDOW));
+    }
+}
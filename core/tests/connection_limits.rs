@@ -29,7 +29,7 @@ use libp2p_core::{
 };
 use rand::Rng;
 use std::task::Poll;
-use util::{TestHandler, test_network};
+use util::{TestHandler, TestNetwork, test_network};
 
 #[test]
 fn max_outgoing() {
@@ -84,9 +84,32 @@ fn max_established_incoming() {
         NetworkConfig::default().with_connection_limits(limits)
     }
 
-    let mut network1 = test_network(config(limit));
-    let mut network2 = test_network(config(limit));
+    let network1 = test_network(config(limit));
+    let network2 = test_network(config(limit));
 
+    exceed_listener_limit(network1, network2, limit);
+}
+
+#[test]
+fn max_established_total() {
+    let limit = rand::thread_rng().gen_range(1, 10);
+
+    fn config(limit: u32) -> NetworkConfig {
+        let limits = ConnectionLimits::default().with_max_established_total(Some(limit));
+        NetworkConfig::default().with_connection_limits(limits)
+    }
+
+    // Only the listener is limited, since the dialer would otherwise close
+    // the connection exceeding the limit itself.
+    let network1 = test_network(config(limit));
+    let network2 = test_network(NetworkConfig::default());
+
+    exceed_listener_limit(network1, network2, limit);
+}
+
+/// Dials `network1` from `network2` until `network1` refuses an incoming
+/// connection exceeding its limit of `limit` established connections.
+fn exceed_listener_limit(mut network1: TestNetwork, mut network2: TestNetwork, limit: u32) {
     let listen_addr = multiaddr![Ip4(std::net::Ipv4Addr::new(127,0,0,1)), Tcp(0u16)];
     let _ = network1.listen_on(listen_addr.clone()).unwrap();
     let (addr_sender, addr_receiver) = futures::channel::oneshot::channel();
@@ -134,7 +157,7 @@ fn max_established_incoming() {
                             // Dial again until the limit is exceeded.
                             let id = network2.dial(&addr, TestHandler()).unwrap();
                             if n == limit {
-                                // The the next dialing attempt exceeds the limit, this
+                                // The next dialing attempt exceeds the limit, this
                                 // is the connection we expected to get closed.
                                 expected_closed = Some(id);
                             }
@@ -163,4 +186,3 @@ fn max_established_incoming() {
     // Wait for the listener to complete.
     async_std::task::block_on(listener);
 }
-
@@ -35,7 +35,7 @@ use libp2p_swarm::{
 };
 use log::warn;
 use smallvec::SmallVec;
-use std::{collections::VecDeque, iter};
+use std::{collections::VecDeque, error, fmt, iter};
 use std::collections::hash_map::{DefaultHasher, HashMap};
 use std::task::{Context, Poll};
 
@@ -58,6 +58,14 @@ pub struct Floodsub {
     // erroneously.
     subscribed_topics: SmallVec<[Topic; 16]>,
 
+    // List of topic prefixes we're subscribed to. Topics with one of these prefixes that connected
+    // peers are subscribed to are added to `subscribed_topics`.
+    subscribed_prefixes: SmallVec<[String; 4]>,
+
+    // List of topics we explicitly unsubscribed from. These are not subscribed to again because
+    // they match one of our prefixes.
+    unsubscribed_topics: FnvHashSet<Topic>,
+
     // We keep track of the messages we received (in the format `hash(source ID, seq_no)`) so that
     // we don't dispatch the same message twice if we receive it twice on the network.
     received: CuckooFilter<DefaultHasher>,
@@ -77,6 +85,8 @@ impl Floodsub {
             target_peers: FnvHashSet::default(),
             connected_peers: HashMap::new(),
             subscribed_topics: SmallVec::new(),
+            subscribed_prefixes: SmallVec::new(),
+            unsubscribed_topics: FnvHashSet::default(),
             received: CuckooFilter::new(),
         }
     }
@@ -118,6 +128,7 @@ impl Floodsub {
     ///
     /// Returns true if the subscription worked. Returns false if we were already subscribed.
     pub fn subscribe(&mut self, topic: Topic) -> bool {
+        self.unsubscribed_topics.remove(&topic);
         if self.subscribed_topics.iter().any(|t| t.id() == topic.id()) {
             return false;
         }
@@ -140,6 +151,48 @@ impl Floodsub {
         true
     }
 
+    /// Subscribes to all topics whose id starts with `prefix`, e.g. to all topics of a
+    /// hierarchical namespace.
+    ///
+    /// As messages are only sent to peers subscribed to their topic, this subscribes to every
+    /// matching topic a connected peer is or later gets subscribed to. These topics remain
+    /// subscribed until [`Floodsub::unsubscribe`] is called for them, after which they are no
+    /// longer subscribed to through any prefix unless [`Floodsub::subscribe`] is called for them.
+    ///
+    /// Returns true if the subscription worked. Returns false if we were already subscribed.
+    pub fn subscribe_prefix(&mut self, prefix: impl Into<String>) -> bool {
+        let prefix = prefix.into();
+        if self.subscribed_prefixes.contains(&prefix) {
+            return false;
+        }
+
+        let topics = self.connected_peers.values()
+            .flatten()
+            .filter(|t| t.id().starts_with(&prefix) && !self.unsubscribed_topics.contains(t))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.subscribed_prefixes.push(prefix);
+        for topic in topics {
+            self.subscribe(topic);
+        }
+        true
+    }
+
+    /// Stops subscribing to topics with the given prefix that peers subscribe to from now on.
+    ///
+    /// Topics already subscribed to through the prefix remain subscribed.
+    ///
+    /// Returns true if we were subscribed to this prefix.
+    pub fn unsubscribe_prefix(&mut self, prefix: &str) -> bool {
+        match self.subscribed_prefixes.iter().position(|p| p == prefix) {
+            Some(pos) => {
+                self.subscribed_prefixes.remove(pos);
+                true
+            }
+            None => false
+        }
+    }
+
     /// Unsubscribes from a topic.
     ///
     /// Note that this only requires the topic name.
@@ -152,6 +205,7 @@ impl Floodsub {
         };
 
         self.subscribed_topics.remove(pos);
+        self.unsubscribed_topics.insert(topic.clone());
 
         for peer in self.connected_peers.keys() {
             self.events.push_back(NetworkBehaviourAction::NotifyHandler {
@@ -175,6 +229,18 @@ impl Floodsub {
         self.publish_many(iter::once(topic), data)
     }
 
+    /// Publishes a message to the network, if we're subscribed to the topic only.
+    ///
+    /// Returns the number of remotes the message has been queued for, which is zero if no
+    /// connected peer is subscribed to the topic.
+    pub fn try_publish(
+        &mut self,
+        topic: impl Into<Topic>,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<usize, PublishError> {
+        self.try_publish_many(iter::once(topic), data)
+    }
+
     /// Publishes a message to the network, even if we're not subscribed to the topic.
     pub fn publish_any(&mut self, topic: impl Into<Topic>, data: impl Into<Vec<u8>>) {
         self.publish_many_any(iter::once(topic), data)
@@ -185,15 +251,33 @@ impl Floodsub {
     ///
     /// > **Note**: Doesn't do anything if we're not subscribed to any of the topics.
     pub fn publish_many(&mut self, topic: impl IntoIterator<Item = impl Into<Topic>>, data: impl Into<Vec<u8>>) {
+        let _ = self.publish_many_inner(topic, data, true);
+    }
+
+    /// Publishes a message with multiple topics to the network, if we're subscribed to any of
+    /// the topics.
+    ///
+    /// Returns the number of remotes the message has been queued for, which is zero if no
+    /// connected peer is subscribed to any of the topics.
+    pub fn try_publish_many(
+        &mut self,
+        topic: impl IntoIterator<Item = impl Into<Topic>>,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<usize, PublishError> {
         self.publish_many_inner(topic, data, true)
     }
 
     /// Publishes a message with multiple topics to the network, even if we're not subscribed to any of the topics.
     pub fn publish_many_any(&mut self, topic: impl IntoIterator<Item = impl Into<Topic>>, data: impl Into<Vec<u8>>) {
-        self.publish_many_inner(topic, data, false)
+        let _ = self.publish_many_inner(topic, data, false);
     }
 
-    fn publish_many_inner(&mut self, topic: impl IntoIterator<Item = impl Into<Topic>>, data: impl Into<Vec<u8>>, check_self_subscriptions: bool) {
+    fn publish_many_inner(
+        &mut self,
+        topic: impl IntoIterator<Item = impl Into<Topic>>,
+        data: impl Into<Vec<u8>>,
+        check_self_subscriptions: bool,
+    ) -> Result<usize, PublishError> {
         let message = FloodsubMessage {
             source: self.config.local_peer_id,
             data: data.into(),
@@ -220,15 +304,17 @@ impl Floodsub {
         // Don't publish the message if we have to check subscriptions
         // and we're not subscribed ourselves to any of the topics.
         if check_self_subscriptions && !self_subscribed {
-            return
+            return Err(PublishError::NotSubscribed)
         }
 
         // Send to peers we know are subscribed to the topic.
+        let mut remotes = 0;
         for (peer_id, sub_topic) in self.connected_peers.iter() {
             if !sub_topic.iter().any(|t| message.topics.iter().any(|u| t == u)) {
                 continue;
             }
 
+            remotes += 1;
             self.events.push_back(NetworkBehaviourAction::NotifyHandler {
                 peer_id: *peer_id,
                 handler: NotifyHandler::Any,
@@ -238,9 +324,28 @@ impl Floodsub {
                 }
             });
         }
+
+        Ok(remotes)
+    }
+}
+
+/// The possible failures of [`Floodsub::try_publish`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishError {
+    /// The local node isn't subscribed to any of the topics of the message.
+    NotSubscribed,
+}
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishError::NotSubscribed => write!(f, "Not subscribed to any of the topics"),
+        }
     }
 }
 
+impl error::Error for PublishError {}
+
 impl NetworkBehaviour for Floodsub {
     type ProtocolsHandler = OneShotHandler<FloodsubProtocol, FloodsubRpc, InnerMessage>;
     type OutEvent = FloodsubEvent;
@@ -300,6 +405,9 @@ impl NetworkBehaviour for Floodsub {
             InnerMessage::Sent => return,
         };
 
+        // Topics to subscribe to because they match one of our prefixes.
+        let mut matching_topics = Vec::new();
+
         // Update connected peers topics
         for subscription in event.subscriptions {
             let remote_peer_topics = self.connected_peers
@@ -310,6 +418,11 @@ impl NetworkBehaviour for Floodsub {
                     if !remote_peer_topics.contains(&subscription.topic) {
                         remote_peer_topics.push(subscription.topic.clone());
                     }
+                    if self.subscribed_prefixes.iter().any(|p| subscription.topic.id().starts_with(p.as_str()))
+                        && !self.unsubscribed_topics.contains(&subscription.topic)
+                    {
+                        matching_topics.push(subscription.topic.clone());
+                    }
                     self.events.push_back(NetworkBehaviourAction::GenerateEvent(FloodsubEvent::Subscribed {
                         peer_id: propagation_source,
                         topic: subscription.topic,
@@ -327,6 +440,10 @@ impl NetworkBehaviour for Floodsub {
             }
         }
 
+        for topic in matching_topics {
+            self.subscribe(topic);
+        }
+
         // List of messages we're going to propagate on the network.
         let mut rpcs_to_dispatch: Vec<(PeerId, FloodsubRpc)> = Vec::new();
 
@@ -442,3 +559,91 @@ pub enum FloodsubEvent {
         topic: Topic,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect_peer(floodsub: &mut Floodsub, topics: &[&Topic]) -> PeerId {
+        let peer_id = PeerId::random();
+        floodsub.inject_connected(&peer_id);
+        let rpc = FloodsubRpc {
+            messages: Vec::new(),
+            subscriptions: topics.iter()
+                .map(|topic| FloodsubSubscription {
+                    topic: (*topic).clone(),
+                    action: FloodsubSubscriptionAction::Subscribe,
+                })
+                .collect(),
+        };
+        floodsub.inject_event(peer_id, ConnectionId::new(0), InnerMessage::Rx(rpc));
+        peer_id
+    }
+
+    #[test]
+    fn try_publish_requires_subscription() {
+        let mut floodsub = Floodsub::new(PeerId::random());
+        let topic = Topic::new("topic");
+        connect_peer(&mut floodsub, &[&topic]);
+
+        assert_eq!(floodsub.try_publish(topic.clone(), vec![1]), Err(PublishError::NotSubscribed));
+        assert_eq!(
+            floodsub.try_publish_many(vec![topic, Topic::new("other")], vec![1]),
+            Err(PublishError::NotSubscribed)
+        );
+    }
+
+    #[test]
+    fn try_publish_returns_number_of_remotes() {
+        let mut floodsub = Floodsub::new(PeerId::random());
+        let topic = Topic::new("topic");
+        let other = Topic::new("other");
+        assert!(floodsub.subscribe(topic.clone()));
+
+        assert_eq!(floodsub.try_publish(topic.clone(), vec![1]), Ok(0));
+
+        connect_peer(&mut floodsub, &[&topic]);
+        connect_peer(&mut floodsub, &[&topic, &other]);
+        connect_peer(&mut floodsub, &[&other]);
+        connect_peer(&mut floodsub, &[]);
+
+        assert_eq!(floodsub.try_publish(topic.clone(), vec![2]), Ok(2));
+        assert_eq!(floodsub.try_publish_many(vec![topic, other], vec![3]), Ok(3));
+    }
+
+    #[test]
+    fn subscribe_prefix_subscribes_matching_topics() {
+        let mut floodsub = Floodsub::new(PeerId::random());
+        let known = Topic::new("chat/known");
+        let unrelated = Topic::new("news/known");
+        connect_peer(&mut floodsub, &[&known, &unrelated]);
+
+        assert!(floodsub.subscribe_prefix("chat/"));
+        assert!(!floodsub.subscribe_prefix("chat/"));
+        assert_eq!(floodsub.subscribed_topics.len(), 1);
+        assert_eq!(floodsub.subscribed_topics[0], known);
+
+        // Topics peers subscribe to later on are subscribed to as well.
+        let later = Topic::new("chat/later");
+        connect_peer(&mut floodsub, &[&later, &Topic::new("news/later")]);
+        assert_eq!(floodsub.subscribed_topics.as_slice(), &[known, later]);
+    }
+
+    #[test]
+    fn subscribe_prefix_skips_unsubscribed_topics() {
+        let mut floodsub = Floodsub::new(PeerId::random());
+        let topic = Topic::new("chat/topic");
+        assert!(floodsub.subscribe_prefix("chat/"));
+        connect_peer(&mut floodsub, &[&topic]);
+        assert!(floodsub.unsubscribe(topic.clone()));
+
+        // Neither a re-announcement nor a new prefix subscribes to the topic again.
+        connect_peer(&mut floodsub, &[&topic]);
+        assert!(floodsub.subscribe_prefix("chat/to"));
+        assert!(floodsub.subscribed_topics.is_empty());
+
+        // Explicitly subscribing to the topic is still possible.
+        assert!(floodsub.subscribe(topic.clone()));
+        assert_eq!(floodsub.subscribed_topics.as_slice(), &[topic]);
+    }
+}
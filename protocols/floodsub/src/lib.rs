@@ -32,7 +32,7 @@ mod rpc_proto {
     include!(concat!(env!("OUT_DIR"), "/floodsub.pb.rs"));
 }
 
-pub use self::layer::{Floodsub, FloodsubEvent};
+pub use self::layer::{Floodsub, FloodsubEvent, PublishError};
 pub use self::protocol::{FloodsubMessage, FloodsubRpc};
 pub use self::topic::Topic;
 
@@ -22,9 +22,10 @@ use std::{
     cmp::{max, Ordering},
     collections::HashSet,
     collections::VecDeque,
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     fmt,
     net::IpAddr,
+    num::NonZeroUsize,
     sync::Arc,
     task::{Context, Poll},
     time::Duration,
@@ -46,7 +47,7 @@ use libp2p_swarm::{
 };
 
 use crate::backoff::BackoffStorage;
-use crate::config::{GossipsubConfig, ValidationMode};
+use crate::config::{GossipsubConfig, GossipsubConfigBuilder, GossipsubParams, ValidationMode};
 use crate::error::{PublishError, SubscriptionError, ValidationError};
 use crate::gossip_promises::GossipPromises;
 use crate::handler::{GossipsubHandler, GossipsubHandlerIn, HandlerEvent};
@@ -139,6 +140,46 @@ pub enum GossipsubEvent {
         /// The topic it has subscribed from.
         topic: TopicHash,
     },
+    /// A message published through [`Gossipsub::publish_acknowledged`] has been acknowledged by
+    /// the requested number of peers, or the
+    /// [`GossipsubConfig::acknowledgement_timeout`] elapsed before.
+    PublishAcknowledged {
+        /// The [`MessageId`] of the published message.
+        message_id: MessageId,
+        /// The peers that acknowledged the message.
+        acks: Vec<PeerId>,
+    },
+    /// The last peer has been removed from the mesh of a subscribed topic, e.g. because it
+    /// pruned us, disconnected or was dropped for its score during a heartbeat.
+    ///
+    /// Reported once per transition to an empty mesh.
+    MeshEmpty {
+        /// The topic whose mesh is empty.
+        topic: TopicHash,
+    },
+    /// A message published while the mesh of its topic was forming has been dropped, because no
+    /// peers could be found to send it to once the [`GossipsubConfig::publish_grace_period`]
+    /// ended, or because we unsubscribed from the topic.
+    PublishDropped {
+        /// The [`MessageId`] of the dropped message.
+        message_id: MessageId,
+        /// The topic the message was published on.
+        topic: TopicHash,
+    },
+}
+
+/// A message published through [`Gossipsub::publish_acknowledged`] awaiting acknowledgements.
+struct PendingAcknowledgement {
+    /// The topic the message was published on.
+    topic_hash: TopicHash,
+    /// The number of acknowledgements after which the publish is reported as acknowledged.
+    required: usize,
+    /// The peers we requested an acknowledgement from.
+    requested: HashSet<PeerId>,
+    /// The peers that acknowledged the message.
+    acks: Vec<PeerId>,
+    /// The time at which the publish is reported regardless of the acknowledgements received.
+    deadline: Instant,
 }
 
 /// A data structure for storing configuration for publishing messages. See [`MessageAuthenticity`]
@@ -216,6 +257,10 @@ pub struct Gossipsub<
     /// Events that need to be yielded to the outside when polling.
     events: VecDeque<GossipsubNetworkBehaviourAction>,
 
+    /// RPCs carrying messages of topics with a send priority, queued per priority and yielded
+    /// before the `events`. See [`GossipsubConfig::topic_priorities`].
+    prioritized_events: BTreeMap<u8, VecDeque<GossipsubNetworkBehaviourAction>>,
+
     /// Pools non-urgent control messages between heartbeats.
     control_pool: HashMap<PeerId, Vec<GossipsubControlAction>>,
 
@@ -244,6 +289,9 @@ pub struct Gossipsub<
     /// Messages are not sent to and are rejected from these peers.
     blacklisted_peers: HashSet<PeerId>,
 
+    /// The id of the local peer, learned when the behaviour is first polled.
+    local_peer_id: Option<PeerId>,
+
     /// Overlay network of connected peers - Maps topics to connected gossipsub peers.
     mesh: HashMap<TopicHash, BTreeSet<PeerId>>,
 
@@ -286,16 +334,39 @@ pub struct Gossipsub<
     /// Counts the number of `IWANT` that we sent the each peer since the last heartbeat.
     count_sent_iwant: HashMap<PeerId, usize>,
 
+    /// Counts the number of messages we sent to each peer in response to `IWANT` since the last
+    /// heartbeat.
+    count_served_iwant: HashMap<PeerId, usize>,
+
     /// Short term cache for published messsage ids. This is used for penalizing peers sending
     /// our own messages back if the messages are anonymous or use a random author.
     published_message_ids: DuplicateCache<MessageId>,
 
+    /// The times at which we published messages on each topic within the current
+    /// [`GossipsubConfig::publish_rate_limit`] interval.
+    published_times: HashMap<TopicHash, VecDeque<Instant>>,
+
+    /// Deadlines until which messages published on freshly subscribed topics are held back while
+    /// their mesh is forming. See [`GossipsubConfig::publish_grace_period`].
+    publish_grace_deadlines: HashMap<TopicHash, Instant>,
+
+    /// Messages published by the local node that are held back until the mesh of their topic has
+    /// formed.
+    pending_publishes: HashMap<TopicHash, Vec<(MessageId, RawGossipsubMessage)>>,
+
+    /// Messages published through [`Gossipsub::publish_acknowledged`] awaiting acknowledgements.
+    pending_acknowledgements: HashMap<MessageId, PendingAcknowledgement>,
+
     /// Short term cache for fast message ids mapping them to the real message ids
     fast_messsage_id_cache: TimeCache<FastMessageId, MessageId>,
 
     /// The filter used to handle message subscriptions.
     subscription_filter: F,
 
+    /// Matchers added through [`Gossipsub::subscribe_pattern`]. Topics advertised by peers that
+    /// match any of them are subscribed to automatically.
+    subscription_patterns: Vec<Box<dyn Fn(&TopicHash) -> bool + Send>>,
+
     /// A general transformation function that can be applied to data received from the wire before
     /// calculating the message-id and sending to the application. This is designed to allow the
     /// user to implement arbitrary topic-based compression algorithms.
@@ -387,6 +458,7 @@ where
 
         Ok(Gossipsub {
             events: VecDeque::new(),
+            prioritized_events: BTreeMap::new(),
             control_pool: HashMap::new(),
             publish_config: privacy.into(),
             duplicate_cache: DuplicateCache::new(config.duplicate_cache_time()),
@@ -395,6 +467,7 @@ where
             peer_topics: HashMap::new(),
             explicit_peers: HashSet::new(),
             blacklisted_peers: HashSet::new(),
+            local_peer_id: None,
             mesh: HashMap::new(),
             fanout: HashMap::new(),
             fanout_last_pub: HashMap::new(),
@@ -414,10 +487,16 @@ where
             peer_score: None,
             count_received_ihave: HashMap::new(),
             count_sent_iwant: HashMap::new(),
+            count_served_iwant: HashMap::new(),
             connected_peers: HashMap::new(),
             published_message_ids: DuplicateCache::new(config.published_message_ids_cache_time()),
+            published_times: HashMap::new(),
+            publish_grace_deadlines: HashMap::new(),
+            pending_publishes: HashMap::new(),
+            pending_acknowledgements: HashMap::new(),
             config,
             subscription_filter,
+            subscription_patterns: Vec::new(),
             data_transform,
         })
     }
@@ -451,6 +530,38 @@ where
         res.into_iter()
     }
 
+    /// Returns a snapshot of the mesh peers of all topics we are subscribed to, e.g. for
+    /// visualizing the overlay.
+    pub fn mesh_snapshot(&self) -> HashMap<TopicHash, Vec<PeerId>> {
+        self.mesh
+            .iter()
+            .map(|(topic, peers)| (topic.clone(), peers.iter().cloned().collect()))
+            .collect()
+    }
+
+    /// Returns a snapshot of the fanout peers of all topics we publish to without being
+    /// subscribed.
+    pub fn fanout_snapshot(&self) -> HashMap<TopicHash, Vec<PeerId>> {
+        self.fanout
+            .iter()
+            .map(|(topic, peers)| (topic.clone(), peers.iter().cloned().collect()))
+            .collect()
+    }
+
+    /// Lists the ids of all messages currently held in the message cache, i.e. messages that are
+    /// served to peers requesting them through IWANT.
+    pub fn cached_message_ids(&self) -> Vec<MessageId> {
+        self.mcache.message_ids().cloned().collect()
+    }
+
+    /// Returns the message with the given id from the message cache, if present.
+    ///
+    /// The message is returned in the form it is sent on the wire, i.e. before the inbound
+    /// [`DataTransform`] is applied to it.
+    pub fn get_cached_message(&self, message_id: &MessageId) -> Option<&RawGossipsubMessage> {
+        self.mcache.get(message_id)
+    }
+
     /// Lists all known peers and their associated subscribed topics.
     pub fn all_peers(&self) -> impl Iterator<Item = (&PeerId, Vec<&TopicHash>)> {
         self.peer_topics
@@ -458,6 +569,13 @@ where
             .map(|(peer_id, topic_set)| (peer_id, topic_set.iter().collect()))
     }
 
+    /// Lists the topics a peer is subscribed to, or `None` if the peer is unknown.
+    pub fn peer_topics(&self, peer_id: &PeerId) -> Option<Vec<TopicHash>> {
+        self.peer_topics
+            .get(peer_id)
+            .map(|topic_set| topic_set.iter().cloned().collect())
+    }
+
     /// Lists all known peers and their associated protocol.
     pub fn peer_protocol(&self) -> impl Iterator<Item = (&PeerId, &PeerKind)> {
         self.connected_peers.iter().map(|(k, v)| (k, &v.kind))
@@ -475,14 +593,56 @@ where
     /// Returns [`Ok(true)`] if the subscription worked. Returns [`Ok(false)`] if we were already
     /// subscribed.
     pub fn subscribe<H: Hasher>(&mut self, topic: &Topic<H>) -> Result<bool, SubscriptionError> {
-        debug!("Subscribing to topic: {}", topic);
-        let topic_hash = topic.hash();
+        self.subscribe_topic_hash(topic.hash())
+    }
+
+    /// Subscribes to all topics advertised by peers that match the given `matcher`, including
+    /// those already advertised, and delivers their messages like those of any other subscribed
+    /// topic.
+    ///
+    /// This is intended for monitoring, e.g. to observe all topics of an application without
+    /// enumerating them, rather than for regular use. Topics subscribed to this way are subject
+    /// to the subscription filter and remain subscribed until [`Gossipsub::unsubscribe`] is
+    /// called for them.
+    pub fn subscribe_pattern(&mut self, matcher: Box<dyn Fn(&TopicHash) -> bool + Send>) {
+        let topics = self
+            .topic_peers
+            .iter()
+            .filter(|(topic_hash, peers)| !peers.is_empty() && matcher(topic_hash))
+            .map(|(topic_hash, _)| topic_hash.clone())
+            .collect::<Vec<_>>();
+        self.subscription_patterns.push(matcher);
+        for topic_hash in topics {
+            self.subscribe_matching_topic(topic_hash);
+        }
+    }
+
+    /// Subscribes to a topic advertised by a peer if it matches a pattern added through
+    /// [`Gossipsub::subscribe_pattern`].
+    fn subscribe_matching_topic(&mut self, topic_hash: TopicHash) {
+        if self.mesh.contains_key(&topic_hash)
+            || !self.subscription_patterns.iter().any(|m| m(&topic_hash))
+        {
+            return;
+        }
+        debug!("Subscribing to topic {} matching a pattern", topic_hash);
+        if let Err(e) = self.subscribe_topic_hash(topic_hash.clone()) {
+            debug!(
+                "Failed to subscribe to topic {} matching a pattern: {:?}",
+                topic_hash, e
+            );
+        }
+    }
+
+    /// Subscribes to a topic by its hash, see [`Gossipsub::subscribe`].
+    fn subscribe_topic_hash(&mut self, topic_hash: TopicHash) -> Result<bool, SubscriptionError> {
+        debug!("Subscribing to topic: {}", topic_hash);
         if !self.subscription_filter.can_subscribe(&topic_hash) {
             return Err(SubscriptionError::NotAllowed);
         }
 
         if self.mesh.get(&topic_hash).is_some() {
-            debug!("Topic: {} is already in the mesh.", topic);
+            debug!("Topic: {} is already in the mesh.", topic_hash);
             return Ok(false);
         }
 
@@ -509,7 +669,12 @@ where
         // call JOIN(topic)
         // this will add new peers to the mesh for the topic
         self.join(&topic_hash);
-        debug!("Subscribed to topic: {}", topic);
+
+        if let Some(grace_period) = self.config.publish_grace_period() {
+            self.publish_grace_deadlines
+                .insert(topic_hash.clone(), Instant::now() + grace_period);
+        }
+        debug!("Subscribed to topic: {}", topic_hash);
         Ok(true)
     }
 
@@ -559,6 +724,14 @@ where
         topic: Topic<H>,
         data: impl Into<Vec<u8>>,
     ) -> Result<MessageId, PublishError> {
+        if self.is_publish_rate_limited(&topic.hash()) {
+            warn!(
+                "Not publishing a message on topic {}: rate limit exceeded",
+                topic
+            );
+            return Err(PublishError::RateLimited);
+        }
+
         let data = data.into();
 
         // Transform the data before building a raw_message.
@@ -599,17 +772,225 @@ where
             return Err(PublishError::Duplicate);
         }
 
+        let topic_hash = raw_message.topic.clone();
+
+        // Hold the message back while the mesh of a freshly subscribed topic is forming.
+        if self.is_mesh_forming(&topic_hash) {
+            debug!(
+                "Mesh for topic {} still forming, queueing message: {:?}",
+                topic_hash, msg_id
+            );
+            self.record_published_message(&msg_id, topic_hash.clone());
+            self.pending_publishes
+                .entry(topic_hash)
+                .or_default()
+                .push((msg_id.clone(), raw_message));
+            return Ok(msg_id);
+        }
+
         debug!("Publishing message: {:?}", msg_id);
+        self.send_published_message(&msg_id, raw_message, event, self.config.flood_publish())?;
+
+        // If the message isn't a duplicate and we have sent it to some peers add it to the
+        // duplicate cache.
+        self.record_published_message(&msg_id, topic_hash);
+
+        debug!("Published message: {:?}", &msg_id);
+        Ok(msg_id)
+    }
+
+    /// Publishes a message with a set of topics to the network, requesting the mesh peers of the
+    /// topic to acknowledge that they accepted it. Requires the acknowledgement extension to be
+    /// enabled via [`GossipsubConfig::acknowledgement_timeout`], which remote peers need to
+    /// support as well.
+    ///
+    /// A [`GossipsubEvent::PublishAcknowledged`] is emitted once `required_acks` peers
+    /// acknowledged the message, or once the timeout elapsed.
+    pub fn publish_acknowledged<H: Hasher>(
+        &mut self,
+        topic: Topic<H>,
+        data: impl Into<Vec<u8>>,
+        required_acks: NonZeroUsize,
+    ) -> Result<MessageId, PublishError> {
+        let timeout = self
+            .config
+            .acknowledgement_timeout()
+            .ok_or(PublishError::AcknowledgementsDisabled)?;
+
+        let topic_hash = topic.hash();
+        let msg_id = self.publish(topic, data)?;
+
+        self.pending_acknowledgements.insert(
+            msg_id.clone(),
+            PendingAcknowledgement {
+                topic_hash: topic_hash.clone(),
+                required: required_acks.get(),
+                requested: HashSet::new(),
+                acks: Vec::new(),
+                deadline: Instant::now() + timeout,
+            },
+        );
+
+        // Messages held back while the mesh is forming request acknowledgements once they are
+        // sent.
+        let queued = self
+            .pending_publishes
+            .get(&topic_hash)
+            .map_or(false, |messages| messages.iter().any(|(id, _)| *id == msg_id));
+        if !queued {
+            self.request_acknowledgements(&msg_id);
+        }
+
+        Ok(msg_id)
+    }
+
+    /// Requests the mesh peers, or the fanout peers if we are not subscribed, of the topic of a
+    /// message published through [`Gossipsub::publish_acknowledged`] to acknowledge it.
+    fn request_acknowledgements(&mut self, msg_id: &MessageId) {
+        let topic_hash = match self.pending_acknowledgements.get(msg_id) {
+            Some(pending) => &pending.topic_hash,
+            None => return,
+        };
+
+        let peers: Vec<PeerId> = match self.mesh.get(topic_hash) {
+            Some(peers) => peers.iter().cloned().collect(),
+            None => self
+                .fanout
+                .get(topic_hash)
+                .map(|peers| peers.iter().cloned().collect())
+                .unwrap_or_default(),
+        };
+        if let Some(pending) = self.pending_acknowledgements.get_mut(msg_id) {
+            pending.requested.extend(peers.iter().cloned());
+        }
+
+        let event = GossipsubRpc {
+            subscriptions: Vec::new(),
+            messages: Vec::new(),
+            control_msgs: vec![GossipsubControlAction::AckRequest {
+                message_ids: vec![msg_id.clone()],
+            }],
+        }
+        .into_protobuf();
+
+        for peer_id in peers {
+            debug!("Requesting acknowledgement of {} from peer {}", msg_id, peer_id);
+            if self.send_message(peer_id, event.clone()).is_err() {
+                error!("Failed to request acknowledgement from peer {}", peer_id);
+            }
+        }
+    }
 
+    /// Handles acknowledgement requests of a peer, acknowledging the messages we accepted.
+    fn handle_ack_request(&mut self, peer_id: &PeerId, message_ids: Vec<MessageId>) {
+        if self.config.acknowledgement_timeout().is_none() {
+            debug!(
+                "Ignoring acknowledgement request from peer {}: acknowledgements disabled",
+                peer_id
+            );
+            return;
+        }
+
+        let message_ids: Vec<MessageId> = message_ids
+            .into_iter()
+            .filter(|id| self.mcache.get(id).map_or(false, |m| m.validated))
+            .collect();
+        if message_ids.is_empty() {
+            return;
+        }
+
+        debug!("Acknowledging messages {:?} to peer {}", message_ids, peer_id);
+        let event = GossipsubRpc {
+            subscriptions: Vec::new(),
+            messages: Vec::new(),
+            control_msgs: vec![GossipsubControlAction::Ack { message_ids }],
+        }
+        .into_protobuf();
+        if self.send_message(*peer_id, event).is_err() {
+            error!("Failed to send acknowledgement to peer {}", peer_id);
+        }
+    }
+
+    /// Handles acknowledgements of a peer, reporting acknowledged publishes once they have
+    /// received the required number of acknowledgements.
+    fn handle_ack(&mut self, peer_id: &PeerId, message_ids: Vec<MessageId>) {
+        for msg_id in message_ids {
+            let pending = match self.pending_acknowledgements.get_mut(&msg_id) {
+                Some(pending) => pending,
+                None => continue,
+            };
+            if !pending.requested.remove(peer_id) {
+                debug!(
+                    "Ignoring unrequested acknowledgement of {} from peer {}",
+                    msg_id, peer_id
+                );
+                continue;
+            }
+
+            pending.acks.push(*peer_id);
+            if pending.acks.len() >= pending.required {
+                let pending = self
+                    .pending_acknowledgements
+                    .remove(&msg_id)
+                    .expect("Entry exists; qed");
+                debug!("Publish of {} acknowledged", msg_id);
+                self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                    GossipsubEvent::PublishAcknowledged {
+                        message_id: msg_id,
+                        acks: pending.acks,
+                    },
+                ));
+            }
+        }
+    }
+
+    /// Reports the acknowledged publishes whose [`GossipsubConfig::acknowledgement_timeout`]
+    /// elapsed with the acknowledgements received so far.
+    fn expire_acknowledgements(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<MessageId> = self
+            .pending_acknowledgements
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(msg_id, _)| msg_id.clone())
+            .collect();
+
+        for msg_id in expired {
+            if let Some(pending) = self.pending_acknowledgements.remove(&msg_id) {
+                debug!(
+                    "Acknowledgement timeout of {} elapsed with {} acknowledgements",
+                    msg_id,
+                    pending.acks.len()
+                );
+                self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                    GossipsubEvent::PublishAcknowledged {
+                        message_id: msg_id,
+                        acks: pending.acks,
+                    },
+                ));
+            }
+        }
+    }
+
+    /// Sends a message published by the local node to the peers of its topic: to the mesh peers,
+    /// explicit peers, floodsub peers and fanout peers, or, if `flood` is `true`, to all peers
+    /// subscribed to the topic that are either explicit peers or have a score above the publish
+    /// threshold.
+    fn send_published_message(
+        &mut self,
+        msg_id: &MessageId,
+        raw_message: RawGossipsubMessage,
+        event: rpc_proto::Rpc,
+        flood: bool,
+    ) -> Result<(), PublishError> {
         let topic_hash = raw_message.topic.clone();
 
         // If we are not flood publishing forward the message to mesh peers.
-        let mesh_peers_sent =
-            !self.config.flood_publish() && self.forward_msg(&msg_id, raw_message.clone(), None)?;
+        let mesh_peers_sent = !flood && self.forward_msg(msg_id, raw_message.clone(), None)?;
 
         let mut recipient_peers = HashSet::new();
         if let Some(set) = self.topic_peers.get(&topic_hash) {
-            if self.config.flood_publish() {
+            if flood {
                 // Forward to all peers above score and all explicit peers
                 recipient_peers.extend(
                     set.iter()
@@ -663,6 +1044,25 @@ where
                                 }
                             },
                         );
+                        // Make room for the new fanout topic by evicting the topic we published
+                        // to least recently
+                        if let Some(max_fanout_topics) = self.config.max_fanout_topics() {
+                            while self.fanout.len() >= max_fanout_topics {
+                                let oldest = self
+                                    .fanout_last_pub
+                                    .iter()
+                                    .min_by_key(|(_, last_pub)| **last_pub)
+                                    .map(|(topic, _)| topic.clone());
+                                match oldest {
+                                    Some(topic) => {
+                                        debug!("Fanout topic evicted: {:?}", topic);
+                                        self.fanout.remove(&topic);
+                                        self.fanout_last_pub.remove(&topic);
+                                    }
+                                    None => break,
+                                }
+                            }
+                        }
                         // Add the new peers to the fanout and recipient peers
                         self.fanout.insert(topic_hash.clone(), new_peers.clone());
                         for peer in new_peers {
@@ -681,10 +1081,29 @@ where
             return Err(PublishError::InsufficientPeers);
         }
 
-        // If the message isn't a duplicate and we have sent it to some peers add it to the
-        // duplicate cache and memcache.
+        // We have sent the message to some peers, add it to the memcache.
+        self.mcache.put_published(msg_id, raw_message);
+
+        // Send to peers we know are subscribed to the topic.
+        for peer_id in recipient_peers.iter() {
+            debug!("Sending message to peer: {:?}", peer_id);
+            self.send_message(*peer_id, event.clone())?;
+        }
+
+        Ok(())
+    }
+
+    /// Records a message published by the local node in the caches that prevent it from being
+    /// published or accepted again.
+    fn record_published_message(&mut self, msg_id: &MessageId, topic_hash: TopicHash) {
         self.duplicate_cache.insert(msg_id.clone());
-        self.mcache.put(&msg_id, raw_message);
+
+        if self.config.publish_rate_limit().is_some() {
+            self.published_times
+                .entry(topic_hash)
+                .or_default()
+                .push_back(Instant::now());
+        }
 
         // If the message is anonymous or has a random author add it to the published message ids
         // cache.
@@ -693,15 +1112,73 @@ where
                 self.published_message_ids.insert(msg_id.clone());
             }
         }
+    }
 
-        // Send to peers we know are subscribed to the topic.
-        for peer_id in recipient_peers.iter() {
-            debug!("Sending message to peer: {:?}", peer_id);
-            self.send_message(*peer_id, event.clone())?;
+    /// Returns `true` if messages published on the topic are to be held back, because we recently
+    /// subscribed to it and its mesh has not reached `mesh_n_low` peers within the
+    /// [`GossipsubConfig::publish_grace_period`] yet, or because earlier messages are still queued.
+    fn is_mesh_forming(&self, topic_hash: &TopicHash) -> bool {
+        if self.pending_publishes.contains_key(topic_hash) {
+            return true;
+        }
+        match self.publish_grace_deadlines.get(topic_hash) {
+            Some(deadline) => {
+                *deadline > Instant::now()
+                    && self.mesh.get(topic_hash).map_or(0, |peers| peers.len())
+                        < self.config.mesh_n_low()
+            }
+            None => false,
         }
+    }
 
-        debug!("Published message: {:?}", &msg_id);
-        Ok(msg_id)
+    /// Sends the messages that were held back while the mesh of a freshly subscribed topic was
+    /// forming. Once the mesh has reached `mesh_n_low` peers, the messages are published to the
+    /// mesh. If the [`GossipsubConfig::publish_grace_period`] elapsed before, they are flood
+    /// published to all peers subscribed to the topic instead.
+    fn flush_pending_publishes(&mut self) {
+        let now = Instant::now();
+        let mesh = &self.mesh;
+        let mesh_n_low = self.config.mesh_n_low();
+        let mut to_flush = Vec::new();
+        self.publish_grace_deadlines.retain(|topic_hash, deadline| {
+            let formed = mesh.get(topic_hash).map_or(0, |peers| peers.len()) >= mesh_n_low;
+            if formed || *deadline <= now {
+                to_flush.push((topic_hash.clone(), !formed));
+                return false;
+            }
+            true
+        });
+
+        for (topic_hash, flood) in to_flush {
+            let messages = match self.pending_publishes.remove(&topic_hash) {
+                Some(messages) => messages,
+                None => continue,
+            };
+            for (msg_id, raw_message) in messages {
+                let event = GossipsubRpc {
+                    subscriptions: Vec::new(),
+                    messages: vec![raw_message.clone()],
+                    control_msgs: Vec::new(),
+                }
+                .into_protobuf();
+                let flood = flood || self.config.flood_publish();
+                match self.send_published_message(&msg_id, raw_message, event, flood) {
+                    Ok(()) => {
+                        debug!("Published queued message: {:?}", msg_id);
+                        self.request_acknowledgements(&msg_id);
+                    }
+                    Err(e) => {
+                        warn!("Dropping queued message {}: {:?}", msg_id, e);
+                        self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                            GossipsubEvent::PublishDropped {
+                                message_id: msg_id,
+                                topic: topic_hash.clone(),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
     }
 
     /// This function should be called when [`GossipsubConfig::validate_messages()`] is `true` after
@@ -731,6 +1208,14 @@ where
     ) -> Result<bool, PublishError> {
         let reject_reason = match acceptance {
             MessageAcceptance::Accept => {
+                if self.is_message_expired(msg_id) {
+                    debug!(
+                        "Message has outlived its time-to-live. Ignoring forwarding. Message Id: {}",
+                        msg_id
+                    );
+                    self.mcache.remove(msg_id);
+                    return Ok(false);
+                }
                 let raw_message = match self.mcache.validate(msg_id) {
                     Some(raw_message) => raw_message.clone(),
                     None => {
@@ -796,6 +1281,38 @@ where
         }
     }
 
+    /// Changes parameters of the configuration at runtime, applying them from the next heartbeat
+    /// on. The resulting configuration is validated like in [`GossipsubConfigBuilder::build`].
+    /// Returns an error and leaves the configuration unchanged if it is invalid.
+    pub fn reconfigure(&mut self, params: GossipsubParams) -> Result<(), &'static str> {
+        let mut builder = GossipsubConfigBuilder::from(self.config.clone());
+        if let Some(mesh_n) = params.mesh_n {
+            builder.mesh_n(mesh_n);
+        }
+        if let Some(mesh_n_low) = params.mesh_n_low {
+            builder.mesh_n_low(mesh_n_low);
+        }
+        if let Some(mesh_n_high) = params.mesh_n_high {
+            builder.mesh_n_high(mesh_n_high);
+        }
+        if let Some(gossip_lazy) = params.gossip_lazy {
+            builder.gossip_lazy(gossip_lazy);
+        }
+        if let Some(heartbeat_interval) = params.heartbeat_interval {
+            builder.heartbeat_interval(heartbeat_interval);
+        }
+        let config = builder.build()?;
+
+        if config.heartbeat_interval() != self.config.heartbeat_interval() {
+            self.heartbeat = Interval::new_at(
+                Instant::now() + config.heartbeat_interval(),
+                config.heartbeat_interval(),
+            );
+        }
+        self.config = config;
+        Ok(())
+    }
+
     /// Activates the peer scoring system with the given parameters. This will reset all scores
     /// if there was already another peer scoring system activated. Returns an error if the
     /// params are not valid or if they got already set.
@@ -854,6 +1371,42 @@ where
         }
     }
 
+    /// Resets the gossipsub score of a peer back to the baseline, e.g. to recover from a
+    /// misconfiguration that unfairly penalized it. The application specific score is kept.
+    ///
+    /// Returns true if scoring is active and the peer is known, false otherwise.
+    pub fn reset_peer_score(&mut self, peer_id: &PeerId) -> bool {
+        if let Some((peer_score, ..)) = &mut self.peer_score {
+            peer_score.reset_peer(peer_id)
+        } else {
+            false
+        }
+    }
+
+    /// Returns a snapshot of the gossipsub scores of all peers scoring keeps track of, including
+    /// disconnected peers whose score is retained. Empty if scoring is not active.
+    pub fn score_snapshot(&self) -> HashMap<PeerId, f64> {
+        self.peer_score
+            .as_ref()
+            .map(|(peer_score, ..)| peer_score.scores())
+            .unwrap_or_default()
+    }
+
+    /// Returns whether a message with the given id has been received and not yet forgotten by
+    /// the duplicate cache.
+    #[cfg(test)]
+    fn has_seen(&mut self, msg_id: &MessageId) -> bool {
+        self.duplicate_cache.contains(msg_id)
+    }
+
+    /// Forgets all received messages, so that they are handled as new messages when they are
+    /// received again.
+    #[cfg(test)]
+    fn clear_seen(&mut self) {
+        self.duplicate_cache.clear();
+        self.fast_messsage_id_cache.clear();
+    }
+
     /// Gossipsub JOIN(topic) - adds topic peers to mesh and sends them GRAFT messages.
     fn join(&mut self, topic_hash: &TopicHash) {
         debug!("Running JOIN for topic: {:?}", topic_hash);
@@ -864,6 +1417,13 @@ where
             return;
         }
 
+        // in pure subscriber mode we don't graft any peers, as we never forward to them
+        if !self.config.forwarding_enabled() {
+            debug!("JOIN: Forwarding is disabled, not grafting any peers");
+            self.mesh.insert(topic_hash.clone(), BTreeSet::new());
+            return;
+        }
+
         let mut added_peers = HashSet::new();
 
         // check if we have mesh_n peers in fanout[topic] and add them to the mesh if we do,
@@ -1016,6 +1576,23 @@ where
     fn leave(&mut self, topic_hash: &TopicHash) {
         debug!("Running LEAVE for topic {:?}", topic_hash);
 
+        self.publish_grace_deadlines.remove(topic_hash);
+        if let Some(messages) = self.pending_publishes.remove(topic_hash) {
+            warn!(
+                "LEAVE: Dropping {} queued messages for topic {:?}",
+                messages.len(),
+                topic_hash
+            );
+            for (message_id, _) in messages {
+                self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                    GossipsubEvent::PublishDropped {
+                        message_id,
+                        topic: topic_hash.clone(),
+                    },
+                ));
+            }
+        }
+
         // If our mesh contains the topic, send prune to peers and delete it from the mesh
         if let Some((_, peers)) = self.mesh.remove_entry(topic_hash) {
             for peer in peers {
@@ -1076,6 +1653,42 @@ where
         }
     }
 
+    /// Returns `true` if the message was first seen longer than [`GossipsubConfig::message_ttl`]
+    /// ago and must therefore no longer be propagated.
+    fn is_message_expired(&self, msg_id: &MessageId) -> bool {
+        match self.config.message_ttl() {
+            Some(ttl) => self.mcache.is_expired(msg_id, ttl),
+            None => false,
+        }
+    }
+
+    /// Returns `true` if publishing another message on the topic would exceed
+    /// [`GossipsubConfig::publish_rate_limit`]. Forgets publish times that fell out of the
+    /// rate limit interval.
+    fn is_publish_rate_limited(&mut self, topic_hash: &TopicHash) -> bool {
+        let (max_messages, interval) = match self.config.publish_rate_limit() {
+            Some(limit) => limit,
+            None => return false,
+        };
+
+        let times = match self.published_times.get_mut(topic_hash) {
+            Some(times) => times,
+            None => return max_messages == 0,
+        };
+        let now = Instant::now();
+        while let Some(time) = times.front() {
+            if *time + interval > now {
+                break;
+            }
+            times.pop_front();
+        }
+        let limited = times.len() >= max_messages;
+        if times.is_empty() {
+            self.published_times.remove(topic_hash);
+        }
+        limited
+    }
+
     /// Handles an IHAVE control message. Checks our cache of messages. If the message is unknown,
     /// requests it with an IWANT control message.
     fn handle_ihave(&mut self, peer_id: &PeerId, ihave_msgs: Vec<(TopicHash, Vec<MessageId>)>) {
@@ -1190,11 +1803,57 @@ where
             return;
         }
 
+        // IWANT flood protection, with a stricter limit for peers outside of our meshes
+        let max_iwant_messages = if self.mesh.values().any(|peers| peers.contains(peer_id)) {
+            self.config.max_iwant_messages()
+        } else {
+            std::cmp::min(
+                self.config.max_iwant_messages(),
+                self.config.max_iwant_messages_non_mesh(),
+            )
+        };
+        let served = self.count_served_iwant.entry(*peer_id).or_insert(0);
+        if *served >= max_iwant_messages {
+            debug!(
+                "IWANT: peer {} has requested too many messages ({}) within this heartbeat \
+            interval; ignoring",
+                peer_id, *served
+            );
+            if let Some((peer_score, ..)) = &mut self.peer_score {
+                peer_score.add_penalty(peer_id, 1);
+            }
+            return;
+        }
+
         debug!("Handling IWANT for peer: {:?}", peer_id);
         // build a hashmap of available messages
         let mut cached_messages = HashMap::new();
+        let message_ttl = self.config.message_ttl();
 
         for id in iwant_msgs {
+            if *served >= max_iwant_messages {
+                debug!(
+                    "IWANT: Peer {} reached the limit of served messages; ignoring the remaining \
+                request",
+                    peer_id
+                );
+                break;
+            }
+            // Messages that have outlived their time-to-live are no longer propagated
+            if let Some(ttl) = message_ttl {
+                if self.mcache.is_expired(&id, ttl) {
+                    debug!("IWANT: Message {} has expired; ignoring request", &id);
+                    continue;
+                }
+            }
+            // In pure subscriber mode we only serve messages published by ourselves
+            if !self.config.forwarding_enabled() && !self.mcache.is_published(&id) {
+                debug!(
+                    "IWANT: Forwarding is disabled; ignoring request for {}",
+                    &id
+                );
+                continue;
+            }
             // If we have it and the IHAVE count is not above the threshold, add it do the
             // cached_messages mapping
             if let Some((msg, count)) = self.mcache.get_with_iwant_counts(&id, peer_id) {
@@ -1204,8 +1863,8 @@ where
                     request",
                         peer_id, &id
                     );
-                } else {
-                    cached_messages.insert(id.clone(), msg.clone());
+                } else if cached_messages.insert(id.clone(), msg.clone()).is_none() {
+                    *served += 1;
                 }
             }
         }
@@ -1248,9 +1907,26 @@ where
             to_prune_topics = topics.into_iter().collect();
             // but don't PX
             do_px = false
+        } else if !self.config.forwarding_enabled() {
+            debug!(
+                "GRAFT: forwarding is disabled, sending PRUNE to peer {}",
+                peer_id
+            );
+            let mesh = &self.mesh;
+            to_prune_topics = topics
+                .into_iter()
+                .filter(|topic_hash| mesh.contains_key(topic_hash))
+                .collect();
+            do_px = false
         } else {
             let (below_zero, score) = self.score_below_threshold(peer_id, |_| 0.0);
             let now = Instant::now();
+            // the number of topics for which the peer is in our mesh
+            let mut mesh_topics = self
+                .mesh
+                .values()
+                .filter(|peers| peers.contains(peer_id))
+                .count();
             for topic_hash in topics {
                 if let Some(peers) = self.mesh.get_mut(&topic_hash) {
                     // if the peer is already in the mesh ignore the graft
@@ -1315,12 +1991,30 @@ where
                         continue;
                     }
 
+                    // check the maximum number of topics the peer may be in our mesh for
+                    if let Some(max_mesh_topics) = self.config.max_mesh_topics_per_peer() {
+                        if mesh_topics >= max_mesh_topics {
+                            debug!(
+                                "GRAFT: peer {} exceeded the maximum number of mesh topics, \
+                                 penalizing",
+                                peer_id
+                            );
+                            if let Some((peer_score, ..)) = &mut self.peer_score {
+                                peer_score.add_penalty(peer_id, 1);
+                            }
+                            do_px = false;
+                            to_prune_topics.insert(topic_hash.clone());
+                            continue;
+                        }
+                    }
+
                     // add peer to the mesh
                     debug!(
                         "GRAFT: Mesh link added for peer: {:?} in topic: {:?}",
                         peer_id, &topic_hash
                     );
                     peers.insert(*peer_id);
+                    mesh_topics += 1;
                     // If the peer did not previously exist in any mesh, inform the handler
                     peer_added_to_mesh(
                         *peer_id,
@@ -1394,6 +2088,14 @@ where
                     topic_hash
                 );
 
+                if peers.is_empty() {
+                    self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                        GossipsubEvent::MeshEmpty {
+                            topic: topic_hash.clone(),
+                        },
+                    ));
+                }
+
                 if let Some((peer_score, ..)) = &mut self.peer_score {
                     peer_score.prune(peer_id, topic_hash.clone());
                 }
@@ -1423,6 +2125,9 @@ where
     }
 
     /// Handles PRUNE control messages. Removes peer from the mesh.
+    ///
+    /// If [`GossipsubConfig::ignore_prune_from_non_mesh_peers`] is set, topics in whose mesh we
+    /// don't have the peer are skipped, so that neither a backoff nor peer exchange applies.
     fn handle_prune(
         &mut self,
         peer_id: &PeerId,
@@ -1432,6 +2137,19 @@ where
         let (below_threshold, score) =
             self.score_below_threshold(peer_id, |pst| pst.accept_px_threshold);
         for (topic_hash, px, backoff) in prune_data {
+            if self.config.ignore_prune_from_non_mesh_peers()
+                && !self
+                    .mesh
+                    .get(&topic_hash)
+                    .map_or(false, |peers| peers.contains(peer_id))
+            {
+                debug!(
+                    "PRUNE: ignoring PRUNE from peer {} not in our mesh for topic {}",
+                    peer_id, topic_hash
+                );
+                continue;
+            }
+
             self.remove_peer_from_mesh(peer_id, &topic_hash, backoff, true);
 
             if self.mesh.contains_key(&topic_hash) {
@@ -1722,6 +2440,13 @@ where
             propagation_source.to_string()
         );
 
+        // Our own subscriptions are never recorded, even if they are echoed back to us under our
+        // own peer id.
+        if self.local_peer_id.as_ref() == Some(propagation_source) {
+            debug!("Ignoring subscriptions claiming to be from the local peer");
+            return;
+        }
+
         let mut unsubscribed_peers = Vec::new();
 
         let subscribed_topics = match self.peer_topics.get_mut(propagation_source) {
@@ -1738,6 +2463,9 @@ where
         // Collect potential graft topics for the peer.
         let mut topics_to_graft = Vec::new();
 
+        // Collect the topics the peer subscribed to that may match a subscription pattern.
+        let mut subscribed = Vec::new();
+
         // Notify the application about the subscription, after the grafts are sent.
         let mut application_event = Vec::new();
 
@@ -1775,6 +2503,7 @@ where
 
                     // add to the peer_topics mapping
                     subscribed_topics.insert(subscription.topic_hash.clone());
+                    subscribed.push(subscription.topic_hash.clone());
 
                     // if the mesh needs peers add the peer to the mesh
                     if !self.explicit_peers.contains(propagation_source)
@@ -1795,7 +2524,8 @@ where
                             .is_backoff_with_slack(&subscription.topic_hash, propagation_source)
                     {
                         if let Some(peers) = self.mesh.get_mut(&subscription.topic_hash) {
-                            if peers.len() < self.config.mesh_n_low()
+                            if self.config.forwarding_enabled()
+                                && peers.len() < self.config.mesh_n_low()
                                 && peers.insert(*propagation_source)
                             {
                                 debug!(
@@ -1891,6 +2621,14 @@ where
             self.events.push_back(event);
         }
 
+        // Subscribe to the topics matching a subscription pattern, which also adds the peer to
+        // their mesh.
+        if !self.subscription_patterns.is_empty() {
+            for topic_hash in subscribed {
+                self.subscribe_matching_topic(topic_hash);
+            }
+        }
+
         trace!(
             "Completed handling subscriptions from source: {:?}",
             propagation_source
@@ -1915,6 +2653,7 @@ where
         let mut to_graft = HashMap::new();
         let mut to_prune = HashMap::new();
         let mut no_px = HashSet::new();
+        let mut emptied_meshes = Vec::new();
 
         // clean up expired backoffs
         self.backoffs.heartbeat();
@@ -1922,6 +2661,7 @@ where
         // clean up ihave counters
         self.count_sent_iwant.clear();
         self.count_received_ihave.clear();
+        self.count_served_iwant.clear();
 
         // apply iwant penalties
         self.apply_iwant_penalties();
@@ -1943,6 +2683,7 @@ where
 
         // maintain the mesh for each topic
         for (topic_hash, peers) in self.mesh.iter_mut() {
+            let had_peers = !peers.is_empty();
             let explicit_peers = &self.explicit_peers;
             let backoffs = &self.backoffs;
             let topic_peers = &self.topic_peers;
@@ -1977,8 +2718,8 @@ where
                 peers.remove(&peer);
             }
 
-            // too little peers - add some
-            if peers.len() < self.config.mesh_n_low() {
+            // too little peers - add some, unless we are a pure subscriber that is never grafted
+            if self.config.forwarding_enabled() && peers.len() < self.config.mesh_n_low() {
                 debug!(
                     "HEARTBEAT: Mesh low. Topic: {} Contains: {} needs: {}",
                     topic_hash,
@@ -2008,6 +2749,11 @@ where
                 peers.extend(peer_list);
             }
 
+            // all peers got dropped and none could be grafted
+            if had_peers && peers.is_empty() {
+                emptied_meshes.push(topic_hash.clone());
+            }
+
             // too many peers - remove some
             if peers.len() > self.config.mesh_n_high() {
                 debug!(
@@ -2018,12 +2764,16 @@ where
                 );
                 let excess_peer_no = peers.len() - self.config.mesh_n();
 
-                // shuffle the peers and then sort by score ascending beginning with the worst
+                // sort the peers by score ascending beginning with the worst, breaking exact ties
+                // by peer id so that the best peers are retained deterministically
                 let mut rng = thread_rng();
                 let mut shuffled = peers.iter().cloned().collect::<Vec<_>>();
-                shuffled.shuffle(&mut rng);
-                shuffled
-                    .sort_by(|p1, p2| score(p1).partial_cmp(&score(p2)).unwrap_or(Ordering::Equal));
+                shuffled.sort_by(|p1, p2| {
+                    score(p1)
+                        .partial_cmp(&score(p2))
+                        .unwrap_or(Ordering::Equal)
+                        .then_with(|| p1.cmp(p2))
+                });
                 // shuffle everything except the last retain_scores many peers (the best ones)
                 shuffled[..peers.len() - self.config.retain_scores()].shuffle(&mut rng);
 
@@ -2062,7 +2812,7 @@ where
             }
 
             // do we have enough outbound peers?
-            if peers.len() >= self.config.mesh_n_low() {
+            if self.config.forwarding_enabled() && peers.len() >= self.config.mesh_n_low() {
                 // count number of outbound peers we have
                 let outbound = { peers.iter().filter(|p| outbound_peers.contains(*p)).count() };
 
@@ -2093,7 +2843,8 @@ where
             }
 
             // should we try to improve the mesh with opportunistic grafting?
-            if self.heartbeat_ticks % self.config.opportunistic_graft_ticks() == 0
+            if self.config.forwarding_enabled()
+                && self.heartbeat_ticks % self.config.opportunistic_graft_ticks() == 0
                 && peers.len() > 1
                 && self.peer_score.is_some()
             {
@@ -2153,6 +2904,12 @@ where
             }
         }
 
+        for topic in emptied_meshes {
+            self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                GossipsubEvent::MeshEmpty { topic },
+            ));
+        }
+
         // remove expired fanout topics
         {
             let fanout = &mut self.fanout; // help the borrow checker
@@ -2257,6 +3014,10 @@ where
             })
         }
 
+        self.flush_pending_publishes();
+
+        self.expire_acknowledgements();
+
         self.emit_gossip();
 
         // send graft/prunes
@@ -2279,8 +3040,13 @@ where
         let mut rng = thread_rng();
         for (topic_hash, peers) in self.mesh.iter().chain(self.fanout.iter()) {
             let mut message_ids = self.mcache.get_gossip_message_ids(&topic_hash);
+            message_ids.retain(|id| !self.is_message_expired(id));
+            // in pure subscriber mode we only gossip messages published by ourselves
+            if !self.config.forwarding_enabled() {
+                message_ids.retain(|id| self.mcache.is_published(id));
+            }
             if message_ids.is_empty() {
-                return;
+                continue;
             }
 
             // if we are emitting more than GossipSubMaxIHaveLength message_ids, truncate the list
@@ -2464,6 +3230,12 @@ where
             }
         }
 
+        // in pure subscriber mode we only send messages published by ourselves
+        if propagation_source.is_some() && !self.config.forwarding_enabled() {
+            debug!("Forwarding is disabled. Not forwarding message: {:?}", msg_id);
+            return Ok(false);
+        }
+
         debug!("Forwarding message: {:?}", msg_id);
         let mut recipient_peers = HashSet::new();
 
@@ -2646,16 +3418,66 @@ where
         let messages = self.fragment_message(message)?;
 
         for message in messages {
-            self.events
-                .push_back(NetworkBehaviourAction::NotifyHandler {
-                    peer_id,
-                    event: Arc::new(GossipsubHandlerIn::Message(message)),
-                    handler: NotifyHandler::Any,
-                })
+            let mut priority = self.rpc_priority(&message);
+            if priority > 0 && self.is_control_queued(&peer_id) {
+                // Keep the RPC behind the control messages and subscriptions queued for the peer.
+                priority = 0;
+            }
+            let event = NetworkBehaviourAction::NotifyHandler {
+                peer_id,
+                event: Arc::new(GossipsubHandlerIn::Message(message)),
+                handler: NotifyHandler::Any,
+            };
+            if priority == 0 {
+                self.events.push_back(event);
+            } else {
+                self.prioritized_events
+                    .entry(priority)
+                    .or_default()
+                    .push_back(event);
+            }
         }
         Ok(())
     }
 
+    /// The send priority of an RPC, i.e. the highest configured priority among the topics of
+    /// its messages. RPCs carrying subscriptions or control messages have priority 0, so that
+    /// they are never sent ahead of the events queued before them.
+    fn rpc_priority(&self, rpc: &rpc_proto::Rpc) -> u8 {
+        let priorities = self.config.topic_priorities();
+        if priorities.is_empty() || !rpc.subscriptions.is_empty() || rpc.control.is_some() {
+            return 0;
+        }
+        rpc.publish
+            .iter()
+            .filter_map(|m| {
+                priorities
+                    .iter()
+                    .find(|(topic_hash, _)| topic_hash.as_str() == m.topic)
+                    .map(|(_, priority)| *priority)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Whether an RPC carrying subscriptions or control messages is queued for `peer_id`.
+    /// Prioritized RPCs are not sent ahead of these, so that each peer receives them in order.
+    fn is_control_queued(&self, peer_id: &PeerId) -> bool {
+        self.events.iter().any(|event| match event {
+            NetworkBehaviourAction::NotifyHandler {
+                peer_id: queued_peer,
+                event,
+                ..
+            } if queued_peer == peer_id => match &**event {
+                GossipsubHandlerIn::Message(rpc) => {
+                    !rpc.subscriptions.is_empty() || rpc.control.is_some()
+                }
+                _ => false,
+            },
+            _ => false,
+        })
+    }
+
     // If a message is too large to be sent as-is, this attempts to fragment it into smaller RPC
     // messages to be sent.
     fn fragment_message(&self, rpc: rpc_proto::Rpc) -> Result<Vec<rpc_proto::Rpc>, PublishError> {
@@ -2767,6 +3589,28 @@ where
                         .prune
                         .push(prune.clone());
                 }
+                for ack_request in &control.ack_request {
+                    let len = ack_request.encoded_len();
+                    create_or_add_rpc!(len);
+                    rpc_list
+                        .last_mut()
+                        .expect("Always an element")
+                        .control
+                        .get_or_insert_with(|| empty_control.clone())
+                        .ack_request
+                        .push(ack_request.clone());
+                }
+                for ack in &control.ack {
+                    let len = ack.encoded_len();
+                    create_or_add_rpc!(len);
+                    rpc_list
+                        .last_mut()
+                        .expect("Always an element")
+                        .control
+                        .get_or_insert_with(|| empty_control.clone())
+                        .ack
+                        .push(ack.clone());
+                }
             } else {
                 let len = control.encoded_len();
                 create_or_add_rpc!(len);
@@ -2801,6 +3645,7 @@ where
             self.config.validation_mode().clone(),
             self.config.idle_timeout(),
             self.config.support_floodsub(),
+            self.config.publish_batch_delay(),
         )
     }
 
@@ -2870,7 +3715,13 @@ where
                 // check the mesh for the topic
                 if let Some(mesh_peers) = self.mesh.get_mut(&topic) {
                     // check if the peer is in the mesh and remove it
-                    mesh_peers.remove(peer_id);
+                    if mesh_peers.remove(peer_id) && mesh_peers.is_empty() {
+                        self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                            GossipsubEvent::MeshEmpty {
+                                topic: topic.clone(),
+                            },
+                        ));
+                    }
                 }
 
                 // remove from topic_peers
@@ -3053,6 +3904,16 @@ where
         _: ConnectionId,
         handler_event: HandlerEvent,
     ) {
+        // Events may still arrive from a peer we no longer consider connected, e.g. racing its
+        // disconnection. Ignore them rather than acting on a peer we have no state for.
+        if !self.connected_peers.contains_key(&propagation_source) {
+            debug!(
+                "Ignoring handler event from unknown peer {}",
+                propagation_source
+            );
+            return;
+        }
+
         match handler_event {
             HandlerEvent::PeerKind(kind) => {
                 // We have identified the protocol this peer is using
@@ -3150,6 +4011,12 @@ where
                             peers,
                             backoff,
                         } => prune_msgs.push((topic_hash, peers, backoff)),
+                        GossipsubControlAction::AckRequest { message_ids } => {
+                            self.handle_ack_request(&propagation_source, message_ids)
+                        }
+                        GossipsubControlAction::Ack { message_ids } => {
+                            self.handle_ack(&propagation_source, message_ids)
+                        }
                     }
                 }
                 if !ihave_msgs.is_empty() {
@@ -3168,14 +4035,34 @@ where
     fn poll(
         &mut self,
         cx: &mut Context<'_>,
-        _: &mut impl PollParameters,
+        params: &mut impl PollParameters,
     ) -> Poll<
         NetworkBehaviourAction<
             <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
             Self::OutEvent,
         >,
     > {
-        if let Some(event) = self.events.pop_front() {
+        if self.local_peer_id.is_none() {
+            self.local_peer_id = Some(*params.local_peer_id());
+        }
+
+        // RPCs of higher-priority topics are yielded first.
+        let prioritized = self
+            .prioritized_events
+            .iter_mut()
+            .next_back()
+            .map(|(priority, queue)| (*priority, queue.pop_front(), queue.is_empty()));
+        let event = match prioritized {
+            Some((priority, event, is_empty)) => {
+                if is_empty {
+                    self.prioritized_events.remove(&priority);
+                }
+                event
+            }
+            None => self.events.pop_front(),
+        };
+
+        if let Some(event) = event {
             return Poll::Ready(match event {
                 NetworkBehaviourAction::NotifyHandler {
                     peer_id,
@@ -3205,6 +4092,12 @@ where
                 NetworkBehaviourAction::CloseConnection { peer_id, connection } => {
                     NetworkBehaviourAction::CloseConnection { peer_id, connection }
                 }
+                NetworkBehaviourAction::ReportPeerUnroutable(peer_id) => {
+                    NetworkBehaviourAction::ReportPeerUnroutable(peer_id)
+                }
+                NetworkBehaviourAction::ListenOn(address) => {
+                    NetworkBehaviourAction::ListenOn(address)
+                }
             });
         }
 
@@ -3393,6 +4286,7 @@ impl<C: DataTransform, F: TopicSubscriptionFilter> fmt::Debug for Gossipsub<C, F
         f.debug_struct("Gossipsub")
             .field("config", &self.config)
             .field("events", &self.events)
+            .field("prioritized_events", &self.prioritized_events)
             .field("control_pool", &self.control_pool)
             .field("publish_config", &self.publish_config)
             .field("topic_peers", &self.topic_peers)
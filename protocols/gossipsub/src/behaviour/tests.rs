@@ -301,10 +301,33 @@ mod tests {
                 });
             }
 
+            let ack_request_msgs = rpc_control.ack_request.into_iter().map(|ack_request| {
+                GossipsubControlAction::AckRequest {
+                    message_ids: ack_request
+                        .message_ids
+                        .into_iter()
+                        .map(MessageId::from)
+                        .collect::<Vec<_>>(),
+                }
+            });
+
+            let ack_msgs = rpc_control
+                .ack
+                .into_iter()
+                .map(|ack| GossipsubControlAction::Ack {
+                    message_ids: ack
+                        .message_ids
+                        .into_iter()
+                        .map(MessageId::from)
+                        .collect::<Vec<_>>(),
+                });
+
             control_msgs.extend(ihave_msgs);
             control_msgs.extend(iwant_msgs);
             control_msgs.extend(graft_msgs);
             control_msgs.extend(prune_msgs);
+            control_msgs.extend(ack_request_msgs);
+            control_msgs.extend(ack_msgs);
         }
 
         GossipsubRpc {
@@ -883,6 +906,46 @@ mod tests {
         );
     }
 
+    #[test]
+    /// Test querying the topics a peer is subscribed to
+    fn test_peer_topics() {
+        let topics = vec!["topic1", "topic2", "topic3"]
+            .iter()
+            .map(|&t| String::from(t))
+            .collect();
+        let (mut gs, peers, topic_hashes) = inject_nodes1()
+            .peer_no(2)
+            .topics(topics)
+            .to_subscribe(false)
+            .create_network();
+
+        assert_eq!(gs.peer_topics(&peers[0]), Some(vec![]));
+        assert_eq!(gs.peer_topics(&PeerId::random()), None);
+
+        let subscriptions = topic_hashes[..2]
+            .iter()
+            .map(|topic_hash| GossipsubSubscription {
+                action: GossipsubSubscriptionAction::Subscribe,
+                topic_hash: topic_hash.clone(),
+            })
+            .collect::<Vec<GossipsubSubscription>>();
+        gs.handle_received_subscriptions(&subscriptions, &peers[0]);
+
+        let mut expected = topic_hashes[..2].to_vec();
+        expected.sort();
+        assert_eq!(gs.peer_topics(&peers[0]), Some(expected));
+        assert_eq!(gs.peer_topics(&peers[1]), Some(vec![]));
+
+        gs.handle_received_subscriptions(
+            &vec![GossipsubSubscription {
+                action: GossipsubSubscriptionAction::Unsubscribe,
+                topic_hash: topic_hashes[0].clone(),
+            }],
+            &peers[0],
+        );
+        assert_eq!(gs.peer_topics(&peers[0]), Some(vec![topic_hashes[1].clone()]));
+    }
+
     #[test]
     /// Test Gossipsub.get_random_peers() function
     fn test_get_random_peers() {
@@ -1258,6 +1321,37 @@ mod tests {
         );
     }
 
+    #[test]
+    // tests that a topic we are not subscribed to in the middle of a graft message doesn't
+    // affect the topics following it
+    fn test_handle_graft_skips_unsubscribed_topic_in_the_middle() {
+        let topics: Vec<String> = ["topic1", "topic2", "topic3"]
+            .iter()
+            .map(|&t| String::from(t))
+            .collect();
+
+        let (mut gs, peers, topic_hashes) = inject_nodes1()
+            .peer_no(20)
+            .topics(topics)
+            .to_subscribe(true)
+            .create_network();
+
+        gs.leave(&topic_hashes[1]);
+
+        gs.handle_graft(&peers[7], topic_hashes.clone());
+
+        for i in [0, 2].iter() {
+            assert!(
+                gs.mesh.get(&topic_hashes[*i]).unwrap().contains(&peers[7]),
+                "Expected peer to be in the mesh for the first and the last topic"
+            );
+        }
+        assert!(
+            !gs.mesh.contains_key(&topic_hashes[1]),
+            "Expected the second topic to not be in the mesh"
+        );
+    }
+
     #[test]
     // tests that a peer is removed from our mesh
     fn test_handle_prune_peer_in_mesh() {
@@ -1316,6 +1410,22 @@ mod tests {
                 .sum::<usize>()
     }
 
+    /// Counts the messages sent to each peer.
+    fn count_sent_messages<D: DataTransform, F: TopicSubscriptionFilter>(
+        gs: &Gossipsub<D, F>,
+    ) -> HashMap<PeerId, usize> {
+        let mut sent = HashMap::new();
+        for e in &gs.events {
+            if let NetworkBehaviourAction::NotifyHandler { peer_id, event, .. } = e {
+                if let GossipsubHandlerIn::Message(ref m) = **event {
+                    *sent.entry(*peer_id).or_insert(0) += proto_to_message(m).messages.len();
+                }
+            }
+        }
+        sent.retain(|_, count| *count > 0);
+        sent
+    }
+
     fn flush_events<D: DataTransform, F: TopicSubscriptionFilter>(gs: &mut Gossipsub<D, F>) {
         gs.control_pool.clear();
         gs.events.clear();
@@ -1415,6 +1525,51 @@ mod tests {
         );
     }
 
+    #[test]
+    // tests that a reconnecting explicit peer is sent our subscriptions right away, but not grafted
+    fn test_explicit_peer_reconnect_sends_subscriptions() {
+        let (mut gs, peers, topic_hashes) = inject_nodes1()
+            .peer_no(1)
+            .topics(vec![String::from("topic1")])
+            .to_subscribe(true)
+            .explicit(1)
+            .create_network();
+
+        let peer = peers.get(0).unwrap();
+
+        gs.inject_disconnected(peer);
+        flush_events(&mut gs);
+
+        //reconnect the explicit peer
+        gs.inject_connected(peer);
+
+        //our subscriptions are sent without waiting for a heartbeat
+        let subscribed = gs.events.iter().any(|e| match e {
+            NetworkBehaviourAction::NotifyHandler { peer_id, event, .. } if peer_id == peer => {
+                if let GossipsubHandlerIn::Message(ref m) = **event {
+                    proto_to_message(m).subscriptions.iter().any(|s| {
+                        s.topic_hash == topic_hashes[0]
+                            && s.action == GossipsubSubscriptionAction::Subscribe
+                    })
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        });
+        assert!(subscribed, "Subscriptions not sent to reconnected explicit peer");
+
+        //explicit peers are never grafted, neither immediately nor in the heartbeat
+        gs.heartbeat();
+        assert!(!gs.mesh[&topic_hashes[0]].contains(peer));
+        assert_eq!(
+            count_control_msgs(&gs, |peer_id, m| peer_id == peer
+                && matches!(m, GossipsubControlAction::Graft { .. })),
+            0,
+            "Explicit peer got grafted"
+        );
+    }
+
     #[test]
     fn test_handle_graft_explicit_peer() {
         let (mut gs, peers, topic_hashes) = inject_nodes1()
@@ -1512,6 +1667,94 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_no_messages_delivered_after_unsubscribe() {
+        let (mut gs, peers, topic_hashes) = inject_nodes1()
+            .peer_no(2)
+            .topics(vec![String::from("topic1"), String::from("topic2")])
+            .to_subscribe(true)
+            .create_network();
+
+        gs.unsubscribe(&Topic::new("topic1")).unwrap();
+        gs.events.clear();
+
+        // a peer still forwards messages on the topic before processing our unsubscription
+        for (seq, topic) in topic_hashes.iter().enumerate() {
+            let message = RawGossipsubMessage {
+                source: Some(peers[0]),
+                data: vec![seq as u8],
+                sequence_number: Some(seq as u64),
+                topic: topic.clone(),
+                signature: None,
+                key: None,
+                validated: true,
+            };
+            gs.handle_received_message(message, &peers[0]);
+        }
+
+        let delivered: Vec<_> = gs
+            .events
+            .iter()
+            .filter_map(|e| match e {
+                NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message {
+                    message, ..
+                }) => Some(message.topic.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(delivered, vec![topic_hashes[1].clone()]);
+    }
+
+    #[test]
+    fn test_duplicate_messages_are_not_delivered_until_forgotten() {
+        let (mut gs, peers, topic_hashes) = inject_nodes1()
+            .peer_no(2)
+            .topics(vec![String::from("topic1")])
+            .to_subscribe(true)
+            .create_network();
+
+        let raw_message = RawGossipsubMessage {
+            source: Some(peers[0]),
+            data: vec![1, 2, 3],
+            sequence_number: Some(0),
+            topic: topic_hashes[0].clone(),
+            signature: None,
+            key: None,
+            validated: true,
+        };
+        let msg_id = gs.config.message_id(
+            &gs.data_transform
+                .inbound_transform(raw_message.clone())
+                .unwrap(),
+        );
+
+        let count_delivered = |gs: &Gossipsub<_, _>| {
+            gs.events
+                .iter()
+                .filter(|e| {
+                    matches!(
+                        e,
+                        NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message { .. })
+                    )
+                })
+                .count()
+        };
+
+        assert!(!gs.has_seen(&msg_id));
+
+        // the message is delivered once even if received twice
+        gs.handle_received_message(raw_message.clone(), &peers[0]);
+        gs.handle_received_message(raw_message.clone(), &peers[1]);
+        assert!(gs.has_seen(&msg_id));
+        assert_eq!(count_delivered(&gs), 1);
+
+        // forgetting the message lets it be delivered again
+        gs.clear_seen();
+        assert!(!gs.has_seen(&msg_id));
+        gs.handle_received_message(raw_message, &peers[1]);
+        assert_eq!(count_delivered(&gs), 2);
+    }
+
     #[test]
     fn do_forward_messages_to_explicit_peers() {
         let (mut gs, peers, topic_hashes) = inject_nodes1()
@@ -1782,6 +2025,81 @@ mod tests {
         assert_eq!(gs.mesh.get(&topics[0]).unwrap().len(), config.mesh_n());
     }
 
+    #[test]
+    fn test_reconfigure_mesh_n() {
+        let config = GossipsubConfig::default();
+
+        let n = config.mesh_n_high() + 10;
+        let (mut gs, peers, topics) = inject_nodes1()
+            .peer_no(n)
+            .topics(vec!["test".into()])
+            .to_subscribe(true)
+            .gs_config(config.clone())
+            .outbound(n)
+            .create_network();
+
+        // graft all the peers
+        for peer in peers {
+            gs.handle_graft(&peer, topics.clone());
+        }
+
+        // An invalid configuration is rejected and leaves the configuration unchanged.
+        assert!(gs
+            .reconfigure(crate::config::GossipsubParams {
+                mesh_n: Some(config.mesh_n_high() + 1),
+                ..Default::default()
+            })
+            .is_err());
+        assert_eq!(gs.config.mesh_n(), config.mesh_n());
+
+        let mesh_n = config.mesh_n() + 2;
+        gs.reconfigure(crate::config::GossipsubParams {
+            mesh_n: Some(mesh_n),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // run a heartbeat
+        gs.heartbeat();
+
+        // Peers should be removed to reach the new mesh_n
+        assert_eq!(gs.mesh.get(&topics[0]).unwrap().len(), mesh_n);
+    }
+
+    #[test]
+    fn test_mesh_empty_event() {
+        let (mut gs, peers, topics) = inject_nodes1()
+            .peer_no(2)
+            .topics(vec!["test".into()])
+            .to_subscribe(true)
+            .create_network();
+
+        let count_mesh_empty = |gs: &Gossipsub<_, _>| {
+            gs.events
+                .iter()
+                .filter(|e| match e {
+                    NetworkBehaviourAction::GenerateEvent(GossipsubEvent::MeshEmpty { topic }) => {
+                        topic == &topics[0]
+                    }
+                    _ => false,
+                })
+                .count()
+        };
+
+        // Pruning all but the last mesh peer doesn't emit the event.
+        gs.handle_prune(&peers[0], vec![(topics[0].clone(), vec![], None)]);
+        assert_eq!(gs.mesh.get(&topics[0]).unwrap().len(), 1);
+        assert_eq!(count_mesh_empty(&gs), 0);
+
+        // Removing the last mesh peer emits the event once.
+        gs.inject_disconnected(&peers[1]);
+        assert!(gs.mesh.get(&topics[0]).unwrap().is_empty());
+        assert_eq!(count_mesh_empty(&gs), 1);
+
+        gs.handle_prune(&peers[0], vec![(topics[0].clone(), vec![], None)]);
+        assert_eq!(count_mesh_empty(&gs), 1);
+    }
+
     #[test]
     fn test_connect_to_px_peers_on_handle_prune() {
         let config: GossipsubConfig = GossipsubConfig::default();
@@ -2144,6 +2462,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_gossip_after_topic_without_messages() {
+        let config: GossipsubConfig = GossipsubConfig::default();
+
+        //subscribe to many topics of which only the last one has messages to gossip
+        let topics = (0..10).map(|i| format!("topic{}", i)).collect::<Vec<_>>();
+        let (mut gs, _, topic_hashes) = inject_nodes1()
+            .peer_no(config.mesh_n_low() + config.gossip_lazy() + 1)
+            .topics(topics)
+            .to_subscribe(true)
+            .create_network();
+
+        //receive message
+        let raw_message = RawGossipsubMessage {
+            source: Some(PeerId::random()),
+            data: vec![],
+            sequence_number: Some(0),
+            topic: topic_hashes[9].clone(),
+            signature: None,
+            key: None,
+            validated: true,
+        };
+        gs.handle_received_message(raw_message, &PeerId::random());
+
+        //emit gossip
+        gs.emit_gossip();
+
+        //check that the message is gossiped regardless of the topics without messages
+        assert_eq!(
+            count_control_msgs(&gs, |_, action| match action {
+                GossipsubControlAction::IHave { topic_hash, .. } => topic_hash == &topic_hashes[9],
+                _ => false,
+            }),
+            config.gossip_lazy()
+        );
+    }
+
     #[test]
     fn test_gossip_to_at_most_gossip_factor_peers() {
         let config: GossipsubConfig = GossipsubConfig::default();
@@ -2998,6 +3353,47 @@ mod tests {
         assert!(gs.events.len() > 1);
     }
 
+    #[test]
+    fn test_ignore_events_from_disconnected_peer() {
+        let (mut gs, peers, topic_hashes) = inject_nodes1()
+            .peer_no(1)
+            .topics(vec![String::from("topic1")])
+            .to_subscribe(true)
+            .create_network();
+
+        let peer = peers[0];
+        gs.inject_disconnected(&peer);
+        flush_events(&mut gs);
+
+        //events arriving after the disconnect, and from a peer never connected, are ignored
+        for peer in &[peer, PeerId::random()] {
+            gs.inject_event(
+                *peer,
+                ConnectionId::new(0),
+                HandlerEvent::Message {
+                    rpc: GossipsubRpc {
+                        messages: Vec::new(),
+                        subscriptions: vec![GossipsubSubscription {
+                            action: GossipsubSubscriptionAction::Subscribe,
+                            topic_hash: topic_hashes[0].clone(),
+                        }],
+                        control_msgs: vec![GossipsubControlAction::Graft {
+                            topic_hash: topic_hashes[0].clone(),
+                        }],
+                    },
+                    invalid_messages: Vec::new(),
+                },
+            );
+            gs.inject_event(
+                *peer,
+                ConnectionId::new(0),
+                HandlerEvent::PeerKind(PeerKind::Gossipsubv1_1),
+            );
+            assert!(!gs.mesh[&topic_hashes[0]].contains(peer));
+        }
+        assert!(gs.events.is_empty());
+    }
+
     #[test]
     fn test_ignore_px_from_peers_below_accept_px_threshold() {
         let config = GossipsubConfigBuilder::default()
@@ -3127,6 +3523,42 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_deterministic_retaining_of_equally_scored_peers() {
+        let config = GossipsubConfig::default();
+
+        for _ in 0..3 {
+            //build mesh with more peers than mesh can hold
+            let n = config.mesh_n_high() + 1;
+            let (mut gs, peers, topics) = inject_nodes1()
+                .peer_no(n)
+                .topics(vec!["test".into()])
+                .to_subscribe(true)
+                .gs_config(config.clone())
+                .explicit(0)
+                .outbound(n)
+                .create_network();
+
+            // graft all, will be accepted since the are outbound
+            for peer in &peers {
+                gs.handle_graft(peer, topics.clone());
+            }
+            assert_eq!(gs.mesh[&topics[0]].len(), n);
+
+            //heartbeat to prune some peers
+            gs.heartbeat();
+
+            //all peers have the same score, hence the peers with the highest peer ids are retained
+            let mut sorted = peers.clone();
+            sorted.sort();
+            let mesh = &gs.mesh[&topics[0]];
+            assert_eq!(mesh.len(), config.mesh_n());
+            for peer in &sorted[(n - config.retain_scores())..] {
+                assert!(mesh.contains(peer));
+            }
+        }
+    }
+
     #[test]
     fn test_scoring_p1() {
         let config = GossipsubConfig::default();
@@ -4384,6 +4816,316 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_limit_number_of_messages_served_through_iwant() {
+        let config = GossipsubConfigBuilder::default()
+            .max_iwant_messages(5)
+            .build()
+            .unwrap();
+        let mut peer_score_params = PeerScoreParams::default();
+        peer_score_params.behaviour_penalty_weight = -1.0;
+
+        //build gossipsub with full mesh
+        let (mut gs, _, topics) = inject_nodes1()
+            .peer_no(config.mesh_n_high())
+            .topics(vec!["test".into()])
+            .to_subscribe(false)
+            .gs_config(config.clone())
+            .scoring(Some((peer_score_params, PeerScoreThresholds::default())))
+            .create_network();
+
+        //add another peer not in the mesh
+        let peer = add_peer(&mut gs, &topics, false, false);
+
+        //receive some messages
+        let mut seq = 0;
+        let mut ids = Vec::new();
+        for _ in 0..10 {
+            let m = random_message(&mut seq, &topics);
+            let message = gs.data_transform.inbound_transform(m.clone()).unwrap();
+            ids.push(config.message_id(&message));
+            gs.handle_received_message(m, &PeerId::random());
+        }
+
+        //clear events
+        gs.events.clear();
+
+        //the peer asks for all messages, split over several IWANTs
+        gs.handle_iwant(&peer, ids[..3].to_vec());
+        gs.handle_iwant(&peer, ids[3..].to_vec());
+
+        assert_eq!(
+            count_sent_messages(&gs).values().sum::<usize>(),
+            config.max_iwant_messages(),
+            "only max_iwant_messages many messages get sent back"
+        );
+        assert_eq!(gs.peer_score.as_ref().unwrap().0.score(&peer), 0.0);
+
+        //further IWANTs within the heartbeat get ignored and penalized
+        gs.handle_iwant(&peer, ids[8..].to_vec());
+        assert_eq!(
+            count_sent_messages(&gs).values().sum::<usize>(),
+            config.max_iwant_messages()
+        );
+        assert_eq!(gs.peer_score.as_ref().unwrap().0.score(&peer), -1.0);
+
+        //after a heartbeat the peer gets served again
+        gs.heartbeat();
+        gs.events.clear();
+        gs.handle_iwant(&peer, ids[8..].to_vec());
+        assert_eq!(count_sent_messages(&gs).values().sum::<usize>(), 2);
+    }
+
+    #[test]
+    fn test_control_messages_from_non_mesh_peers() {
+        let config = GossipsubConfigBuilder::default()
+            .max_iwant_messages(5)
+            .max_iwant_messages_non_mesh(2)
+            .ignore_prune_from_non_mesh_peers(true)
+            .build()
+            .unwrap();
+
+        let (mut gs, _, topics) = inject_nodes1()
+            .peer_no(config.mesh_n())
+            .topics(vec!["test".into()])
+            .to_subscribe(true)
+            .gs_config(config.clone())
+            .create_network();
+
+        let mesh_peer = gs.mesh[&topics[0]].iter().next().cloned().unwrap();
+
+        //add another peer not in the mesh
+        let peer = add_peer(&mut gs, &topics, false, false);
+        assert!(!gs.mesh[&topics[0]].contains(&peer));
+
+        //receive some messages
+        let mut seq = 0;
+        let mut ids = Vec::new();
+        for _ in 0..10 {
+            let m = random_message(&mut seq, &topics);
+            let message = gs.data_transform.inbound_transform(m.clone()).unwrap();
+            ids.push(config.message_id(&message));
+            gs.handle_received_message(m, &PeerId::random());
+        }
+
+        //IWANTs of the mesh peer are subject to max_iwant_messages
+        gs.events.clear();
+        gs.handle_iwant(&mesh_peer, ids.clone());
+        assert_eq!(
+            count_sent_messages(&gs).get(&mesh_peer),
+            Some(&config.max_iwant_messages())
+        );
+
+        //IWANTs of the non-mesh peer are subject to max_iwant_messages_non_mesh
+        gs.handle_iwant(&peer, ids.clone());
+        assert_eq!(
+            count_sent_messages(&gs).get(&peer),
+            Some(&config.max_iwant_messages_non_mesh())
+        );
+
+        //a PRUNE from the non-mesh peer is a no-op
+        gs.handle_prune(&peer, vec![(topics[0].clone(), Vec::new(), Some(60))]);
+        assert!(!gs.backoffs.is_backoff_with_slack(&topics[0], &peer));
+
+        //a PRUNE from the mesh peer removes it from the mesh and applies the backoff
+        gs.handle_prune(&mesh_peer, vec![(topics[0].clone(), Vec::new(), Some(60))]);
+        assert!(!gs.mesh[&topics[0]].contains(&mesh_peer));
+        assert!(gs.backoffs.is_backoff_with_slack(&topics[0], &mesh_peer));
+    }
+
+    /// [`PollParameters`] of a swarm without any protocols or addresses.
+    struct DummyPollParameters(PeerId);
+
+    impl PollParameters for DummyPollParameters {
+        type SupportedProtocolsIter = std::iter::Empty<Vec<u8>>;
+        type ListenedAddressesIter = std::iter::Empty<Multiaddr>;
+        type ExternalAddressesIter = std::iter::Empty<libp2p_swarm::AddressRecord>;
+
+        fn supported_protocols(&self) -> Self::SupportedProtocolsIter {
+            std::iter::empty()
+        }
+
+        fn listened_addresses(&self) -> Self::ListenedAddressesIter {
+            std::iter::empty()
+        }
+
+        fn external_addresses(&self) -> Self::ExternalAddressesIter {
+            std::iter::empty()
+        }
+
+        fn local_peer_id(&self) -> &PeerId {
+            &self.0
+        }
+    }
+
+    #[test]
+    fn test_send_higher_priority_topics_first() {
+        let high = Topic::new("high");
+        let config = GossipsubConfigBuilder::default()
+            .topic_priority(high.hash(), 1)
+            .build()
+            .unwrap();
+
+        let (mut gs, _, topics) = inject_nodes1()
+            .peer_no(5)
+            .topics(vec!["low".into(), "high".into()])
+            .to_subscribe(true)
+            .gs_config(config)
+            .create_network();
+        gs.events.clear();
+
+        // nothing polls the behaviour, so all messages stay queued
+        for i in 0..3 {
+            gs.publish(Topic::new("low"), vec![i; 10]).unwrap();
+        }
+        gs.publish(high, vec![0; 10]).unwrap();
+
+        // polling the behaviour yields the queued messages in the order they are sent
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut params = DummyPollParameters(PeerId::random());
+        let mut queued_topics: Vec<TopicHash> = Vec::new();
+        while let Poll::Ready(event) = gs.poll(&mut cx, &mut params) {
+            if let NetworkBehaviourAction::NotifyHandler {
+                event: GossipsubHandlerIn::Message(m),
+                ..
+            } = event
+            {
+                queued_topics.extend(proto_to_message(&m).messages.into_iter().map(|m| m.topic));
+            }
+        }
+
+        let last_high = queued_topics
+            .iter()
+            .rposition(|t| t == &topics[1])
+            .expect("high-priority messages are queued");
+        let first_low = queued_topics
+            .iter()
+            .position(|t| t == &topics[0])
+            .expect("low-priority messages are queued");
+        assert!(
+            last_high < first_low,
+            "high-priority messages are sent before low-priority ones"
+        );
+    }
+
+    #[test]
+    fn test_priority_keeps_control_messages_in_order() {
+        let high = Topic::new("high");
+        let config = GossipsubConfigBuilder::default()
+            .topic_priority(high.hash(), 1)
+            .build()
+            .unwrap();
+
+        let (mut gs, peers, topics) = inject_nodes1()
+            .peer_no(5)
+            .topics(vec!["high".into()])
+            .to_subscribe(true)
+            .gs_config(config)
+            .create_network();
+        gs.events.clear();
+
+        // a control message queued for a peer before publishing a high-priority message
+        gs.send_message(
+            peers[0],
+            GossipsubRpc {
+                subscriptions: Vec::new(),
+                messages: Vec::new(),
+                control_msgs: vec![GossipsubControlAction::Graft {
+                    topic_hash: topics[0].clone(),
+                }],
+            }
+            .into_protobuf(),
+        )
+        .unwrap();
+        gs.publish(high, vec![0; 10]).unwrap();
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut params = DummyPollParameters(PeerId::random());
+        let mut sent = Vec::new();
+        while let Poll::Ready(event) = gs.poll(&mut cx, &mut params) {
+            if let NetworkBehaviourAction::NotifyHandler {
+                peer_id,
+                event: GossipsubHandlerIn::Message(m),
+                ..
+            } = event
+            {
+                let rpc = proto_to_message(&m);
+                sent.push((peer_id, rpc.control_msgs.is_empty()));
+            }
+        }
+
+        // the peer receives the control message first, the other peers only the message
+        let sent_to_peer = sent
+            .iter()
+            .filter(|(peer, _)| peer == &peers[0])
+            .map(|(_, is_message)| *is_message)
+            .collect::<Vec<_>>();
+        assert_eq!(sent_to_peer, vec![false, true]);
+        assert_eq!(sent.len(), peers.len() + 1);
+    }
+
+    #[test]
+    fn test_do_not_forward_messages_older_than_message_ttl() {
+        let config = GossipsubConfigBuilder::default()
+            .validate_messages()
+            .message_ttl(Some(Duration::from_millis(50)))
+            .build()
+            .unwrap();
+
+        //build gossipsub with full mesh
+        let (mut gs, peers, topics) = inject_nodes1()
+            .peer_no(config.mesh_n_high())
+            .topics(vec!["test".into()])
+            .to_subscribe(true)
+            .gs_config(config.clone())
+            .create_network();
+
+        //receive two messages
+        let mut seq = 0;
+        let m1 = random_message(&mut seq, &topics);
+        let m2 = random_message(&mut seq, &topics);
+        let id1 = config.message_id(&gs.data_transform.inbound_transform(m1.clone()).unwrap());
+        let id2 = config.message_id(&gs.data_transform.inbound_transform(m2.clone()).unwrap());
+        gs.handle_received_message(m1, &peers[0]);
+        gs.handle_received_message(m2, &peers[0]);
+
+        //the first message gets validated in time and is forwarded
+        gs.events.clear();
+        assert!(gs
+            .report_message_validation_result(&id1, &peers[0], MessageAcceptance::Accept)
+            .unwrap());
+        assert!(!count_sent_messages(&gs).is_empty());
+
+        //the second message gets validated after its ttl expired
+        sleep(Duration::from_millis(100));
+        gs.events.clear();
+        assert!(!gs
+            .report_message_validation_result(&id2, &peers[0], MessageAcceptance::Accept)
+            .unwrap());
+        assert!(
+            count_sent_messages(&gs).is_empty(),
+            "expired message must not be forwarded"
+        );
+
+        //expired messages are neither served through IWANT nor gossiped
+        gs.handle_iwant(&peers[1], vec![id1.clone(), id2]);
+        assert!(
+            count_sent_messages(&gs).is_empty(),
+            "expired message must not be served"
+        );
+        gs.emit_gossip();
+        assert_eq!(
+            count_control_msgs(&gs, |_, a| match a {
+                GossipsubControlAction::IHave { message_ids, .. } => message_ids.contains(&id1),
+                _ => false,
+            }),
+            0,
+            "expired message must not be gossiped"
+        );
+    }
+
     #[test]
     fn test_ignore_too_many_ihaves() {
         let config = GossipsubConfigBuilder::default()
@@ -5228,4 +5970,789 @@ mod tests {
         //nobody got penalized
         assert!(gs1.peer_score.as_ref().unwrap().0.score(&p2) >= original_score);
     }
+
+    #[test]
+    fn test_publish_rate_limit() {
+        let config = GossipsubConfigBuilder::default()
+            .publish_rate_limit(Some((3, Duration::from_millis(300))))
+            .build()
+            .unwrap();
+
+        let publish_topic = String::from("test_publish");
+        let other_topic = String::from("test_other");
+        let (mut gs, _, _) = inject_nodes1()
+            .peer_no(20)
+            .topics(vec![publish_topic.clone(), other_topic.clone()])
+            .to_subscribe(true)
+            .gs_config(config)
+            .create_network();
+
+        // publish faster than the limit allows
+        let results: Vec<_> = (0..10u8)
+            .map(|i| gs.publish(Topic::new(publish_topic.clone()), vec![i; 42]))
+            .collect();
+        assert!(results[..3].iter().all(|r| r.is_ok()));
+        assert!(results[3..]
+            .iter()
+            .all(|r| matches!(r, Err(PublishError::RateLimited))));
+
+        // the limit applies per topic
+        assert!(gs.publish(Topic::new(other_topic), vec![0; 42]).is_ok());
+
+        // once the interval has passed, publishing is allowed again
+        sleep(Duration::from_millis(300));
+        assert!(gs.publish(Topic::new(publish_topic), vec![42; 42]).is_ok());
+    }
+
+    #[test]
+    fn test_max_fanout_topics() {
+        let config = GossipsubConfigBuilder::default()
+            .flood_publish(false)
+            .max_fanout_topics(Some(3))
+            .build()
+            .unwrap();
+
+        let (mut gs, _, _) = inject_nodes1()
+            .peer_no(0)
+            .topics(Vec::new())
+            .to_subscribe(false)
+            .gs_config(config)
+            .create_network();
+
+        let topics = (0..10)
+            .map(|i| Topic::new(format!("topic{}", i)))
+            .collect::<Vec<Topic>>();
+        let topic_hashes = topics.iter().map(|t| t.hash()).collect();
+        for _ in 0..20 {
+            add_peer(&mut gs, &topic_hashes, false, false);
+        }
+
+        for topic in &topics {
+            gs.publish(topic.clone(), vec![42; 42]).unwrap();
+
+            // the number of fanout topics is bounded
+            assert!(gs.fanout.len() <= 3);
+            assert_eq!(gs.fanout.len(), gs.fanout_last_pub.len());
+            // the number of fanout peers per topic is bounded
+            for peers in gs.fanout.values() {
+                assert!(peers.len() <= gs.config.mesh_n());
+            }
+            // the topic just published to is never evicted
+            assert!(gs.fanout.contains_key(&topic.hash()));
+        }
+        assert_eq!(gs.fanout.len(), 3);
+    }
+
+    #[test]
+    fn test_mesh_and_fanout_snapshots() {
+        let config = GossipsubConfigBuilder::default()
+            .flood_publish(false)
+            .build()
+            .unwrap();
+
+        let (mut gs, peers, topic_hashes) = inject_nodes1()
+            .peer_no(20)
+            .topics(vec![String::from("topic1"), String::from("topic2")])
+            .to_subscribe(true)
+            .gs_config(config)
+            .create_network();
+
+        gs.handle_graft(&peers[7], vec![topic_hashes[0].clone()]);
+        gs.handle_graft(&peers[8], vec![topic_hashes[0].clone()]);
+
+        // publishing on a topic we are not subscribed to creates a fanout
+        gs.unsubscribe(&Topic::new("topic2")).unwrap();
+        gs.publish(Topic::new("topic2"), vec![1; 10]).unwrap();
+
+        let as_sets = |snapshot: HashMap<TopicHash, Vec<PeerId>>| {
+            snapshot
+                .into_iter()
+                .map(|(topic, peers)| (topic, peers.into_iter().collect::<BTreeSet<_>>()))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let mesh = as_sets(gs.mesh_snapshot());
+        assert_eq!(mesh.len(), 1);
+        assert!(mesh[&topic_hashes[0]].contains(&peers[7]));
+        assert!(mesh[&topic_hashes[0]].contains(&peers[8]));
+        assert_eq!(
+            mesh[&topic_hashes[0]],
+            gs.mesh[&topic_hashes[0]].iter().cloned().collect()
+        );
+
+        let fanout = as_sets(gs.fanout_snapshot());
+        assert_eq!(fanout.len(), 1);
+        assert!(!fanout[&topic_hashes[1]].is_empty());
+        assert_eq!(
+            fanout[&topic_hashes[1]],
+            gs.fanout[&topic_hashes[1]].iter().cloned().collect()
+        );
+    }
+
+    #[test]
+    fn test_prune_grafts_exceeding_max_mesh_topics_per_peer() {
+        let config = GossipsubConfigBuilder::default()
+            .max_mesh_topics_per_peer(Some(2))
+            .build()
+            .unwrap();
+
+        let (mut gs, _, topics) = inject_nodes1()
+            .peer_no(0)
+            .topics(vec!["t1".into(), "t2".into(), "t3".into(), "t4".into()])
+            .to_subscribe(true)
+            .gs_config(config)
+            .scoring(Some((
+                PeerScoreParams::default(),
+                PeerScoreThresholds::default(),
+            )))
+            .create_network();
+
+        let peer = add_peer(&mut gs, &topics, false, false);
+        //start with the peer not being in any mesh
+        for topic in &topics {
+            gs.mesh.get_mut(topic).unwrap().remove(&peer);
+        }
+        gs.events.clear();
+
+        //graft for more topics than allowed
+        gs.handle_graft(&peer, topics.clone());
+
+        let mesh_topics: Vec<_> = topics
+            .iter()
+            .filter(|t| gs.mesh[*t].contains(&peer))
+            .cloned()
+            .collect();
+        assert_eq!(mesh_topics, topics[..2].to_vec());
+
+        //the excess grafts got pruned
+        for topic in &topics[2..] {
+            assert_eq!(
+                count_control_msgs(&gs, |peer_id, m| peer_id == &peer
+                    && match m {
+                        GossipsubControlAction::Prune { topic_hash, .. } => topic_hash == topic,
+                        _ => false,
+                    }),
+                1
+            );
+        }
+
+        //and the peer got penalized
+        assert!(gs.peer_score(&peer).unwrap() < 0.0);
+    }
+
+    #[test]
+    fn test_leaf_node_does_not_forward_messages() {
+        let config = GossipsubConfigBuilder::default()
+            .forwarding_enabled(false)
+            .build()
+            .unwrap();
+
+        //build gossipsub with peers subscribed to the topic
+        let (mut gs, peers, topics) = inject_nodes1()
+            .peer_no(config.mesh_n_high())
+            .topics(vec!["test".into()])
+            .to_subscribe(true)
+            .gs_config(config.clone())
+            .create_network();
+
+        //the leaf node does not graft any peers, neither on joining nor during the heartbeat
+        gs.heartbeat();
+        assert!(gs.mesh[&topics[0]].is_empty());
+        assert_eq!(
+            count_control_msgs(&gs, |_, m| matches!(
+                m,
+                GossipsubControlAction::Graft { .. }
+            )),
+            0
+        );
+        gs.events.clear();
+
+        //receive a message from a mesh peer
+        let mut seq = 0;
+        let message = random_message(&mut seq, &topics);
+        gs.handle_received_message(message.clone(), &peers[0]);
+
+        //the message is delivered locally but not forwarded to any peer
+        let mut delivered = 0;
+        for e in &gs.events {
+            match e {
+                NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message { .. }) => {
+                    delivered += 1
+                }
+                NetworkBehaviourAction::NotifyHandler { event, peer_id, .. } => {
+                    if let GossipsubHandlerIn::Message(ref m) = **event {
+                        assert!(
+                            proto_to_message(m).messages.is_empty(),
+                            "Leaf node forwarded a message to {}",
+                            peer_id
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(delivered, 1);
+
+        //duplicates are still filtered
+        gs.events.clear();
+        gs.handle_received_message(message, &peers[1]);
+        assert!(gs.events.is_empty());
+
+        //grafts from other peers are answered with a prune
+        let peer = add_peer(&mut gs, &topics, false, false);
+        gs.mesh.get_mut(&topics[0]).unwrap().remove(&peer);
+        gs.events.clear();
+        gs.handle_graft(&peer, topics.clone());
+        assert!(!gs.mesh[&topics[0]].contains(&peer));
+        assert_eq!(
+            count_control_msgs(&gs, |peer_id, m| peer_id == &peer
+                && match m {
+                    GossipsubControlAction::Prune { topic_hash, .. } => topic_hash == &topics[0],
+                    _ => false,
+                }),
+            1
+        );
+    }
+
+    #[test]
+    fn test_leaf_node_only_gossips_and_serves_published_messages() {
+        let config = GossipsubConfigBuilder::default()
+            .forwarding_enabled(false)
+            .build()
+            .unwrap();
+
+        //build gossipsub with peers subscribed to the topic
+        let (mut gs, peers, topics) = inject_nodes1()
+            .peer_no(config.mesh_n_high())
+            .topics(vec!["test".into()])
+            .to_subscribe(true)
+            .gs_config(config)
+            .create_network();
+
+        //receive a message from a peer
+        let mut seq = 0;
+        gs.handle_received_message(random_message(&mut seq, &topics), &peers[0]);
+        let received_id = gs.cached_message_ids().pop().unwrap();
+
+        //publish a message
+        let published_id = gs.publish(Topic::new("test"), vec![1, 2, 3]).unwrap();
+        gs.events.clear();
+
+        //only the published message is gossiped
+        gs.emit_gossip();
+        gs.flush_control_pool();
+        let ihaves = |id: &MessageId| {
+            count_control_msgs(&gs, |_, m| match m {
+                GossipsubControlAction::IHave { message_ids, .. } => message_ids.contains(id),
+                _ => false,
+            })
+        };
+        assert!(ihaves(&published_id) > 0);
+        assert_eq!(
+            ihaves(&received_id),
+            0,
+            "received message must not be gossiped"
+        );
+        gs.events.clear();
+
+        //and only the published message is served
+        gs.handle_iwant(&peers[1], vec![received_id]);
+        assert!(
+            count_sent_messages(&gs).is_empty(),
+            "received message must not be served"
+        );
+        gs.handle_iwant(&peers[1], vec![published_id]);
+        assert_eq!(count_sent_messages(&gs).get(&peers[1]), Some(&1));
+    }
+
+    #[test]
+    fn test_published_message_is_listed_in_cache() {
+        let (mut gs, _, topic_hashes) = inject_nodes1()
+            .peer_no(20)
+            .topics(vec![String::from("test_publish")])
+            .to_subscribe(true)
+            .create_network();
+
+        assert!(gs.cached_message_ids().is_empty());
+
+        let msg_id = gs
+            .publish(Topic::new("test_publish"), vec![1, 2, 3])
+            .unwrap();
+
+        assert_eq!(gs.cached_message_ids(), vec![msg_id.clone()]);
+        let message = gs.get_cached_message(&msg_id).unwrap();
+        assert_eq!(message.topic, topic_hashes[0]);
+        assert_eq!(message.data, vec![1, 2, 3]);
+        assert!(gs.get_cached_message(&MessageId::new(b"unknown")).is_none());
+    }
+
+    #[test]
+    fn test_publish_right_after_subscribing_is_sent_once_mesh_forms() {
+        let config = GossipsubConfigBuilder::default()
+            .publish_grace_period(Some(Duration::from_secs(10)))
+            .build()
+            .unwrap();
+
+        let (mut gs, _, _) = inject_nodes1()
+            .peer_no(0)
+            .topics(vec![])
+            .to_subscribe(false)
+            .gs_config(config.clone())
+            .create_network();
+
+        let topic = Topic::new("test");
+        gs.subscribe(&topic).unwrap();
+
+        //publishing right away does not fail although there are no peers yet
+        let msg_id = gs.publish(topic.clone(), vec![1; 10]).unwrap();
+        assert!(count_sent_messages(&gs).is_empty());
+        assert!(gs.get_cached_message(&msg_id).is_none());
+
+        //the mesh forms as peers subscribe
+        let peers: Vec<_> = (0..config.mesh_n_low())
+            .map(|_| add_peer(&mut gs, &vec![topic.hash()], false, false))
+            .collect();
+        assert_eq!(gs.mesh[&topic.hash()].len(), config.mesh_n_low());
+
+        //the queued message is sent to the mesh on the next heartbeat
+        gs.heartbeat();
+        let sent = count_sent_messages(&gs);
+        assert_eq!(sent.len(), peers.len());
+        assert!(peers.iter().all(|p| sent.get(p) == Some(&1)));
+        assert!(gs.get_cached_message(&msg_id).is_some());
+
+        //further messages are published immediately
+        gs.events.clear();
+        gs.publish(topic, vec![2; 10]).unwrap();
+        assert_eq!(count_sent_messages(&gs).len(), peers.len());
+    }
+
+    #[test]
+    fn test_publish_right_after_subscribing_is_flood_published_after_grace_period() {
+        let config = GossipsubConfigBuilder::default()
+            .publish_grace_period(Some(Duration::from_millis(100)))
+            .build()
+            .unwrap();
+
+        let (mut gs, _, _) = inject_nodes1()
+            .peer_no(0)
+            .topics(vec![])
+            .to_subscribe(false)
+            .gs_config(config)
+            .create_network();
+
+        let topic = Topic::new("test");
+        gs.subscribe(&topic).unwrap();
+        gs.publish(topic.clone(), vec![1; 10]).unwrap();
+
+        //a single peer is not enough for the mesh to form
+        let peer = add_peer(&mut gs, &vec![topic.hash()], false, false);
+        gs.heartbeat();
+        assert!(count_sent_messages(&gs).is_empty());
+
+        //once the grace period elapsed the message is flood published
+        sleep(Duration::from_millis(100));
+        gs.heartbeat();
+        let sent = count_sent_messages(&gs);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent.get(&peer), Some(&1));
+    }
+
+    #[test]
+    fn test_publish_right_after_subscribing_is_reported_dropped_without_peers() {
+        let config = GossipsubConfigBuilder::default()
+            .publish_grace_period(Some(Duration::from_millis(100)))
+            .build()
+            .unwrap();
+
+        let (mut gs, _, _) = inject_nodes1()
+            .peer_no(0)
+            .topics(vec![])
+            .to_subscribe(false)
+            .gs_config(config)
+            .create_network();
+
+        let topic = Topic::new("test");
+        gs.subscribe(&topic).unwrap();
+        let msg_id = gs.publish(topic.clone(), vec![1; 10]).unwrap();
+
+        //once the grace period elapsed without any peers the message is reported as dropped
+        sleep(Duration::from_millis(100));
+        gs.events.clear();
+        gs.heartbeat();
+        assert!(count_sent_messages(&gs).is_empty());
+        assert!(gs.events.iter().any(|e| matches!(
+            e,
+            NetworkBehaviourAction::GenerateEvent(GossipsubEvent::PublishDropped {
+                message_id,
+                topic: t,
+            }) if *message_id == msg_id && *t == topic.hash()
+        )));
+    }
+
+    #[test]
+    fn test_reset_peer_score() {
+        let (mut gs, peers, topics) = inject_nodes1()
+            .peer_no(2)
+            .topics(vec!["test".into()])
+            .to_subscribe(true)
+            .gs_config(GossipsubConfig::default())
+            .explicit(0)
+            .outbound(0)
+            .scoring(Some((
+                PeerScoreParams::default(),
+                PeerScoreThresholds::default(),
+            )))
+            .create_network();
+
+        gs.peer_score.as_mut().unwrap().0.add_penalty(&peers[0], 2);
+
+        let snapshot = gs.score_snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert!(snapshot[&peers[0]] < 0.0);
+        assert_eq!(snapshot[&peers[1]], 0.0);
+        assert_eq!(Some(snapshot[&peers[0]]), gs.peer_score(&peers[0]));
+
+        assert!(gs.reset_peer_score(&peers[0]));
+        assert_eq!(gs.peer_score(&peers[0]), Some(0.0));
+        assert_eq!(gs.score_snapshot()[&peers[0]], 0.0);
+
+        // the peer stays in the mesh
+        assert!(gs.mesh[&topics[0]].contains(&peers[0]));
+
+        // unknown peers can't be reset
+        assert!(!gs.reset_peer_score(&PeerId::random()));
+    }
+
+    #[test]
+    fn test_ignore_subscriptions_from_local_peer() {
+        let (mut gs, _, topic_hashes) = inject_nodes1()
+            .peer_no(1)
+            .topics(vec![String::from("topic1")])
+            .to_subscribe(true)
+            .create_network();
+
+        // a connection appearing to come from ourselves, whose id is learned when polled
+        let local_id = PeerId::random();
+        gs.local_peer_id = Some(local_id);
+        gs.inject_connection_established(
+            &local_id,
+            &ConnectionId::new(0),
+            &ConnectedPoint::Dialer {
+                address: Multiaddr::empty(),
+            },
+        );
+        <Gossipsub<_, _> as NetworkBehaviour>::inject_connected(&mut gs, &local_id);
+        gs.events.clear();
+
+        gs.handle_received_subscriptions(
+            &[GossipsubSubscription {
+                action: GossipsubSubscriptionAction::Subscribe,
+                topic_hash: topic_hashes[0].clone(),
+            }],
+            &local_id,
+        );
+
+        assert!(!gs.topic_peers[&topic_hashes[0]].contains(&local_id));
+        assert!(gs
+            .peer_topics
+            .get(&local_id)
+            .map_or(true, |topics| topics.is_empty()));
+        assert!(!gs.mesh[&topic_hashes[0]].contains(&local_id));
+        assert!(gs.events.is_empty());
+    }
+
+    #[test]
+    fn test_publish_acknowledged() {
+        let config = GossipsubConfigBuilder::default()
+            .acknowledgement_timeout(Some(Duration::from_millis(100)))
+            .build()
+            .unwrap();
+
+        let (mut gs, peers, topic_hashes) = inject_nodes1()
+            .peer_no(3)
+            .topics(vec![String::from("topic1")])
+            .to_subscribe(true)
+            .gs_config(config)
+            .create_network();
+        assert_eq!(gs.mesh[&topic_hashes[0]].len(), 3);
+
+        let msg_id = gs
+            .publish_acknowledged(
+                Topic::new("topic1"),
+                vec![1; 10],
+                NonZeroUsize::new(2).unwrap(),
+            )
+            .unwrap();
+
+        // every mesh peer is asked for an acknowledgement
+        for peer in &peers {
+            assert_eq!(
+                count_control_msgs(&gs, |peer_id, m| peer_id == peer
+                    && match m {
+                        GossipsubControlAction::AckRequest { message_ids } =>
+                            message_ids == &vec![msg_id.clone()],
+                        _ => false,
+                    }),
+                1
+            );
+        }
+        flush_events(&mut gs);
+
+        let acknowledged = |gs: &Gossipsub<_, _>| {
+            gs.events
+                .iter()
+                .filter_map(|e| match e {
+                    NetworkBehaviourAction::GenerateEvent(GossipsubEvent::PublishAcknowledged {
+                        message_id,
+                        acks,
+                    }) => Some((message_id.clone(), acks.clone())),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // unrequested and repeated acknowledgements don't count
+        gs.handle_ack(&PeerId::random(), vec![msg_id.clone()]);
+        gs.handle_ack(&peers[0], vec![msg_id.clone()]);
+        gs.handle_ack(&peers[0], vec![msg_id.clone()]);
+        assert!(acknowledged(&gs).is_empty());
+
+        gs.handle_ack(&peers[2], vec![msg_id.clone()]);
+        assert_eq!(acknowledged(&gs), vec![(msg_id.clone(), vec![peers[0], peers[2]])]);
+        assert!(gs.pending_acknowledgements.is_empty());
+
+        // mesh peers acknowledge messages they accepted
+        flush_events(&mut gs);
+        gs.handle_ack_request(&peers[1], vec![msg_id.clone(), MessageId::new(&[0])]);
+        assert_eq!(
+            count_control_msgs(&gs, |peer_id, m| peer_id == &peers[1]
+                && match m {
+                    GossipsubControlAction::Ack { message_ids } => message_ids == &vec![msg_id.clone()],
+                    _ => false,
+                }),
+            1
+        );
+
+        // the publish is reported once the timeout elapsed
+        flush_events(&mut gs);
+        let msg_id = gs
+            .publish_acknowledged(
+                Topic::new("topic1"),
+                vec![2; 10],
+                NonZeroUsize::new(2).unwrap(),
+            )
+            .unwrap();
+        gs.handle_ack(&peers[1], vec![msg_id.clone()]);
+        gs.heartbeat();
+        assert!(acknowledged(&gs).is_empty());
+        sleep(Duration::from_millis(100));
+        gs.heartbeat();
+        assert_eq!(acknowledged(&gs), vec![(msg_id, vec![peers[1]])]);
+    }
+
+    #[test]
+    fn test_acknowledge_only_validated_messages() {
+        let config = GossipsubConfigBuilder::default()
+            .acknowledgement_timeout(Some(Duration::from_millis(100)))
+            .validate_messages()
+            .build()
+            .unwrap();
+
+        let (mut gs, peers, topics) = inject_nodes1()
+            .peer_no(2)
+            .topics(vec![String::from("topic1")])
+            .to_subscribe(true)
+            .gs_config(config.clone())
+            .create_network();
+
+        // messages arrive unvalidated from the wire
+        let mut seq = 0;
+        let mut message = random_message(&mut seq, &topics);
+        message.validated = false;
+        let msg_id = config.message_id(
+            &gs.data_transform
+                .inbound_transform(message.clone())
+                .unwrap(),
+        );
+        gs.handle_received_message(message, &peers[0]);
+
+        let acks = |gs: &Gossipsub<_, _>| {
+            count_control_msgs(gs, |peer_id, m| {
+                peer_id == &peers[1]
+                    && matches!(m, GossipsubControlAction::Ack { message_ids }
+                        if message_ids == &vec![msg_id.clone()])
+            })
+        };
+
+        // a message pending validation is not acknowledged
+        flush_events(&mut gs);
+        gs.handle_ack_request(&peers[1], vec![msg_id.clone()]);
+        assert_eq!(acks(&gs), 0);
+
+        // once accepted it is
+        gs.report_message_validation_result(&msg_id, &peers[0], MessageAcceptance::Accept)
+            .unwrap();
+        flush_events(&mut gs);
+        gs.handle_ack_request(&peers[1], vec![msg_id.clone()]);
+        assert_eq!(acks(&gs), 1);
+    }
+
+    #[test]
+    fn test_publish_acknowledged_requires_extension() {
+        let (mut gs, _, _) = inject_nodes1()
+            .peer_no(3)
+            .topics(vec![String::from("topic1")])
+            .to_subscribe(true)
+            .create_network();
+
+        assert!(matches!(
+            gs.publish_acknowledged(
+                Topic::new("topic1"),
+                vec![1; 10],
+                NonZeroUsize::new(1).unwrap(),
+            ),
+            Err(PublishError::AcknowledgementsDisabled)
+        ));
+    }
+
+    #[test]
+    fn test_data_transform_compression() {
+        use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+        use std::io::{Read, Write};
+
+        /// Compresses the data of all messages using gzip.
+        #[derive(Default, Clone, Debug)]
+        struct GzipTransform;
+
+        impl DataTransform for GzipTransform {
+            fn inbound_transform(
+                &self,
+                raw_message: RawGossipsubMessage,
+            ) -> Result<GossipsubMessage, std::io::Error> {
+                let mut data = Vec::new();
+                GzDecoder::new(&raw_message.data[..]).read_to_end(&mut data)?;
+                Ok(GossipsubMessage {
+                    source: raw_message.source,
+                    data,
+                    sequence_number: raw_message.sequence_number,
+                    topic: raw_message.topic,
+                })
+            }
+
+            fn outbound_transform(
+                &self,
+                _topic: &TopicHash,
+                data: Vec<u8>,
+            ) -> Result<Vec<u8>, std::io::Error> {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&data)?;
+                encoder.finish()
+            }
+        }
+
+        let topic = String::from("test_compression");
+        let (mut gs1, _, _) = inject_nodes::<GzipTransform, AllowAllSubscriptionFilter>()
+            .peer_no(1)
+            .topics(vec![topic.clone()])
+            .to_subscribe(true)
+            .create_network();
+        let (mut gs2, peers, _) = inject_nodes::<GzipTransform, AllowAllSubscriptionFilter>()
+            .peer_no(1)
+            .topics(vec![topic.clone()])
+            .to_subscribe(true)
+            .create_network();
+
+        let payload = "a highly compressible payload ".repeat(100).into_bytes();
+        gs1.publish(Topic::new(topic), payload.clone()).unwrap();
+
+        // the data is sent compressed
+        let raw_messages = gs1
+            .events
+            .iter()
+            .fold(vec![], |mut collected_publish, e| match e {
+                NetworkBehaviourAction::NotifyHandler { event, .. } => match **event {
+                    GossipsubHandlerIn::Message(ref message) => {
+                        collected_publish.extend(proto_to_message(message).messages);
+                        collected_publish
+                    }
+                    _ => collected_publish,
+                },
+                _ => collected_publish,
+            });
+        assert_eq!(raw_messages.len(), 1);
+        assert!(raw_messages[0].data.len() < payload.len());
+
+        // and decompressed again by the receiver
+        flush_events(&mut gs2);
+        gs2.inject_event(
+            peers[0],
+            ConnectionId::new(0),
+            HandlerEvent::Message {
+                rpc: GossipsubRpc {
+                    messages: raw_messages,
+                    subscriptions: vec![],
+                    control_msgs: vec![],
+                },
+                invalid_messages: vec![],
+            },
+        );
+        let received = gs2
+            .events
+            .iter()
+            .filter_map(|e| match e {
+                NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message {
+                    message, ..
+                }) => Some(message.data.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(received, vec![payload]);
+    }
+
+    #[test]
+    fn test_subscribe_pattern() {
+        let (mut gs, peers, _) = inject_nodes1()
+            .peer_no(2)
+            .topics(Vec::new())
+            .to_subscribe(false)
+            .create_network();
+
+        let monitored = TopicHash::from_raw("monitor/a");
+        let other = TopicHash::from_raw("other");
+
+        //a peer advertises a matching topic before the pattern is added
+        let subscribe = |topic_hash: &TopicHash| {
+            vec![GossipsubSubscription {
+                action: GossipsubSubscriptionAction::Subscribe,
+                topic_hash: topic_hash.clone(),
+            }]
+        };
+        let early = TopicHash::from_raw("monitor/early");
+        gs.handle_received_subscriptions(&subscribe(&early), &peers[1]);
+        assert!(!gs.mesh.contains_key(&early));
+
+        gs.subscribe_pattern(Box::new(|topic_hash: &TopicHash| {
+            topic_hash.as_str().starts_with("monitor/")
+        }));
+        assert!(gs.mesh[&early].contains(&peers[1]));
+
+        //peers dynamically advertise a matching and a non-matching topic
+        gs.handle_received_subscriptions(&subscribe(&monitored), &peers[0]);
+        gs.handle_received_subscriptions(&subscribe(&other), &peers[0]);
+
+        assert!(gs.mesh[&monitored].contains(&peers[0]));
+        assert!(!gs.mesh.contains_key(&other));
+
+        //messages on the matching topic are delivered
+        gs.events.clear();
+        let mut seq = 0;
+        let message = random_message(&mut seq, &vec![monitored.clone()]);
+        gs.handle_received_message(message, &peers[0]);
+        assert!(gs.events.iter().any(|e| matches!(
+            e,
+            NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message { message, .. })
+                if message.topic == monitored
+        )));
+    }
 }
@@ -19,10 +19,12 @@
 // DEALINGS IN THE SOFTWARE.
 
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::time::Duration;
 
 use libp2p_core::PeerId;
 
+use crate::topic::TopicHash;
 use crate::types::{FastMessageId, GossipsubMessage, MessageId, RawGossipsubMessage};
 
 /// The types of message validation that can be employed by gossipsub.
@@ -63,8 +65,10 @@ pub struct GossipsubConfig {
     heartbeat_initial_delay: Duration,
     heartbeat_interval: Duration,
     fanout_ttl: Duration,
+    max_fanout_topics: Option<usize>,
     check_explicit_peers_ticks: u64,
     max_transmit_size: usize,
+    publish_batch_delay: Option<Duration>,
     idle_timeout: Duration,
     duplicate_cache_time: Duration,
     validate_messages: bool,
@@ -85,9 +89,19 @@ pub struct GossipsubConfig {
     max_messages_per_rpc: Option<usize>,
     max_ihave_length: usize,
     max_ihave_messages: usize,
+    max_iwant_messages: usize,
+    max_iwant_messages_non_mesh: usize,
+    ignore_prune_from_non_mesh_peers: bool,
+    topic_priorities: HashMap<TopicHash, u8>,
     iwant_followup_time: Duration,
     support_floodsub: bool,
     published_message_ids_cache_time: Duration,
+    message_ttl: Option<Duration>,
+    publish_rate_limit: Option<(usize, Duration)>,
+    max_mesh_topics_per_peer: Option<usize>,
+    forwarding_enabled: bool,
+    publish_grace_period: Option<Duration>,
+    acknowledgement_timeout: Option<Duration>,
 }
 
 impl GossipsubConfig {
@@ -132,7 +146,8 @@ impl GossipsubConfig {
     /// Affects how peers are selected when pruning a mesh due to over subscription.
     ///
     ///  At least `retain_scores` of the retained peers will be high-scoring, while the remainder are
-    ///  chosen randomly (D_score in the spec, default is 4).
+    ///  chosen randomly (D_score in the spec, default is 4). Ties between equally scored peers
+    ///  are broken by their [`PeerId`] when selecting the high-scoring peers.
     pub fn retain_scores(&self) -> usize {
         self.retain_scores
     }
@@ -166,6 +181,13 @@ impl GossipsubConfig {
         self.fanout_ttl
     }
 
+    /// The maximum number of topics we keep fanout peers for. Publishing to a new topic once the
+    /// limit is reached evicts the topic we published to least recently. If this is unset, there
+    /// is no limit. The default is None.
+    pub fn max_fanout_topics(&self) -> Option<usize> {
+        self.max_fanout_topics
+    }
+
     /// The number of heartbeat ticks until we recheck the connection to explicit peers and
     /// reconnecting if necessary (default 300).
     pub fn check_explicit_peers_ticks(&self) -> u64 {
@@ -182,6 +204,15 @@ impl GossipsubConfig {
         self.max_transmit_size
     }
 
+    /// The time outbound RPCs are held back by a connection handler so that RPCs sent in quick
+    /// succession are coalesced into fewer frames, as long as the coalesced RPC does not exceed
+    /// [`Self::max_transmit_size`]. If `None`, RPCs are sent as soon as possible.
+    ///
+    /// The default is None.
+    pub fn publish_batch_delay(&self) -> Option<Duration> {
+        self.publish_batch_delay
+    }
+
     /// The time a connection is maintained to a peer without being in the mesh and without
     /// send/receiving a message from. Connections that idle beyond this timeout are disconnected.
     /// Default is 120 seconds.
@@ -342,6 +373,44 @@ impl GossipsubConfig {
         self.max_ihave_messages
     }
 
+    /// The maximum number of messages we will send to a single peer in response to its IWANT
+    /// requests within a heartbeat. Requests beyond this limit are ignored and the peer receives
+    /// a behavioural penalty, protecting us from peers using IWANT to amplify our upload. The
+    /// default is 5000.
+    pub fn max_iwant_messages(&self) -> usize {
+        self.max_iwant_messages
+    }
+
+    /// The maximum number of messages we will send within a heartbeat in response to IWANT
+    /// requests of a peer that is not in any of our meshes. Such peers only learn about our
+    /// messages through gossip, so this is usually set lower than [`Self::max_iwant_messages`];
+    /// the smaller of both limits applies. The default is 5000.
+    pub fn max_iwant_messages_non_mesh(&self) -> usize {
+        self.max_iwant_messages_non_mesh
+    }
+
+    /// Whether to ignore PRUNE messages for topics in whose mesh we don't have the sending peer.
+    ///
+    /// Control messages from peers outside of a topic's mesh are handled as follows: GRAFT is
+    /// the regular way of joining a mesh and is processed as usual, IHAVE is rate limited by
+    /// [`Self::max_ihave_messages`], IWANT is rate limited by
+    /// [`Self::max_iwant_messages_non_mesh`] and PRUNE is a no-op if this option is enabled.
+    /// Otherwise such a PRUNE still sets the backoff requested by the peer and is used for peer
+    /// exchange. The default is false.
+    pub fn ignore_prune_from_non_mesh_peers(&self) -> bool {
+        self.ignore_prune_from_non_mesh_peers
+    }
+
+    /// The send priorities of topics. When messages queue up before being handed to the
+    /// connection handlers, e.g. because the swarm is busy, RPCs containing messages of a
+    /// higher-priority topic are sent before those of lower priority. RPCs of the same priority
+    /// are sent in order. Topics without a configured priority, as well as RPCs without messages
+    /// or carrying subscriptions or control messages, have priority 0. By default no priorities
+    /// are configured.
+    pub fn topic_priorities(&self) -> &HashMap<TopicHash, u8> {
+        &self.topic_priorities
+    }
+
     /// Time to wait for a message requested through IWANT following an IHAVE advertisement.
     /// If the message is not received within this window, a broken promise is declared and
     /// the router may apply behavioural penalties. The default is 3 seconds.
@@ -358,6 +427,63 @@ impl GossipsubConfig {
     pub fn published_message_ids_cache_time(&self) -> Duration {
         self.published_message_ids_cache_time
     }
+
+    /// The maximum time a received message is considered for propagation. Messages that were
+    /// received longer than this ago are no longer forwarded, advertised through IHAVE or served
+    /// in response to IWANT requests. This matters mostly when
+    /// [`GossipsubConfig::validate_messages`] is set, as the application may report the validation
+    /// result long after the message arrived. The default is `None`, i.e. unlimited.
+    pub fn message_ttl(&self) -> Option<Duration> {
+        self.message_ttl
+    }
+
+    /// Limits the number of messages the local node may publish on a single topic. When set to
+    /// `Some((max_messages, interval))`, at most `max_messages` messages are published per topic
+    /// within any window of length `interval`. Further calls to [`crate::Gossipsub::publish`] fail
+    /// with [`crate::error::PublishError::RateLimited`] without sending anything. This acts as a
+    /// safety valve against buggy producers flooding the mesh. The default is `None`, i.e.
+    /// unlimited.
+    pub fn publish_rate_limit(&self) -> Option<(usize, Duration)> {
+        self.publish_rate_limit
+    }
+
+    /// The maximum number of topics for which a single peer may be in our mesh. GRAFTs from a
+    /// peer that is already in the mesh of this many topics are answered with a PRUNE and the
+    /// peer receives a behavioural penalty for each of them. If this is unset, there is no limit.
+    /// The default is None.
+    pub fn max_mesh_topics_per_peer(&self) -> Option<usize> {
+        self.max_mesh_topics_per_peer
+    }
+
+    /// Whether to forward messages received from other peers. If disabled, the node acts as a
+    /// pure subscriber: received messages are still deduplicated and delivered locally, but never
+    /// forwarded, gossiped or served through IWANT, no peers are grafted into the mesh and GRAFTs
+    /// from other peers are answered with a PRUNE. Messages published by the local node are
+    /// unaffected. The default is true.
+    pub fn forwarding_enabled(&self) -> bool {
+        self.forwarding_enabled
+    }
+
+    /// The time after subscribing to a topic during which published messages are held back until
+    /// the mesh of the topic has reached `mesh_n_low` peers, so that messages published right
+    /// after subscribing are not lost. Queued messages are published to the mesh once it has
+    /// formed, or flood published to all peers subscribed to the topic once the grace period has
+    /// elapsed. Queued messages are checked on every heartbeat. The default is `None`, i.e.
+    /// messages are never held back.
+    pub fn publish_grace_period(&self) -> Option<Duration> {
+        self.publish_grace_period
+    }
+
+    /// Enables the acknowledgement extension, which is not part of the gossipsub spec. If set,
+    /// messages can be published through
+    /// [`Gossipsub::publish_acknowledged`](crate::Gossipsub::publish_acknowledged), requesting
+    /// the mesh peers of the topic to acknowledge that they accepted the message, and we
+    /// acknowledge messages for peers requesting it. The outcome of an acknowledged publish is
+    /// reported once enough acknowledgements arrived or this timeout elapsed, which is checked on
+    /// every heartbeat. The default is `None`, i.e. the extension is disabled.
+    pub fn acknowledgement_timeout(&self) -> Option<Duration> {
+        self.acknowledgement_timeout
+    }
 }
 
 impl Default for GossipsubConfig {
@@ -369,6 +495,22 @@ impl Default for GossipsubConfig {
     }
 }
 
+/// Parameters of a [`GossipsubConfig`] that can be changed on a running behaviour through
+/// [`crate::Gossipsub::reconfigure`]. Parameters that are `None` are left unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct GossipsubParams {
+    /// See [`GossipsubConfig::mesh_n`].
+    pub mesh_n: Option<usize>,
+    /// See [`GossipsubConfig::mesh_n_low`].
+    pub mesh_n_low: Option<usize>,
+    /// See [`GossipsubConfig::mesh_n_high`].
+    pub mesh_n_high: Option<usize>,
+    /// See [`GossipsubConfig::gossip_lazy`].
+    pub gossip_lazy: Option<usize>,
+    /// See [`GossipsubConfig::heartbeat_interval`].
+    pub heartbeat_interval: Option<Duration>,
+}
+
 /// The builder struct for constructing a gossipsub configuration.
 pub struct GossipsubConfigBuilder {
     config: GossipsubConfig,
@@ -390,8 +532,10 @@ impl Default for GossipsubConfigBuilder {
                 heartbeat_initial_delay: Duration::from_secs(5),
                 heartbeat_interval: Duration::from_secs(1),
                 fanout_ttl: Duration::from_secs(60),
+                max_fanout_topics: None,
                 check_explicit_peers_ticks: 300,
                 max_transmit_size: 65536,
+                publish_batch_delay: None,
                 idle_timeout: Duration::from_secs(120),
                 duplicate_cache_time: Duration::from_secs(60),
                 validate_messages: false,
@@ -425,9 +569,19 @@ impl Default for GossipsubConfigBuilder {
                 max_messages_per_rpc: None,
                 max_ihave_length: 5000,
                 max_ihave_messages: 10,
+                max_iwant_messages: 5000,
+                max_iwant_messages_non_mesh: 5000,
+                ignore_prune_from_non_mesh_peers: false,
+                topic_priorities: HashMap::new(),
                 iwant_followup_time: Duration::from_secs(3),
                 support_floodsub: false,
                 published_message_ids_cache_time: Duration::from_secs(10),
+                message_ttl: None,
+                publish_rate_limit: None,
+                max_mesh_topics_per_peer: None,
+                forwarding_enabled: true,
+                publish_grace_period: None,
+                acknowledgement_timeout: None,
             },
         }
     }
@@ -480,7 +634,8 @@ impl GossipsubConfigBuilder {
     /// Affects how peers are selected when pruning a mesh due to over subscription.
     ///
     /// At least [`Self::retain_scores`] of the retained peers will be high-scoring, while the remainder are
-    /// chosen randomly (D_score in the spec, default is 4).
+    /// chosen randomly (D_score in the spec, default is 4). Ties between equally scored peers
+    /// are broken by their [`PeerId`] when selecting the high-scoring peers.
     pub fn retain_scores(&mut self, retain_scores: usize) -> &mut Self {
         self.config.retain_scores = retain_scores;
         self
@@ -527,12 +682,29 @@ impl GossipsubConfigBuilder {
         self
     }
 
+    /// The maximum number of topics we keep fanout peers for. Publishing to a new topic once the
+    /// limit is reached evicts the topic we published to least recently. If this is unset, there
+    /// is no limit. The default is None.
+    pub fn max_fanout_topics(&mut self, max: Option<usize>) -> &mut Self {
+        self.config.max_fanout_topics = max;
+        self
+    }
+
     /// The maximum byte size for each gossip (default is 2048 bytes).
     pub fn max_transmit_size(&mut self, max_transmit_size: usize) -> &mut Self {
         self.config.max_transmit_size = max_transmit_size;
         self
     }
 
+    /// The time outbound RPCs are held back by a connection handler so that RPCs sent in quick
+    /// succession are coalesced into fewer frames. If `None`, RPCs are sent as soon as possible.
+    ///
+    /// The default is None.
+    pub fn publish_batch_delay(&mut self, delay: Option<Duration>) -> &mut Self {
+        self.config.publish_batch_delay = delay;
+        self
+    }
+
     /// The time a connection is maintained to a peer without being in the mesh and without
     /// send/receiving a message from. Connections that idle beyond this timeout are disconnected.
     /// Default is 120 seconds.
@@ -706,6 +878,39 @@ impl GossipsubConfigBuilder {
         self
     }
 
+    /// The maximum number of messages we will send to a single peer in response to its IWANT
+    /// requests within a heartbeat. Requests beyond this limit are ignored and the peer receives
+    /// a behavioural penalty. The default is 5000.
+    pub fn max_iwant_messages(&mut self, max_iwant_messages: usize) -> &mut Self {
+        self.config.max_iwant_messages = max_iwant_messages;
+        self
+    }
+
+    /// The maximum number of messages we will send within a heartbeat in response to IWANT
+    /// requests of a peer that is not in any of our meshes. The default is 5000.
+    pub fn max_iwant_messages_non_mesh(&mut self, max_iwant_messages_non_mesh: usize) -> &mut Self {
+        self.config.max_iwant_messages_non_mesh = max_iwant_messages_non_mesh;
+        self
+    }
+
+    /// Whether to ignore PRUNE messages for topics in whose mesh we don't have the sending peer.
+    /// The default is false.
+    pub fn ignore_prune_from_non_mesh_peers(
+        &mut self,
+        ignore_prune_from_non_mesh_peers: bool,
+    ) -> &mut Self {
+        self.config.ignore_prune_from_non_mesh_peers = ignore_prune_from_non_mesh_peers;
+        self
+    }
+
+    /// Sets the send priority of a topic. RPCs containing messages of a higher-priority topic
+    /// are sent before queued RPCs of lower priority, but never ahead of subscriptions or control
+    /// messages queued for the same peer. Topics without a configured priority have priority 0.
+    pub fn topic_priority(&mut self, topic: TopicHash, priority: u8) -> &mut Self {
+        self.config.topic_priorities.insert(topic, priority);
+        self
+    }
+
     /// By default, gossipsub will reject messages that are sent to us that has the same message
     /// source as we have specified locally. Enabling this, allows these messages and prevents
     /// penalizing the peer that sent us the message. Default is false.
@@ -737,8 +942,61 @@ impl GossipsubConfigBuilder {
         self
     }
 
+    /// The maximum time a received message is considered for propagation. Messages that were
+    /// received longer than this ago are neither forwarded nor gossiped. The default is `None`,
+    /// i.e. unlimited.
+    pub fn message_ttl(&mut self, message_ttl: Option<Duration>) -> &mut Self {
+        self.config.message_ttl = message_ttl;
+        self
+    }
+
+    /// Limits the number of messages the local node may publish on a single topic to
+    /// `max_messages` within any window of length `interval`. Publishing beyond the limit fails
+    /// with [`crate::error::PublishError::RateLimited`]. The default is `None`, i.e. unlimited.
+    pub fn publish_rate_limit(
+        &mut self,
+        publish_rate_limit: Option<(usize, Duration)>,
+    ) -> &mut Self {
+        self.config.publish_rate_limit = publish_rate_limit;
+        self
+    }
+
+    /// The maximum number of topics for which a single peer may be in our mesh. Excess GRAFTs
+    /// are answered with a PRUNE and penalized. If this is unset, there is no limit. The default
+    /// is None.
+    pub fn max_mesh_topics_per_peer(&mut self, max: Option<usize>) -> &mut Self {
+        self.config.max_mesh_topics_per_peer = max;
+        self
+    }
+
+    /// Whether to forward messages received from other peers. If disabled, received messages are
+    /// only delivered locally and never gossiped or served through IWANT, no peers are grafted and
+    /// GRAFTs from other peers are answered with a PRUNE. The default is true.
+    pub fn forwarding_enabled(&mut self, forwarding_enabled: bool) -> &mut Self {
+        self.config.forwarding_enabled = forwarding_enabled;
+        self
+    }
+
+    /// The time after subscribing to a topic during which published messages are held back until
+    /// the mesh of the topic has reached `mesh_n_low` peers. The default is `None`, i.e. messages
+    /// are never held back.
+    pub fn publish_grace_period(&mut self, publish_grace_period: Option<Duration>) -> &mut Self {
+        self.config.publish_grace_period = publish_grace_period;
+        self
+    }
+
+    /// Enables the acknowledgement extension, reporting the outcome of acknowledged publishes at
+    /// the latest after the given timeout. The default is `None`, i.e. the extension is disabled.
+    pub fn acknowledgement_timeout(
+        &mut self,
+        acknowledgement_timeout: Option<Duration>,
+    ) -> &mut Self {
+        self.config.acknowledgement_timeout = acknowledgement_timeout;
+        self
+    }
+
     /// Constructs a [`GossipsubConfig`] from the given configuration and validates the settings.
-    pub fn build(&self) -> Result<GossipsubConfig, &str> {
+    pub fn build(&self) -> Result<GossipsubConfig, &'static str> {
         // check all constraints on config
 
         if self.config.max_transmit_size < 100 {
@@ -760,6 +1018,10 @@ impl GossipsubConfigBuilder {
                 mesh_outbound_min < mesh_n_low <= mesh_n <= mesh_n_high");
         }
 
+        if self.config.max_fanout_topics == Some(0) {
+            return Err("The max_fanout_topics must be greater than 0 if set");
+        }
+
         if self.config.mesh_outbound_min * 2 > self.config.mesh_n {
             return Err(
                 "The following inequality doesn't hold mesh_outbound_min <= self.config.mesh_n / 2",
@@ -784,7 +1046,9 @@ impl std::fmt::Debug for GossipsubConfig {
         let _ = builder.field("heartbeat_initial_delay", &self.heartbeat_initial_delay);
         let _ = builder.field("heartbeat_interval", &self.heartbeat_interval);
         let _ = builder.field("fanout_ttl", &self.fanout_ttl);
+        let _ = builder.field("max_fanout_topics", &self.max_fanout_topics);
         let _ = builder.field("max_transmit_size", &self.max_transmit_size);
+        let _ = builder.field("publish_batch_delay", &self.publish_batch_delay);
         let _ = builder.field("idle_timeout", &self.idle_timeout);
         let _ = builder.field("duplicate_cache_time", &self.duplicate_cache_time);
         let _ = builder.field("validate_messages", &self.validate_messages);
@@ -802,12 +1066,28 @@ impl std::fmt::Debug for GossipsubConfig {
         let _ = builder.field("max_messages_per_rpc", &self.max_messages_per_rpc);
         let _ = builder.field("max_ihave_length", &self.max_ihave_length);
         let _ = builder.field("max_ihave_messages", &self.max_ihave_messages);
+        let _ = builder.field("max_iwant_messages", &self.max_iwant_messages);
+        let _ = builder.field(
+            "max_iwant_messages_non_mesh",
+            &self.max_iwant_messages_non_mesh,
+        );
+        let _ = builder.field(
+            "ignore_prune_from_non_mesh_peers",
+            &self.ignore_prune_from_non_mesh_peers,
+        );
+        let _ = builder.field("topic_priorities", &self.topic_priorities);
         let _ = builder.field("iwant_followup_time", &self.iwant_followup_time);
         let _ = builder.field("support_floodsub", &self.support_floodsub);
         let _ = builder.field(
             "published_message_ids_cache_time",
             &self.published_message_ids_cache_time,
         );
+        let _ = builder.field("message_ttl", &self.message_ttl);
+        let _ = builder.field("publish_rate_limit", &self.publish_rate_limit);
+        let _ = builder.field("max_mesh_topics_per_peer", &self.max_mesh_topics_per_peer);
+        let _ = builder.field("forwarding_enabled", &self.forwarding_enabled);
+        let _ = builder.field("publish_grace_period", &self.publish_grace_period);
+        let _ = builder.field("acknowledgement_timeout", &self.acknowledgement_timeout);
         builder.finish()
     }
 }
@@ -38,6 +38,12 @@ pub enum PublishError {
     MessageTooLarge,
     /// The compression algorithm failed.
     TransformFailed(std::io::Error),
+    /// The local publish rate limit for the topic has been exceeded. See
+    /// [`GossipsubConfig::publish_rate_limit`](crate::GossipsubConfig::publish_rate_limit).
+    RateLimited,
+    /// An acknowledged publish was requested without enabling the acknowledgement extension. See
+    /// [`GossipsubConfig::acknowledgement_timeout`](crate::GossipsubConfig::acknowledgement_timeout).
+    AcknowledgementsDisabled,
 }
 
 /// Error associated with subscribing to a topic.
@@ -31,6 +31,7 @@ use libp2p_swarm::protocols_handler::{
 };
 use libp2p_swarm::NegotiatedSubstream;
 use log::{error, trace, warn};
+use prost::Message as _;
 use smallvec::SmallVec;
 use std::{
     collections::VecDeque,
@@ -39,7 +40,7 @@ use std::{
     task::{Context, Poll},
     time::Duration,
 };
-use wasm_timer::Instant;
+use wasm_timer::{Delay, Instant};
 
 /// The initial time (in seconds) we set the keep alive for protocol negotiations to occur.
 const INITIAL_KEEP_ALIVE: u64 = 30;
@@ -94,6 +95,15 @@ pub struct GossipsubHandler {
     /// Queue of values that we want to send to the remote.
     send_queue: SmallVec<[crate::rpc_proto::Rpc; 16]>,
 
+    /// If set, queued RPCs are held back for this long and coalesced before being sent.
+    publish_batch_delay: Option<Duration>,
+
+    /// Timer of the current batch, started when an RPC is queued and no batch is pending.
+    batch_timer: Option<Delay>,
+
+    /// The maximum size of an RPC, which bounds the size of coalesced RPCs.
+    max_transmit_size: usize,
+
     /// Flag indicating that an outbound substream is being established to prevent duplicate
     /// requests.
     outbound_substream_establishing: bool,
@@ -168,6 +178,7 @@ impl GossipsubHandler {
         validation_mode: ValidationMode,
         idle_timeout: Duration,
         support_floodsub: bool,
+        publish_batch_delay: Option<Duration>,
     ) -> Self {
         GossipsubHandler {
             listen_protocol: SubstreamProtocol::new(
@@ -185,6 +196,9 @@ impl GossipsubHandler {
             outbound_substreams_created: 0,
             inbound_substreams_created: 0,
             send_queue: SmallVec::new(),
+            publish_batch_delay,
+            batch_timer: None,
+            max_transmit_size,
             peer_kind: None,
             peer_kind_sent: false,
             protocol_unsupported: false,
@@ -194,6 +208,62 @@ impl GossipsubHandler {
             in_mesh: false,
         }
     }
+
+    /// Returns whether the queued RPCs may be sent.
+    ///
+    /// If a batch is pending, this is only the case once its timer has fired, at which point the
+    /// queued RPCs are coalesced.
+    fn poll_batch(&mut self, cx: &mut Context<'_>) -> bool {
+        match self.batch_timer.as_mut() {
+            None => true,
+            Some(timer) => match timer.poll_unpin(cx) {
+                Poll::Ready(_) => {
+                    self.batch_timer = None;
+                    self.coalesce_send_queue();
+                    true
+                }
+                Poll::Pending => false,
+            },
+        }
+    }
+
+    /// Merges consecutive RPCs of the send queue as long as the merged RPC does not exceed the
+    /// maximum transmit size.
+    fn coalesce_send_queue(&mut self) {
+        let mut batched: SmallVec<[crate::rpc_proto::Rpc; 16]> = SmallVec::new();
+        // The sum of the encoded lengths of the RPCs merged into the last batched RPC, an upper
+        // bound of its length.
+        let mut last_len = 0;
+        for rpc in self.send_queue.drain(..) {
+            let len = rpc.encoded_len();
+            match batched.last_mut() {
+                Some(last) if last_len + len <= self.max_transmit_size => {
+                    merge_rpc(last, rpc);
+                    last_len += len;
+                }
+                _ => {
+                    batched.push(rpc);
+                    last_len = len;
+                }
+            }
+        }
+        self.send_queue = batched;
+    }
+}
+
+/// Appends the contents of `rpc` to `into`.
+fn merge_rpc(into: &mut crate::rpc_proto::Rpc, rpc: crate::rpc_proto::Rpc) {
+    into.subscriptions.extend(rpc.subscriptions);
+    into.publish.extend(rpc.publish);
+    if let Some(control) = rpc.control {
+        let into_control = into.control.get_or_insert_with(Default::default);
+        into_control.ihave.extend(control.ihave);
+        into_control.iwant.extend(control.iwant);
+        into_control.graft.extend(control.graft);
+        into_control.prune.extend(control.prune);
+        into_control.ack_request.extend(control.ack_request);
+        into_control.ack.extend(control.ack);
+    }
 }
 
 impl ProtocolsHandler for GossipsubHandler {
@@ -263,7 +333,14 @@ impl ProtocolsHandler for GossipsubHandler {
     fn inject_event(&mut self, message: GossipsubHandlerIn) {
         if !self.protocol_unsupported {
             match message {
-                GossipsubHandlerIn::Message(m) => self.send_queue.push(m),
+                GossipsubHandlerIn::Message(m) => {
+                    if let Some(delay) = self.publish_batch_delay {
+                        if self.batch_timer.is_none() {
+                            self.batch_timer = Some(Delay::new(delay));
+                        }
+                    }
+                    self.send_queue.push(m)
+                }
                 // If we have joined the mesh, keep the connection alive.
                 GossipsubHandlerIn::JoinedMesh => {
                     self.in_mesh = true;
@@ -361,8 +438,11 @@ impl ProtocolsHandler for GossipsubHandler {
             ));
         }
 
+        let batch_ready = self.poll_batch(cx);
+
         // determine if we need to create the stream
-        if !self.send_queue.is_empty()
+        if batch_ready
+            && !self.send_queue.is_empty()
             && self.outbound_substream.is_none()
             && !self.outbound_substream_establishing
         {
@@ -466,7 +546,7 @@ impl ProtocolsHandler for GossipsubHandler {
             ) {
                 // outbound idle state
                 Some(OutboundSubstreamState::WaitingOutput(substream)) => {
-                    if !self.send_queue.is_empty() {
+                    if batch_ready && !self.send_queue.is_empty() {
                         let message = self.send_queue.remove(0);
                         self.send_queue.shrink_to_fit();
                         self.outbound_substream =
@@ -569,3 +649,74 @@ impl ProtocolsHandler for GossipsubHandler {
         Poll::Pending
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc_proto;
+
+    fn publish_rpc(data: u8) -> rpc_proto::Rpc {
+        rpc_proto::Rpc {
+            subscriptions: Vec::new(),
+            publish: vec![rpc_proto::Message {
+                from: None,
+                data: Some(vec![data]),
+                seqno: None,
+                topic: String::from("topic"),
+                signature: None,
+                key: None,
+            }],
+            control: None,
+        }
+    }
+
+    #[test]
+    fn coalesces_rpcs_queued_within_batch_delay() {
+        let mut handler = GossipsubHandler::new(
+            std::borrow::Cow::Borrowed("meshsub"),
+            65536,
+            ValidationMode::Strict,
+            Duration::from_secs(120),
+            false,
+            Some(Duration::from_millis(50)),
+        );
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+
+        for i in 0..3 {
+            handler.inject_event(GossipsubHandlerIn::Message(publish_rpc(i)));
+        }
+
+        // Nothing is sent before the batch delay elapsed.
+        assert!(handler.poll(&mut cx).is_pending());
+
+        std::thread::sleep(Duration::from_millis(100));
+
+        match handler.poll(&mut cx) {
+            Poll::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol }) => {
+                assert_eq!(protocol.info().publish.len(), 3);
+            }
+            _ => panic!("Expected a single outbound substream request."),
+        }
+        assert!(handler.send_queue.is_empty());
+    }
+
+    #[test]
+    fn coalesced_rpcs_do_not_exceed_max_transmit_size() {
+        let mut handler = GossipsubHandler::new(
+            std::borrow::Cow::Borrowed("meshsub"),
+            2 * publish_rpc(0).encoded_len(),
+            ValidationMode::Strict,
+            Duration::from_secs(120),
+            false,
+            Some(Duration::from_millis(50)),
+        );
+
+        for i in 0..3 {
+            handler.inject_event(GossipsubHandlerIn::Message(publish_rpc(i)));
+        }
+        handler.coalesce_send_queue();
+
+        let sizes: Vec<_> = handler.send_queue.iter().map(|rpc| rpc.publish.len()).collect();
+        assert_eq!(sizes, vec![2, 1]);
+    }
+}
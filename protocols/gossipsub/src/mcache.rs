@@ -23,7 +23,12 @@ use crate::types::{MessageId, RawGossipsubMessage};
 use libp2p_core::PeerId;
 use log::debug;
 use std::fmt::Debug;
-use std::{collections::HashMap, fmt};
+use std::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+use wasm_timer::Instant;
 
 /// CacheEntry stored in the history.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -36,6 +41,10 @@ pub struct CacheEntry {
 #[derive(Clone)]
 pub struct MessageCache {
     msgs: HashMap<MessageId, RawGossipsubMessage>,
+    /// The time at which each message was first put into the cache.
+    received: HashMap<MessageId, Instant>,
+    /// The ids of the messages in the cache that were published by the local node.
+    published: HashSet<MessageId>,
     /// For every message and peer the number of times this peer asked for the message
     iwant_counts: HashMap<MessageId, HashMap<PeerId, u32>>,
     history: Vec<Vec<CacheEntry>>,
@@ -61,6 +70,8 @@ impl MessageCache {
         MessageCache {
             gossip,
             msgs: HashMap::default(),
+            received: HashMap::default(),
+            published: HashSet::default(),
             iwant_counts: HashMap::default(),
             history: vec![Vec::new(); history_capacity],
         }
@@ -84,16 +95,40 @@ impl MessageCache {
         if seen_message.is_none() {
             // Don't add duplicate entries to the cache.
             self.history[0].push(cache_entry);
+            self.received.insert(message_id.clone(), Instant::now());
         }
         seen_message
     }
 
+    /// Put a message published by the local node into the memory cache.
+    ///
+    /// Returns the message if it already exists.
+    pub fn put_published(
+        &mut self,
+        message_id: &MessageId,
+        msg: RawGossipsubMessage,
+    ) -> Option<RawGossipsubMessage> {
+        let seen_message = self.put(message_id, msg);
+        self.published.insert(message_id.clone());
+        seen_message
+    }
+
     /// Get a message with `message_id`
-    #[cfg(test)]
     pub fn get(&self, message_id: &MessageId) -> Option<&RawGossipsubMessage> {
         self.msgs.get(message_id)
     }
 
+    /// Returns `true` if the message with `message_id` is in the cache and was published by the
+    /// local node.
+    pub fn is_published(&self, message_id: &MessageId) -> bool {
+        self.published.contains(message_id)
+    }
+
+    /// Returns the ids of all messages in the cache.
+    pub fn message_ids(&self) -> impl Iterator<Item = &MessageId> {
+        self.msgs.keys()
+    }
+
     /// Increases the iwant count for the given message by one and returns the message together
     /// with the iwant if the message exists.
     pub fn get_with_iwant_counts(
@@ -127,9 +162,26 @@ impl MessageCache {
         })
     }
 
+    /// Returns `true` if the message with `message_id` was first seen more than `ttl` ago.
+    /// Messages that are not in the cache are never considered expired.
+    pub fn is_expired(&self, message_id: &MessageId, ttl: Duration) -> bool {
+        match self.received.get(message_id) {
+            Some(received) => received.elapsed() > ttl,
+            None => false,
+        }
+    }
+
     /// Get a list of [`MessageId`]s for a given topic.
     pub fn get_gossip_message_ids(&self, topic: &TopicHash) -> Vec<MessageId> {
-        self.history[..self.gossip]
+        self.message_ids_for_topic(topic, self.gossip)
+    }
+
+    /// Get a list of the validated [`MessageId`]s for a given topic that were put into the cache
+    /// within the last `windows` history windows, most recent first. `windows` is capped at the
+    /// history length.
+    pub fn message_ids_for_topic(&self, topic: &TopicHash, windows: usize) -> Vec<MessageId> {
+        let windows = std::cmp::min(windows, self.history.len());
+        self.history[..windows]
             .iter()
             .fold(vec![], |mut current_entries, entries| {
                 // search for entries with desired topic
@@ -173,6 +225,8 @@ impl MessageCache {
             }
             debug!("Remove message from the cache: {}", &entry.mid);
 
+            self.received.remove(&entry.mid);
+            self.published.remove(&entry.mid);
             self.iwant_counts.remove(&entry.mid);
         }
 
@@ -185,6 +239,8 @@ impl MessageCache {
         //We only remove the message from msgs and iwant_count and keep the message_id in the
         // history vector. Zhe id in the history vector will simply be ignored on popping.
 
+        self.received.remove(message_id);
+        self.published.remove(message_id);
         self.iwant_counts.remove(message_id);
         self.msgs.remove(message_id)
     }
@@ -363,4 +419,39 @@ mod tests {
         assert_eq!(mc.history[0].len(), 0);
         assert_eq!(mc.msgs.len(), 0);
     }
+
+    #[test]
+    /// Test listing the message ids of a topic within a number of history windows.
+    fn test_message_ids_for_topic() {
+        let mut mc = new_cache(1, 5);
+
+        let topic1_hash = Topic::new("topic1").hash().clone();
+        let topic2_hash = Topic::new("topic2").hash().clone();
+
+        let put_validated = |mc: &mut MessageCache, x: u64, topic: &TopicHash| {
+            let (id, m) = gen_testm(x, topic.clone());
+            mc.put(&id, m);
+            mc.validate(&id);
+            id
+        };
+
+        let old1 = put_validated(&mut mc, 1, &topic1_hash);
+        let old2 = put_validated(&mut mc, 2, &topic2_hash);
+        mc.shift();
+        let new1 = put_validated(&mut mc, 3, &topic1_hash);
+        let new2 = put_validated(&mut mc, 4, &topic2_hash);
+
+        // Unvalidated messages are not listed.
+        let (id, m) = gen_testm(5, topic1_hash.clone());
+        mc.put(&id, m);
+
+        assert_eq!(mc.message_ids_for_topic(&topic1_hash, 1), vec![new1.clone()]);
+        assert_eq!(mc.message_ids_for_topic(&topic2_hash, 1), vec![new2.clone()]);
+        assert_eq!(mc.message_ids_for_topic(&topic1_hash, 2), vec![new1, old1]);
+        assert_eq!(mc.message_ids_for_topic(&topic2_hash, 10), vec![new2.clone(), old2]);
+        assert_eq!(mc.get_gossip_message_ids(&topic2_hash), vec![new2]);
+        assert!(mc
+            .message_ids_for_topic(&Topic::new("topic3").hash(), 5)
+            .is_empty());
+    }
 }
@@ -569,7 +569,24 @@ impl PeerScore {
     }
 
     pub fn deliver_message(&mut self, from: &PeerId, msg_id: &MessageId, topic_hash: &TopicHash) {
-        self.mark_first_message_delivery(from, topic_hash);
+        // only credit the first delivery if the message got validated within the configured
+        // window after it was first seen
+        let first_seen = self.deliveries.get(msg_id).map(|record| record.first_seen);
+        let window = self
+            .params
+            .topics
+            .get(topic_hash)
+            .and_then(|params| params.first_message_deliveries_window);
+        match (first_seen, window) {
+            (Some(first_seen), Some(window)) if first_seen.elapsed() > window => {
+                debug!(
+                    "Message from {} was validated {}ms after it was first seen, not crediting the first delivery",
+                    from,
+                    first_seen.elapsed().as_millis()
+                );
+            }
+            _ => self.mark_first_message_delivery(from, topic_hash),
+        }
 
         let record = self
             .deliveries
@@ -720,6 +737,46 @@ impl PeerScore {
         }
     }
 
+    /// Resets the scoring statistics of a peer, bringing its score back to the baseline. Returns
+    /// true if the peer was known and false otherwise.
+    ///
+    /// The application specific score and the IP colocation factor are kept, as they are not
+    /// accumulated over time. A connected peer that is part of a mesh is treated as if it had just
+    /// been grafted, so that it doesn't immediately get penalized for missing mesh message
+    /// deliveries. A disconnected peer is forgotten altogether.
+    pub fn reset_peer(&mut self, peer_id: &PeerId) -> bool {
+        let peer_stats = match self.peer_stats.entry(*peer_id) {
+            hash_map::Entry::Occupied(entry) => entry,
+            hash_map::Entry::Vacant(_) => return false,
+        };
+
+        if let ConnectionStatus::Disconnected { .. } = peer_stats.get().status {
+            Self::remove_ips_for_peer(peer_stats.get(), &mut self.peer_ips, peer_id);
+            peer_stats.remove();
+            return true;
+        }
+
+        let peer_stats = peer_stats.into_mut();
+        debug!("Resetting score of peer {}", peer_id);
+        for topic_stats in peer_stats.topics.values_mut() {
+            let in_mesh = topic_stats.in_mesh();
+            *topic_stats = TopicStats::default();
+            if in_mesh {
+                topic_stats.mesh_status = MeshStatus::new_active();
+            }
+        }
+        peer_stats.behaviour_penalty = 0.0;
+        true
+    }
+
+    /// Returns the current score of all peers we keep statistics for.
+    pub fn scores(&self) -> HashMap<PeerId, f64> {
+        self.peer_stats
+            .keys()
+            .map(|peer_id| (*peer_id, self.score(peer_id)))
+            .collect()
+    }
+
     /// Sets scoring parameters for a topic.
     pub fn set_topic_params(&mut self, topic_hash: TopicHash, params: TopicScoreParams) {
         use hash_map::Entry::*;
@@ -243,9 +243,12 @@ pub struct TopicScoreParams {
     ///  The value of the parameter is a counter, decaying with `first_message_deliveries_decay`, and capped
     ///  by `first_message_deliveries_cap`.
     ///  The weight of the parameter MUST be positive (or zero to disable).
+    /// If `first_message_deliveries_window` is set, a delivery only counts if the message is
+    /// validated within the window after it was first seen.
     pub first_message_deliveries_weight: f64,
     pub first_message_deliveries_decay: f64,
     pub first_message_deliveries_cap: f64,
+    pub first_message_deliveries_window: Option<Duration>,
 
     ///  P3: mesh message deliveries
     ///  This is the number of message deliveries in the mesh, within the
@@ -298,6 +301,7 @@ impl Default for TopicScoreParams {
             first_message_deliveries_weight: 1.0,
             first_message_deliveries_decay: 0.5,
             first_message_deliveries_cap: 2000.0,
+            first_message_deliveries_window: None,
             // P3
             mesh_message_deliveries_weight: -1.0,
             mesh_message_deliveries_decay: 0.5,
@@ -310,6 +310,49 @@ fn test_score_first_message_deliveries_decay() {
     );
 }
 
+#[test]
+fn test_score_first_message_deliveries_window() {
+    // Create parameters with reasonable default values
+    let topic = Topic::new("test");
+    let topic_hash = topic.hash();
+    let mut params = PeerScoreParams::default();
+
+    let mut topic_params = TopicScoreParams::default();
+    topic_params.topic_weight = 1.0;
+    topic_params.first_message_deliveries_weight = 1.0;
+    topic_params.first_message_deliveries_decay = 1.0; // test without decay
+    topic_params.first_message_deliveries_cap = 2000.0;
+    topic_params.first_message_deliveries_window = Some(Duration::from_millis(50));
+    topic_params.time_in_mesh_weight = 0.0;
+    topic_params.mesh_message_deliveries_weight = 0.0;
+
+    params.topics.insert(topic_hash, topic_params.clone());
+    let peer_id_a = PeerId::random();
+    let peer_id_b = PeerId::random();
+    let mut peer_score = PeerScore::new(params);
+    for peer_id in &[peer_id_a, peer_id_b] {
+        peer_score.add_peer(*peer_id);
+        peer_score.graft(peer_id, topic.clone());
+    }
+
+    // the same message delivered by both peers only credits the first
+    let (id, msg) = make_test_message(1);
+    peer_score.validate_message(&peer_id_a, &id, &msg.topic);
+    peer_score.deliver_message(&peer_id_a, &id, &msg.topic);
+    peer_score.duplicated_message(&peer_id_b, &id, &msg.topic);
+
+    // a message validated after the window does not earn a credit
+    let (id, msg) = make_test_message(2);
+    peer_score.validate_message(&peer_id_b, &id, &msg.topic);
+    std::thread::sleep(Duration::from_millis(100));
+    peer_score.deliver_message(&peer_id_b, &id, &msg.topic);
+
+    peer_score.refresh_scores();
+    let expected = topic_params.topic_weight * topic_params.first_message_deliveries_weight;
+    assert_eq!(peer_score.score(&peer_id_a), expected);
+    assert_eq!(peer_score.score(&peer_id_b), 0.0);
+}
+
 #[test]
 fn test_score_mesh_message_deliveries() {
     // Create parameters with reasonable default values
@@ -520,10 +520,34 @@ impl Decoder for GossipsubCodec {
                 });
             }
 
+            let ack_request_msgs = rpc_control
+                .ack_request
+                .into_iter()
+                .map(|ack_request| GossipsubControlAction::AckRequest {
+                    message_ids: ack_request
+                        .message_ids
+                        .into_iter()
+                        .map(MessageId::from)
+                        .collect::<Vec<_>>(),
+                });
+
+            let ack_msgs = rpc_control
+                .ack
+                .into_iter()
+                .map(|ack| GossipsubControlAction::Ack {
+                    message_ids: ack
+                        .message_ids
+                        .into_iter()
+                        .map(MessageId::from)
+                        .collect::<Vec<_>>(),
+                });
+
             control_msgs.extend(ihave_msgs);
             control_msgs.extend(iwant_msgs);
             control_msgs.extend(graft_msgs);
             control_msgs.extend(prune_msgs);
+            control_msgs.extend(ack_request_msgs);
+            control_msgs.extend(ack_msgs);
         }
 
         Ok(Some(HandlerEvent::Message {
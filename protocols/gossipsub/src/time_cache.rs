@@ -206,6 +206,12 @@ where
     pub fn contains(&mut self, key: &Key) -> bool {
         self.0.contains_key(key)
     }
+
+    /// Empties the entire cache.
+    #[cfg(test)]
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
 }
 
 #[cfg(test)]
@@ -212,6 +212,18 @@ pub enum GossipsubControlAction {
         /// The backoff time in seconds before we allow to reconnect
         backoff: Option<u64>,
     },
+    /// The node requests the receiver to acknowledge the given messages - AckRequest control
+    /// message. Not part of the gossipsub spec.
+    AckRequest {
+        /// A list of message ids to acknowledge.
+        message_ids: Vec<MessageId>,
+    },
+    /// The node acknowledges having accepted the given messages - Ack control message. Not part
+    /// of the gossipsub spec.
+    Ack {
+        /// A list of accepted message ids.
+        message_ids: Vec<MessageId>,
+    },
 }
 
 /// An RPC received/sent.
@@ -268,6 +280,8 @@ impl Into<rpc_proto::Rpc> for GossipsubRpc {
             iwant: Vec::new(),
             graft: Vec::new(),
             prune: Vec::new(),
+            ack_request: Vec::new(),
+            ack: Vec::new(),
         };
 
         let empty_control_msg = self.control_msgs.is_empty();
@@ -316,6 +330,18 @@ impl Into<rpc_proto::Rpc> for GossipsubRpc {
                     };
                     control.prune.push(rpc_prune);
                 }
+                GossipsubControlAction::AckRequest { message_ids } => {
+                    let rpc_ack_request = rpc_proto::ControlAckRequest {
+                        message_ids: message_ids.into_iter().map(|msg_id| msg_id.0).collect(),
+                    };
+                    control.ack_request.push(rpc_ack_request);
+                }
+                GossipsubControlAction::Ack { message_ids } => {
+                    let rpc_ack = rpc_proto::ControlAck {
+                        message_ids: message_ids.into_iter().map(|msg_id| msg_id.0).collect(),
+                    };
+                    control.ack.push(rpc_ack);
+                }
             }
         }
 
@@ -62,7 +62,7 @@ use futures::stream::StreamExt;
 use libp2p::dns::DnsConfig;
 use libp2p::ping::{Ping, PingConfig, PingEvent};
 use libp2p::plaintext;
-use libp2p::relay::{Relay, RelayConfig};
+use libp2p::relay::{Relay, RelayConfig, RelayEvent};
 use libp2p::swarm::SwarmEvent;
 use libp2p::tcp::TcpConfig;
 use libp2p::Transport;
@@ -218,7 +218,7 @@ struct Behaviour {
 
 #[derive(Debug)]
 enum Event {
-    Relay(()),
+    Relay(RelayEvent),
     Ping(PingEvent),
 }
 
@@ -228,9 +228,9 @@ impl From<PingEvent> for Event {
     }
 }
 
-impl From<()> for Event {
-    fn from(_: ()) -> Self {
-        Event::Relay(())
+impl From<RelayEvent> for Event {
+    fn from(e: RelayEvent) -> Self {
+        Event::Relay(e)
     }
 }
 
@@ -21,12 +21,14 @@
 use crate::handler::{RelayHandlerConfig, RelayHandlerEvent, RelayHandlerIn, RelayHandlerProto};
 use crate::message_proto::circuit_relay;
 use crate::protocol;
+use crate::protocol::copy_future::{CircuitLimits, CircuitStats};
 use crate::transport::TransportToBehaviourMsg;
 use crate::RequestId;
 use futures::channel::{mpsc, oneshot};
 use futures::prelude::*;
+use futures_timer::Delay;
 use libp2p_core::connection::{ConnectedPoint, ConnectionId, ListenerId};
-use libp2p_core::multiaddr::Multiaddr;
+use libp2p_core::multiaddr::{Multiaddr, Protocol};
 use libp2p_core::PeerId;
 use libp2p_swarm::{
     DialPeerCondition, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler, PollParameters,
@@ -34,6 +36,7 @@ use libp2p_swarm::{
 use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
 use std::task::{Context, Poll};
 use std::time::Duration;
+use wasm_timer::Instant;
 
 /// Network behaviour allowing the local node to act as a source, a relay and a destination.
 pub struct Relay {
@@ -45,7 +48,7 @@ pub struct Relay {
     /// [`Self::listeners`] or [`Self::listener_any_relay`].
     outbox_to_listeners: VecDeque<(PeerId, BehaviourToListenerMsg)>,
     /// Events that need to be yielded to the outside when polling.
-    outbox_to_swarm: VecDeque<NetworkBehaviourAction<RelayHandlerIn, ()>>,
+    outbox_to_swarm: VecDeque<NetworkBehaviourAction<RelayHandlerIn, RelayEvent>>,
 
     /// List of peers the network is connected to.
     connected_peers: HashMap<PeerId, HashSet<ConnectionId>>,
@@ -67,6 +70,27 @@ pub struct Relay {
     /// Channel sender to listener listening for incoming relayed connections from relay nodes via
     /// which the local node is not explicitly listening.
     listener_any_relay: Option<mpsc::Sender<BehaviourToListenerMsg>>,
+
+    /// Counters of the circuits relayed by the local node, shared with all [`RelayHandler`]s.
+    ///
+    /// [`RelayHandler`]: crate::handler::RelayHandler
+    circuit_stats: CircuitStats,
+
+    /// Sources of the circuits currently relayed by the local node, indexed by the connection to
+    /// their destination, so that they are reported as closed together with the connection.
+    open_circuits: HashMap<ConnectionId, Vec<PeerId>>,
+
+    /// Reservations of the local node on relay nodes via which it explicitly listens for incoming
+    /// relayed connections, indexed by relay [`PeerId`].
+    reservations: HashMap<PeerId, Reservation>,
+}
+
+struct Reservation {
+    /// The address to listen on again if the connection to the relay is lost, for reservations
+    /// made via [`Relay::reserve`].
+    listen_addr: Option<Multiaddr>,
+    /// Fires once the reservation is due to be renewed. Only set while the reservation is accepted.
+    renewal: Option<Delay>,
 }
 
 #[derive(Default)]
@@ -115,6 +139,34 @@ pub struct RelayConfig {
     /// destination node should establish a connection to a relay node before
     /// advertising their relayed address via that relay node to a source node.
     pub actively_connect_to_dst_nodes: bool,
+    /// Maximum number of circuits the local node relays at the same time.
+    /// Unlimited if `None`.
+    ///
+    /// A circuit counts towards the limit from accepting the request of the
+    /// source node until the circuit is closed. Requests exceeding the limit
+    /// are denied. As the circuit relay v1 specification defines no dedicated
+    /// status code for exhausted resources, they are denied with
+    /// `HOP_CANT_SPEAK_RELAY`.
+    pub max_circuits: Option<usize>,
+    /// Maximum number of circuits the local node relays at the same time on
+    /// behalf of a single source node. Unlimited if `None`. See
+    /// [`RelayConfig::max_circuits`].
+    pub max_circuits_per_peer: Option<usize>,
+    /// Maximum duration of a circuit relayed by the local node, after which
+    /// the circuit is closed. Unlimited if `None`.
+    pub circuit_duration: Option<Duration>,
+    /// Maximum number of bytes relayed, in both directions, over a circuit,
+    /// after which the circuit is closed. Unlimited if `None`.
+    pub circuit_max_bytes: Option<u64>,
+    /// Duration for which a reservation of the local node on a relay node is valid, reported
+    /// through [`RelayEvent::ReservationAccepted`].
+    ///
+    /// Circuit relay v1 has no notion of reservations on the relay itself. Instead, the connection
+    /// to the relay is kept alive for as long as the local node listens via it. Reservations are
+    /// renewed once three quarters of the duration have elapsed: if the local node is still
+    /// connected to the relay, the reservation is extended and reported again. Otherwise, if the
+    /// reservation was made via [`Relay::reserve`], the local node listens via the relay anew.
+    pub reservation_duration: Duration,
 }
 
 impl Default for RelayConfig {
@@ -122,6 +174,11 @@ impl Default for RelayConfig {
         RelayConfig {
             connection_idle_timeout: Duration::from_secs(10),
             actively_connect_to_dst_nodes: false,
+            max_circuits: None,
+            max_circuits_per_peer: None,
+            circuit_duration: None,
+            circuit_max_bytes: None,
+            reservation_duration: Duration::from_secs(60 * 60),
         }
     }
 }
@@ -146,18 +203,152 @@ impl Relay {
             outgoing_relay_reqs: Default::default(),
             listeners: Default::default(),
             listener_any_relay: Default::default(),
+            circuit_stats: Default::default(),
+            open_circuits: Default::default(),
+            reservations: Default::default(),
+        }
+    }
+
+    /// Returns the number of circuits the local node is currently relaying between a source and
+    /// a destination.
+    pub fn active_circuits(&self) -> usize {
+        self.circuit_stats.active_circuits()
+    }
+
+    /// Returns the total number of bytes the local node has relayed, in both directions, across
+    /// all circuits since the [`Relay`] was created.
+    pub fn relayed_bytes(&self) -> u64 {
+        self.circuit_stats.relayed_bytes()
+    }
+
+    /// Starts listening for incoming relayed connections via the relay `relay_peer_id`, reachable
+    /// at `relay_addr`.
+    ///
+    /// Asks the `Swarm` to listen on `<relay_addr>/p2p/<relay_peer_id>/p2p-circuit`, equivalent to
+    /// calling `Swarm::listen_on` with that address. [`RelayEvent::ReservationAccepted`] is emitted
+    /// once the connection to the relay is established.
+    ///
+    /// The reservation is renewed before it expires, see [`RelayConfig::reservation_duration`].
+    /// Unlike for listeners created via `Swarm::listen_on`, the local node listens via the relay
+    /// anew on renewal if the connection to the relay has been lost in the meantime. Closing the
+    /// listener gives up the reservation.
+    pub fn reserve(&mut self, relay_peer_id: PeerId, relay_addr: Multiaddr) {
+        let listen_addr = relay_addr
+            .with(Protocol::P2p(relay_peer_id.into()))
+            .with(Protocol::P2pCircuit);
+        self.reservations.insert(
+            relay_peer_id,
+            Reservation {
+                listen_addr: Some(listen_addr.clone()),
+                renewal: None,
+            },
+        );
+        self.outbox_to_swarm
+            .push_back(NetworkBehaviourAction::ListenOn(listen_addr));
+    }
+
+    /// Reports the reservation on the relay `relay` as accepted, scheduling its renewal.
+    fn accept_reservation(&mut self, relay: PeerId) {
+        let duration = self.config.reservation_duration;
+        let reservation = self.reservations.entry(relay).or_insert(Reservation {
+            listen_addr: None,
+            renewal: None,
+        });
+        reservation.renewal = Some(Delay::new(duration * 3 / 4));
+        self.outbox_to_swarm
+            .push_back(NetworkBehaviourAction::GenerateEvent(
+                RelayEvent::ReservationAccepted {
+                    relay,
+                    expires_at: Instant::now() + duration,
+                },
+            ));
+    }
+
+    /// Renews the reservation on the relay `relay`, which is due.
+    fn renew_reservation(&mut self, relay: PeerId) {
+        match self.listeners.get(&relay) {
+            // The listener has been closed, giving up the reservation.
+            Some(listener) if listener.is_closed() => {
+                self.listeners.remove(&relay);
+                self.reservations.remove(&relay);
+            }
+            Some(RelayListener::Connected { .. }) => self.accept_reservation(relay),
+            // The reservation is accepted again once connected to the relay.
+            Some(RelayListener::Connecting { .. }) => {
+                if let Some(reservation) = self.reservations.get_mut(&relay) {
+                    reservation.renewal = None;
+                }
+            }
+            // The connection to the relay has been lost.
+            None => match self.reservations.get_mut(&relay) {
+                Some(Reservation {
+                    listen_addr: Some(listen_addr),
+                    renewal,
+                }) => {
+                    *renewal = None;
+                    self.outbox_to_swarm
+                        .push_back(NetworkBehaviourAction::ListenOn(listen_addr.clone()));
+                }
+                _ => {
+                    self.reservations.remove(&relay);
+                }
+            },
         }
     }
 }
 
+/// Event emitted by the [`Relay`] behaviour.
+#[derive(Debug)]
+pub enum RelayEvent {
+    /// The local node, acting as a relay, started relaying a circuit from `src` to `dst`.
+    CircuitOpened { src: PeerId, dst: PeerId },
+    /// A circuit relayed by the local node from `src` to `dst` has been closed.
+    CircuitClosed {
+        src: PeerId,
+        dst: PeerId,
+        reason: CircuitCloseReason,
+    },
+    /// The local node requested to listen for incoming relayed connections via the relay `peer`.
+    ///
+    /// Circuit relay v1 has no notion of reservations on the relay itself. The request is
+    /// reported as soon as the local node starts listening via the relay, e.g. via
+    /// [`Relay::reserve`] or `Swarm::listen_on` with a `/p2p-circuit` address.
+    ReservationRequested { peer: PeerId },
+    /// The local node is connected to the relay `relay` and listens for incoming relayed
+    /// connections via it.
+    ///
+    /// Emitted again whenever the reservation is renewed, before `expires_at`. See
+    /// [`RelayConfig::reservation_duration`].
+    ReservationAccepted { relay: PeerId, expires_at: Instant },
+}
+
+/// The reason for a relayed circuit being closed.
+#[derive(Debug)]
+pub enum CircuitCloseReason {
+    /// Both the source and the destination finished sending data.
+    Done,
+    /// Relaying data failed, e.g. because the circuit was inactive for too long.
+    Io(std::io::Error),
+    /// The circuit exceeded [`RelayConfig::circuit_duration`] or
+    /// [`RelayConfig::circuit_max_bytes`].
+    LimitExceeded,
+    /// The connection to the destination was closed while relaying the circuit.
+    ConnectionClosed,
+}
+
 impl NetworkBehaviour for Relay {
     type ProtocolsHandler = RelayHandlerProto;
-    type OutEvent = ();
+    type OutEvent = RelayEvent;
 
     fn new_handler(&mut self) -> Self::ProtocolsHandler {
         RelayHandlerProto {
             config: RelayHandlerConfig {
                 connection_idle_timeout: self.config.connection_idle_timeout,
+                circuit_limits: CircuitLimits {
+                    max_duration: self.config.circuit_duration,
+                    max_bytes: self.config.circuit_max_bytes,
+                },
+                circuit_stats: self.circuit_stats.clone(),
             },
         }
     }
@@ -226,6 +417,7 @@ impl NetworkBehaviour for Relay {
             to_listener
                 .start_send(BehaviourToListenerMsg::ConnectionToRelayEstablished)
                 .expect("Channel to have at least capacity of 1.");
+            self.accept_reservation(*peer);
             self.listeners.insert(
                 *peer,
                 RelayListener::Connected {
@@ -307,6 +499,7 @@ impl NetworkBehaviour for Relay {
                 // By removing the entry, the channel to the listener is dropped and thus the
                 // listener is notified that dialing the relay failed.
                 o.remove_entry();
+                self.reservations.remove(peer_id);
             }
         }
 
@@ -353,6 +546,19 @@ impl NetworkBehaviour for Relay {
             "`inject_connection_closed` called for known connection"
         );
 
+        // Circuits relayed over the connection are closed with it, without the handler
+        // reporting them.
+        for src in self.open_circuits.remove(connection).into_iter().flatten() {
+            self.outbox_to_swarm
+                .push_back(NetworkBehaviourAction::GenerateEvent(
+                    RelayEvent::CircuitClosed {
+                        src,
+                        dst: *peer,
+                        reason: CircuitCloseReason::ConnectionClosed,
+                    },
+                ));
+        }
+
         match self.listeners.get(peer) {
             None => {}
             Some(RelayListener::Connecting { .. }) => unreachable!(
@@ -443,8 +649,31 @@ impl NetworkBehaviour for Relay {
             RelayHandlerEvent::IncomingRelayReq {
                 request_id,
                 src_addr,
-                req,
+                mut req,
             } => {
+                match self.circuit_stats.try_reserve(
+                    event_source,
+                    self.config.max_circuits,
+                    self.config.max_circuits_per_peer,
+                ) {
+                    Some(reservation) => req.set_reservation(reservation),
+                    None => {
+                        log::debug!(
+                            "Denying relay request of {}: circuit limit reached.",
+                            event_source
+                        );
+                        self.outbox_to_swarm
+                            .push_back(NetworkBehaviourAction::NotifyHandler {
+                                peer_id: event_source,
+                                handler: NotifyHandler::One(connection),
+                                event: RelayHandlerIn::DenyIncomingRelayReq(
+                                    req.deny(circuit_relay::Status::HopCantSpeakRelay),
+                                ),
+                            });
+                        return;
+                    }
+                }
+
                 if self.connected_peers.get(&req.dst_peer().peer_id).is_some() {
                     let dest_id = req.dst_peer().peer_id;
                     let event = RelayHandlerIn::OutgoingDstReq {
@@ -555,6 +784,47 @@ impl NetworkBehaviour for Relay {
                         event: RelayHandlerIn::DenyIncomingRelayReq(incoming_relay_req_deny_fut),
                     });
             }
+            RelayHandlerEvent::CircuitOpened { src_peer_id } => {
+                self.open_circuits
+                    .entry(connection)
+                    .or_default()
+                    .push(src_peer_id);
+                self.outbox_to_swarm
+                    .push_back(NetworkBehaviourAction::GenerateEvent(
+                        RelayEvent::CircuitOpened {
+                            src: src_peer_id,
+                            dst: event_source,
+                        },
+                    ));
+            }
+            RelayHandlerEvent::CircuitClosed {
+                src_peer_id,
+                result,
+            } => {
+                if let Entry::Occupied(mut srcs) = self.open_circuits.entry(connection) {
+                    if let Some(i) = srcs.get().iter().position(|src| *src == src_peer_id) {
+                        srcs.get_mut().swap_remove(i);
+                    }
+                    if srcs.get().is_empty() {
+                        srcs.remove();
+                    }
+                }
+                let reason = match result {
+                    Ok(()) => CircuitCloseReason::Done,
+                    Err(protocol::IncomingRelayReqError::Io(e)) => CircuitCloseReason::Io(e),
+                    Err(protocol::IncomingRelayReqError::LimitExceeded) => {
+                        CircuitCloseReason::LimitExceeded
+                    }
+                };
+                self.outbox_to_swarm
+                    .push_back(NetworkBehaviourAction::GenerateEvent(
+                        RelayEvent::CircuitClosed {
+                            src: src_peer_id,
+                            dst: event_source,
+                            reason,
+                        },
+                    ));
+            }
         }
     }
 
@@ -594,11 +864,13 @@ impl NetworkBehaviour for Relay {
                                 .1,
                         ) {
                             self.listeners.remove(&relay_peer_id);
+                            self.reservations.remove(&relay_peer_id);
                         }
                     }
                     Poll::Ready(Err(mpsc::SendError { .. })) => {
                         self.outbox_to_listeners.pop_front();
                         self.listeners.remove(&relay_peer_id);
+                        self.reservations.remove(&relay_peer_id);
                     }
                     Poll::Pending => {}
                 },
@@ -700,6 +972,12 @@ impl NetworkBehaviour for Relay {
                         // Listener is listening for incoming relayed
                         // connections from this relay only.
                         Some((relay_peer_id, relay_addr)) => {
+                            self.outbox_to_swarm
+                                .push_back(NetworkBehaviourAction::GenerateEvent(
+                                    RelayEvent::ReservationRequested {
+                                        peer: relay_peer_id,
+                                    },
+                                ));
                             if let Some(connections) = self.connected_peers.get(&relay_peer_id) {
                                 to_listener
                                     .start_send(
@@ -707,11 +985,12 @@ impl NetworkBehaviour for Relay {
                                     )
                                     .expect("Channel to have at least capacity of 1.");
                                 let primary_connection =
-                                    connections.iter().next().expect("At least one connection.");
+                                    *connections.iter().next().expect("At least one connection.");
+                                self.accept_reservation(relay_peer_id);
                                 self.listeners.insert(
                                     relay_peer_id,
                                     RelayListener::Connected {
-                                        connection_id: *primary_connection,
+                                        connection_id: primary_connection,
                                         to_listener,
                                     },
                                 );
@@ -719,7 +998,7 @@ impl NetworkBehaviour for Relay {
                                 self.outbox_to_swarm.push_back(
                                     NetworkBehaviourAction::NotifyHandler {
                                         peer_id: relay_peer_id,
-                                        handler: NotifyHandler::One(*primary_connection),
+                                        handler: NotifyHandler::One(primary_connection),
                                         event: RelayHandlerIn::UsedForListening(true),
                                     },
                                 );
@@ -747,6 +1026,18 @@ impl NetworkBehaviour for Relay {
             }
         }
 
+        let due_reservations = self
+            .reservations
+            .iter_mut()
+            .filter_map(|(relay, reservation)| {
+                let renewal = reservation.renewal.as_mut()?;
+                renewal.poll_unpin(cx).is_ready().then(|| *relay)
+            })
+            .collect::<Vec<_>>();
+        for relay in due_reservations {
+            self.renew_reservation(relay);
+        }
+
         if let Some(event) = self.outbox_to_swarm.pop_front() {
             return Poll::Ready(event);
         }
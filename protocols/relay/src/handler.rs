@@ -20,6 +20,7 @@
 
 use crate::message_proto::circuit_relay;
 use crate::protocol;
+use crate::protocol::copy_future::{CircuitLimits, CircuitStats};
 use crate::RequestId;
 use futures::channel::oneshot::{self, Canceled};
 use futures::future::BoxFuture;
@@ -39,6 +40,8 @@ use wasm_timer::Instant;
 
 pub struct RelayHandlerConfig {
     pub connection_idle_timeout: Duration,
+    pub circuit_limits: CircuitLimits,
+    pub circuit_stats: CircuitStats,
 }
 
 pub struct RelayHandlerProto {
@@ -98,8 +101,10 @@ pub struct RelayHandler {
             >,
         >,
     >,
-    /// Futures that copy from a source to a destination.
-    copy_futures: FuturesUnordered<BoxFuture<'static, Result<(), protocol::IncomingRelayReqError>>>,
+    /// Futures that copy from a source to a destination, indexed by the source [`PeerId`].
+    copy_futures: FuturesUnordered<
+        BoxFuture<'static, (PeerId, Result<(), protocol::IncomingRelayReqError>)>,
+    >,
     /// Requests asking the remote to become a relay.
     outgoing_relay_reqs: Vec<OutgoingRelayReq>,
     /// Requests asking the remote to become a destination.
@@ -193,6 +198,17 @@ pub enum RelayHandlerEvent {
         src_connection_id: ConnectionId,
         incoming_relay_req_deny_fut: BoxFuture<'static, Result<(), std::io::Error>>,
     },
+
+    /// The local node started relaying a circuit from the given source to the node we handle,
+    /// acting as the destination.
+    CircuitOpened { src_peer_id: PeerId },
+
+    /// A circuit relayed by the local node from the given source to the node we handle has been
+    /// closed.
+    CircuitClosed {
+        src_peer_id: PeerId,
+        result: Result<(), protocol::IncomingRelayReqError>,
+    },
 }
 
 /// Event that can be sent to the relay handler.
@@ -329,16 +345,29 @@ impl ProtocolsHandler for RelayHandler {
             }
             // We have successfully asked the node to be a destination.
             EitherOutput::Second((to_dest_substream, from_dst_read_buffer)) => {
-                let incoming_relay_req = match open_info {
+                let (src_peer_id, incoming_relay_req) = match open_info {
                     RelayOutboundOpenInfo::Destination {
-                        incoming_relay_req, ..
-                    } => incoming_relay_req,
+                        src_peer_id,
+                        incoming_relay_req,
+                        ..
+                    } => (src_peer_id, incoming_relay_req),
                     RelayOutboundOpenInfo::Relay { .. } => unreachable!(
                         "Can not successfully dial a destination when actually dialing a relay."
                     ),
                 };
-                self.copy_futures
-                    .push(incoming_relay_req.fulfill(to_dest_substream, from_dst_read_buffer));
+                self.copy_futures.push(
+                    incoming_relay_req
+                        .fulfill(
+                            to_dest_substream,
+                            from_dst_read_buffer,
+                            self.config.circuit_limits,
+                            self.config.circuit_stats.clone(),
+                        )
+                        .map(move |result| (src_peer_id, result))
+                        .boxed(),
+                );
+                self.queued_events
+                    .push(RelayHandlerEvent::CircuitOpened { src_peer_id });
             }
         }
     }
@@ -691,10 +720,20 @@ impl ProtocolsHandler for RelayHandler {
             Poll::Pending => {}
         }
 
-        while let Poll::Ready(Some(result)) = self.copy_futures.poll_next_unpin(cx) {
-            if let Err(e) = result {
-                warn!("Incoming relay request failed: {:?}", e);
+        while let Poll::Ready(Some((src_peer_id, result))) = self.copy_futures.poll_next_unpin(cx)
+        {
+            match &result {
+                Ok(()) => {}
+                Err(protocol::IncomingRelayReqError::LimitExceeded) => {
+                    log::debug!("Closing circuit from {}: limit exceeded.", src_peer_id);
+                }
+                Err(e) => warn!("Incoming relay request failed: {:?}", e),
             }
+            self.queued_events
+                .push(RelayHandlerEvent::CircuitClosed {
+                    src_peer_id,
+                    result,
+                });
         }
 
         while let Poll::Ready(Some(result)) = self.deny_futures.poll_next_unpin(cx) {
@@ -95,7 +95,7 @@ mod handler;
 mod protocol;
 mod transport;
 
-pub use behaviour::{Relay, RelayConfig};
+pub use behaviour::{CircuitCloseReason, Relay, RelayConfig, RelayEvent};
 pub use transport::{RelayError, RelayTransport};
 
 use libp2p_core::Transport;
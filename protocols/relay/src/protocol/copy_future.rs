@@ -29,36 +29,174 @@ use futures::io::{AsyncBufRead, BufReader};
 use futures::io::{AsyncRead, AsyncWrite};
 use futures::ready;
 use futures_timer::Delay;
+use libp2p_core::PeerId;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::io;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::Duration;
 
+/// Counters shared between a [`Relay`](crate::Relay) and the [`CopyFuture`]s relaying data on its
+/// behalf.
+#[derive(Clone, Debug, Default)]
+pub struct CircuitStats {
+    active_circuits: Arc<AtomicUsize>,
+    relayed_bytes: Arc<AtomicU64>,
+    reserved: Arc<Mutex<ReservedCircuits>>,
+}
+
+/// Circuits accepted by a [`Relay`](crate::Relay), counted from accepting the relay request of a
+/// source until the circuit is closed.
+#[derive(Debug, Default)]
+struct ReservedCircuits {
+    total: usize,
+    per_src: HashMap<PeerId, usize>,
+}
+
+impl CircuitStats {
+    /// Number of circuits currently being relayed.
+    pub fn active_circuits(&self) -> usize {
+        self.active_circuits.load(Ordering::Relaxed)
+    }
+
+    /// Total number of bytes relayed, in both directions, across all circuits.
+    pub fn relayed_bytes(&self) -> u64 {
+        self.relayed_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Accounts for `n` bytes relayed outside of a [`CopyFuture`].
+    pub(crate) fn add_relayed_bytes(&self, n: u64) {
+        self.relayed_bytes.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Reserves a circuit for a relay request of `src_peer_id`, unless `max_circuits` circuits in
+    /// total or `max_circuits_per_peer` circuits of `src_peer_id` are already reserved. A limit of
+    /// `None` is unlimited.
+    ///
+    /// The circuit counts towards the limits until the returned [`CircuitReservation`] is
+    /// dropped.
+    pub fn try_reserve(
+        &self,
+        src_peer_id: PeerId,
+        max_circuits: Option<usize>,
+        max_circuits_per_peer: Option<usize>,
+    ) -> Option<CircuitReservation> {
+        let mut reserved = self.reserved.lock().unwrap();
+        let ReservedCircuits { total, per_src } = &mut *reserved;
+        let of_src = per_src.get(&src_peer_id).copied().unwrap_or(0);
+        if matches!(max_circuits, Some(max) if *total >= max)
+            || matches!(max_circuits_per_peer, Some(max) if of_src >= max)
+        {
+            return None;
+        }
+        *total += 1;
+        *per_src.entry(src_peer_id).or_default() += 1;
+        Some(CircuitReservation {
+            src_peer_id,
+            reserved: self.reserved.clone(),
+        })
+    }
+}
+
+/// A circuit reserved via [`CircuitStats::try_reserve`], released on drop.
+#[derive(Debug)]
+pub struct CircuitReservation {
+    src_peer_id: PeerId,
+    reserved: Arc<Mutex<ReservedCircuits>>,
+}
+
+impl Drop for CircuitReservation {
+    fn drop(&mut self) {
+        if let Ok(mut reserved) = self.reserved.lock() {
+            reserved.total -= 1;
+            if let Some(of_src) = reserved.per_src.get_mut(&self.src_peer_id) {
+                *of_src -= 1;
+                if *of_src == 0 {
+                    reserved.per_src.remove(&self.src_peer_id);
+                }
+            }
+        }
+    }
+}
+
+/// Limits of a single circuit relayed by a [`CopyFuture`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CircuitLimits {
+    /// Maximum duration of the circuit.
+    pub max_duration: Option<Duration>,
+    /// Maximum number of bytes relayed, in both directions, over the circuit.
+    pub max_bytes: Option<u64>,
+}
+
+/// Error produced by a [`CopyFuture`].
+#[derive(Debug)]
+pub enum CopyError {
+    /// Reading from or writing to either side of the circuit failed, or the
+    /// circuit was inactive for too long.
+    Io(io::Error),
+    /// The circuit exceeded one of its [`CircuitLimits`].
+    LimitExceeded,
+}
+
+impl From<io::Error> for CopyError {
+    fn from(e: io::Error) -> Self {
+        CopyError::Io(e)
+    }
+}
+
 pub struct CopyFuture<S, D> {
     src: BufReader<S>,
     dst: BufReader<D>,
 
     active_timeout: Delay,
     configured_timeout: Duration,
+
+    /// Fires once the circuit exceeded [`CircuitLimits::max_duration`].
+    max_duration: Option<Delay>,
+    max_bytes: Option<u64>,
+    /// Number of bytes relayed over this circuit, in both directions.
+    relayed_bytes: u64,
+
+    stats: CircuitStats,
 }
 
 impl<S: AsyncRead, D: AsyncRead> CopyFuture<S, D> {
-    pub fn new(src: S, dst: D, timeout: Duration) -> Self {
+    pub fn new(
+        src: S,
+        dst: D,
+        timeout: Duration,
+        limits: CircuitLimits,
+        stats: CircuitStats,
+    ) -> Self {
+        stats.active_circuits.fetch_add(1, Ordering::Relaxed);
         CopyFuture {
             src: BufReader::new(src),
             dst: BufReader::new(dst),
             active_timeout: Delay::new(timeout),
             configured_timeout: timeout,
+            max_duration: limits.max_duration.map(Delay::new),
+            max_bytes: limits.max_bytes,
+            relayed_bytes: 0,
+            stats,
         }
     }
 }
 
+impl<S, D> Drop for CopyFuture<S, D> {
+    fn drop(&mut self) {
+        self.stats.active_circuits.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 impl<S, D> Future for CopyFuture<S, D>
 where
     S: AsyncRead + AsyncWrite + Unpin,
     D: AsyncRead + AsyncWrite + Unpin,
 {
-    type Output = io::Result<()>;
+    type Output = Result<(), CopyError>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = &mut *self;
@@ -72,14 +210,20 @@ where
                 Progressed,
             }
 
-            let src_status = match forward_data(&mut this.src, &mut this.dst, cx) {
+            let mut budget = ByteBudget {
+                total: &this.stats.relayed_bytes,
+                circuit: &mut this.relayed_bytes,
+                max: this.max_bytes,
+            };
+
+            let src_status = match forward_data(&mut this.src, &mut this.dst, &mut budget, cx) {
                 Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
                 Poll::Ready(Ok(true)) => Status::Done,
                 Poll::Ready(Ok(false)) => Status::Progressed,
                 Poll::Pending => Status::Pending,
             };
 
-            let dst_status = match forward_data(&mut this.dst, &mut this.src, cx) {
+            let dst_status = match forward_data(&mut this.dst, &mut this.src, &mut budget, cx) {
                 Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
                 Poll::Ready(Ok(true)) => Status::Done,
                 Poll::Ready(Ok(false)) => Status::Progressed,
@@ -104,22 +248,39 @@ where
         }
 
         if let Poll::Ready(()) = this.active_timeout.poll_unpin(cx) {
-            return Poll::Ready(Err(io::ErrorKind::TimedOut.into()));
+            return Poll::Ready(Err(io::Error::from(io::ErrorKind::TimedOut).into()));
+        }
+
+        if let Some(Poll::Ready(())) = this.max_duration.as_mut().map(|d| d.poll_unpin(cx)) {
+            return Poll::Ready(Err(CopyError::LimitExceeded));
         }
 
         Poll::Pending
     }
 }
 
-/// Forwards data from `source` to `destination`.
+/// Accounts for the bytes relayed over a single circuit.
+struct ByteBudget<'a> {
+    /// Number of bytes relayed across all circuits.
+    total: &'a AtomicU64,
+    /// Number of bytes relayed over this circuit.
+    circuit: &'a mut u64,
+    /// Maximum number of bytes to relay over this circuit.
+    max: Option<u64>,
+}
+
+/// Forwards data from `source` to `destination`, adding the number of forwarded bytes to
+/// `budget`.
 ///
 /// Returns `true` when done, i.e. `source` having reached EOF, returns false otherwise, thus
-/// indicating progress.
+/// indicating progress. Fails with [`CopyError::LimitExceeded`] when there is data left to forward
+/// once the maximum number of bytes of `budget` has been forwarded.
 fn forward_data<S: AsyncBufRead + Unpin, D: AsyncWrite + Unpin>(
     mut src: &mut S,
     mut dst: &mut D,
+    budget: &mut ByteBudget<'_>,
     cx: &mut Context<'_>,
-) -> Poll<io::Result<bool>> {
+) -> Poll<Result<bool, CopyError>> {
     let buffer = ready!(Pin::new(&mut src).poll_fill_buf(cx))?;
     if buffer.is_empty() {
         ready!(Pin::new(&mut dst).poll_flush(cx))?;
@@ -127,11 +288,22 @@ fn forward_data<S: AsyncBufRead + Unpin, D: AsyncWrite + Unpin>(
         return Poll::Ready(Ok(true));
     }
 
+    let buffer = match budget.max.map(|max| max.saturating_sub(*budget.circuit)) {
+        Some(0) => return Poll::Ready(Err(CopyError::LimitExceeded)),
+        Some(remaining) => {
+            let remaining = usize::try_from(remaining).unwrap_or(usize::MAX);
+            &buffer[..buffer.len().min(remaining)]
+        }
+        None => buffer,
+    };
+
     let i = ready!(Pin::new(dst).poll_write(cx, buffer))?;
     if i == 0 {
-        return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+        return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()));
     }
     Pin::new(src).consume(i);
+    *budget.circuit += i as u64;
+    budget.total.fetch_add(i as u64, Ordering::Relaxed);
 
     Poll::Ready(Ok(false))
 }
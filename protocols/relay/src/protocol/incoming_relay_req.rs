@@ -18,7 +18,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use super::copy_future::CopyFuture;
+use super::copy_future::{CircuitLimits, CircuitReservation, CircuitStats, CopyError, CopyFuture};
 use crate::message_proto::{circuit_relay, circuit_relay::Status, CircuitRelay};
 use crate::protocol::Peer;
 
@@ -29,6 +29,7 @@ use futures::future::BoxFuture;
 use futures::prelude::*;
 use libp2p_swarm::NegotiatedSubstream;
 use prost::Message;
+use std::convert::TryFrom;
 use std::time::Duration;
 use unsigned_varint::codec::UviBytes;
 
@@ -46,6 +47,8 @@ pub struct IncomingRelayReq {
     stream: Framed<NegotiatedSubstream, UviBytes>,
     /// Target of the request.
     dest: Peer,
+    /// The circuit reserved for the request, if any, released once the request is dropped.
+    _reservation: Option<CircuitReservation>,
 
     _notifier: oneshot::Sender<()>,
 }
@@ -63,6 +66,7 @@ impl IncomingRelayReq
             IncomingRelayReq {
                 stream,
                 dest,
+                _reservation: None,
                 _notifier: tx,
             },
             rx,
@@ -74,11 +78,22 @@ impl IncomingRelayReq
         &self.dest
     }
 
+    /// Attaches a circuit reservation to the request, keeping the circuit reserved until the
+    /// request is denied or the circuit relayed on its behalf is closed.
+    pub(crate) fn set_reservation(&mut self, reservation: CircuitReservation) {
+        self._reservation = Some(reservation);
+    }
+
     /// Accepts the request by providing a stream to the destination.
+    ///
+    /// The relayed circuit and the data transferred over it, including `dst_read_buffer`, are
+    /// accounted for in `stats`. The circuit is closed once it exceeds one of the given `limits`.
     pub fn fulfill<TDestSubstream>(
         mut self,
         dst_stream: TDestSubstream,
         dst_read_buffer: Bytes,
+        mut limits: CircuitLimits,
+        stats: CircuitStats,
     ) -> BoxFuture<'static, Result<(), IncomingRelayReqError>>
     where
         TDestSubstream: AsyncRead + AsyncWrite + Send + Unpin + 'static,
@@ -112,10 +127,24 @@ impl IncomingRelayReq
             );
 
             if !dst_read_buffer.is_empty() {
-                io.write_all(&dst_read_buffer).await?;
+                // The data already read from the destination counts towards the byte limit of the
+                // circuit, like the data relayed by the `CopyFuture`.
+                let len = match limits.max_bytes {
+                    Some(max) => dst_read_buffer
+                        .len()
+                        .min(usize::try_from(max).unwrap_or(usize::MAX)),
+                    None => dst_read_buffer.len(),
+                };
+                io.write_all(&dst_read_buffer[..len]).await?;
+                stats.add_relayed_bytes(len as u64);
+                if len < dst_read_buffer.len() {
+                    return Err(IncomingRelayReqError::LimitExceeded);
+                }
+                limits.max_bytes = limits.max_bytes.map(|max| max - len as u64);
             }
 
-            let copy_future = CopyFuture::new(io, dst_stream, Duration::from_secs(5));
+            let copy_future =
+                CopyFuture::new(io, dst_stream, Duration::from_secs(5), limits, stats);
 
             copy_future.await.map_err(Into::into)
         }
@@ -147,6 +176,8 @@ impl IncomingRelayReq
 #[derive(Debug)]
 pub enum IncomingRelayReqError {
     Io(std::io::Error),
+    /// The relayed circuit exceeded its limits.
+    LimitExceeded,
 }
 
 impl From<std::io::Error> for IncomingRelayReqError {
@@ -154,3 +185,12 @@ impl From<std::io::Error> for IncomingRelayReqError {
         IncomingRelayReqError::Io(e)
     }
 }
+
+impl From<CopyError> for IncomingRelayReqError {
+    fn from(e: CopyError) -> Self {
+        match e {
+            CopyError::Io(e) => IncomingRelayReqError::Io(e),
+            CopyError::LimitExceeded => IncomingRelayReqError::LimitExceeded,
+        }
+    }
+}
@@ -34,7 +34,7 @@ use libp2p_identify::{Identify, IdentifyConfig, IdentifyEvent, IdentifyInfo};
 use libp2p_kad::{GetClosestPeersOk, Kademlia, KademliaEvent, QueryResult};
 use libp2p_ping::{Ping, PingConfig, PingEvent};
 use libp2p_plaintext::PlainText2Config;
-use libp2p_relay::{Relay, RelayConfig};
+use libp2p_relay::{CircuitCloseReason, Relay, RelayConfig, RelayEvent};
 use libp2p_swarm::protocols_handler::{
     KeepAlive, ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr, SubstreamProtocol,
 };
@@ -195,6 +195,411 @@ fn src_connect_to_dst_listening_via_relay() {
     });
 }
 
+#[test]
+fn relay_reports_active_circuits_and_relayed_bytes() {
+    let _ = env_logger::try_init();
+
+    let mut pool = LocalPool::new();
+
+    let mut src_swarm = build_swarm(Reachability::Firewalled, RelayMode::Passive);
+    let mut dst_swarm = build_swarm(Reachability::Firewalled, RelayMode::Passive);
+    let mut relay_swarm = build_swarm(Reachability::Routable, RelayMode::Passive);
+
+    let dst_peer_id = *dst_swarm.local_peer_id();
+    let relay_peer_id = *relay_swarm.local_peer_id();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let dst_listen_addr_via_relay = relay_addr
+        .clone()
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit);
+    let dst_addr_via_relay = dst_listen_addr_via_relay
+        .clone()
+        .with(Protocol::P2p(dst_peer_id.into()));
+
+    relay_swarm.listen_on(relay_addr.clone()).unwrap();
+    let dst_listener = dst_swarm
+        .listen_on(dst_listen_addr_via_relay.clone())
+        .unwrap();
+
+    assert_eq!(relay_swarm.behaviour().relay.active_circuits(), 0);
+    assert_eq!(relay_swarm.behaviour().relay.relayed_bytes(), 0);
+
+    pool.run_until(async {
+        let relay = async {
+            loop {
+                relay_swarm.select_next_some().await;
+            }
+        };
+
+        let dst_and_src = async {
+            // Destination Node reporting listen address via relay.
+            loop {
+                match dst_swarm.select_next_some().await {
+                    SwarmEvent::NewListenAddr { listener_id, .. } if listener_id == dst_listener => {
+                        break
+                    }
+                    _ => {}
+                }
+            }
+
+            let dst = async {
+                loop {
+                    dst_swarm.select_next_some().await;
+                }
+            };
+
+            src_swarm.dial_addr(dst_addr_via_relay).unwrap();
+            let src = async {
+                // Source Node waiting for Ping from Destination Node via Relay.
+                loop {
+                    match src_swarm.select_next_some().await {
+                        SwarmEvent::Behaviour(CombinedEvent::Ping(PingEvent {
+                            peer,
+                            result: Ok(_),
+                        })) if peer == dst_peer_id => break,
+                        _ => {}
+                    }
+                }
+            };
+
+            futures::future::select(dst.boxed(), src.boxed()).await;
+        };
+
+        futures::future::select(relay.boxed(), dst_and_src.boxed()).await;
+    });
+
+    assert_eq!(relay_swarm.behaviour().relay.active_circuits(), 1);
+    assert!(relay_swarm.behaviour().relay.relayed_bytes() > 0);
+}
+
+#[test]
+fn relay_reports_circuit_events() {
+    let _ = env_logger::try_init();
+
+    let mut pool = LocalPool::new();
+
+    let mut src_swarm = build_swarm(Reachability::Firewalled, RelayMode::Passive);
+    let mut dst_swarm = build_swarm(Reachability::Firewalled, RelayMode::Passive);
+    let mut relay_swarm = build_swarm(Reachability::Routable, RelayMode::Passive);
+
+    let src_peer_id = *src_swarm.local_peer_id();
+    let dst_peer_id = *dst_swarm.local_peer_id();
+    let relay_peer_id = *relay_swarm.local_peer_id();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let dst_listen_addr_via_relay = relay_addr
+        .clone()
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit);
+    let dst_addr_via_relay = dst_listen_addr_via_relay
+        .clone()
+        .with(Protocol::P2p(dst_peer_id.into()));
+
+    relay_swarm.listen_on(relay_addr.clone()).unwrap();
+    let dst_listener = dst_swarm
+        .listen_on(dst_listen_addr_via_relay.clone())
+        .unwrap();
+
+    pool.run_until(async {
+        let relay = async {
+            loop {
+                relay_swarm.select_next_some().await;
+            }
+        };
+
+        let dst_and_src = async {
+            // Destination Node reporting listen address via relay.
+            loop {
+                match dst_swarm.select_next_some().await {
+                    SwarmEvent::NewListenAddr { listener_id, .. } if listener_id == dst_listener => {
+                        break
+                    }
+                    _ => {}
+                }
+            }
+
+            let dst = async {
+                loop {
+                    dst_swarm.select_next_some().await;
+                }
+            };
+
+            src_swarm.dial_addr(dst_addr_via_relay).unwrap();
+            let src = async {
+                // Source Node waiting for Ping from Destination Node via Relay.
+                loop {
+                    match src_swarm.select_next_some().await {
+                        SwarmEvent::Behaviour(CombinedEvent::Ping(PingEvent {
+                            peer,
+                            result: Ok(_),
+                        })) if peer == dst_peer_id => break,
+                        _ => {}
+                    }
+                }
+            };
+
+            futures::future::select(dst.boxed(), src.boxed()).await;
+        };
+
+        futures::future::select(relay.boxed(), dst_and_src.boxed()).await;
+    });
+
+    assert!(matches!(
+        dst_swarm.behaviour().relay_events.as_slice(),
+        [
+            RelayEvent::ReservationRequested { peer },
+            RelayEvent::ReservationAccepted { relay, .. },
+        ] if *peer == relay_peer_id && *relay == relay_peer_id
+    ));
+
+    // Source Node going offline, closing the circuit.
+    drop(src_swarm);
+
+    pool.run_until(async {
+        let relay = async {
+            loop {
+                relay_swarm.select_next_some().await;
+                let closed = relay_swarm
+                    .behaviour()
+                    .relay_events
+                    .iter()
+                    .any(|e| matches!(e, RelayEvent::CircuitClosed { .. }));
+                if closed {
+                    break;
+                }
+            }
+        };
+
+        let dst = async {
+            loop {
+                dst_swarm.select_next_some().await;
+            }
+        };
+
+        futures::future::select(relay.boxed(), dst.boxed()).await;
+    });
+
+    match relay_swarm.behaviour().relay_events.as_slice() {
+        [RelayEvent::CircuitOpened { src, dst }, RelayEvent::CircuitClosed {
+            src: closed_src,
+            dst: closed_dst,
+            ..
+        }] => {
+            assert_eq!(*src, src_peer_id);
+            assert_eq!(*dst, dst_peer_id);
+            assert_eq!(closed_src, src);
+            assert_eq!(closed_dst, dst);
+        }
+        events => panic!("Unexpected relay events: {:?}", events),
+    }
+}
+
+#[test]
+fn relay_closes_circuit_exceeding_byte_limit() {
+    let _ = env_logger::try_init();
+
+    let mut pool = LocalPool::new();
+
+    let mut src_swarm = build_swarm(Reachability::Firewalled, RelayMode::Passive);
+    let mut dst_swarm = build_swarm(Reachability::Firewalled, RelayMode::Passive);
+    let mut relay_swarm = build_swarm_with_config(
+        Reachability::Routable,
+        RelayConfig {
+            circuit_max_bytes: Some(1024),
+            ..Default::default()
+        },
+    );
+
+    let dst_peer_id = *dst_swarm.local_peer_id();
+    let relay_peer_id = *relay_swarm.local_peer_id();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let dst_listen_addr_via_relay = relay_addr
+        .clone()
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit);
+    let dst_addr_via_relay = dst_listen_addr_via_relay
+        .clone()
+        .with(Protocol::P2p(dst_peer_id.into()));
+
+    relay_swarm.listen_on(relay_addr.clone()).unwrap();
+    let dst_listener = dst_swarm
+        .listen_on(dst_listen_addr_via_relay.clone())
+        .unwrap();
+
+    pool.run_until(async {
+        // Destination Node reporting listen address via relay.
+        let dst_listening = async {
+            loop {
+                match dst_swarm.select_next_some().await {
+                    SwarmEvent::NewListenAddr { listener_id, .. } if listener_id == dst_listener => {
+                        break
+                    }
+                    _ => {}
+                }
+            }
+        };
+        let relay = async {
+            loop {
+                relay_swarm.select_next_some().await;
+            }
+        };
+        futures::future::select(relay.boxed(), dst_listening.boxed()).await;
+    });
+
+    src_swarm.dial_addr(dst_addr_via_relay).unwrap();
+
+    // Source and Destination Node keep exchanging pings via the Relay until the circuit exceeds
+    // its byte limit.
+    pool.run_until(async {
+        let relay = async {
+            loop {
+                relay_swarm.select_next_some().await;
+                let closed = relay_swarm
+                    .behaviour()
+                    .relay_events
+                    .iter()
+                    .any(|e| matches!(e, RelayEvent::CircuitClosed { .. }));
+                if closed {
+                    break;
+                }
+            }
+        };
+        let dst = async {
+            loop {
+                dst_swarm.select_next_some().await;
+            }
+        };
+        let src = async {
+            loop {
+                src_swarm.select_next_some().await;
+            }
+        };
+        let dst_and_src = futures::future::select(dst.boxed(), src.boxed());
+        futures::future::select(relay.boxed(), dst_and_src).await;
+    });
+
+    match relay_swarm.behaviour().relay_events.as_slice() {
+        [RelayEvent::CircuitOpened { .. }, RelayEvent::CircuitClosed { reason, .. }] => {
+            assert!(matches!(reason, CircuitCloseReason::LimitExceeded));
+        }
+        events => panic!("Unexpected relay events: {:?}", events),
+    }
+    assert!(relay_swarm.behaviour().relay.relayed_bytes() <= 1024);
+}
+
+#[test]
+fn dst_reserves_via_relay_through_behaviour() {
+    let _ = env_logger::try_init();
+
+    let mut pool = LocalPool::new();
+
+    let mut dst_swarm = build_swarm(Reachability::Firewalled, RelayMode::Passive);
+    let mut relay_swarm = build_swarm(Reachability::Routable, RelayMode::Passive);
+
+    let relay_peer_id = *relay_swarm.local_peer_id();
+
+    let relay_addr: Multiaddr = Protocol::Memory(rand::random::<u64>()).into();
+    let dst_listen_addr_via_relay = relay_addr
+        .clone()
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit);
+
+    relay_swarm.listen_on(relay_addr.clone()).unwrap();
+    spawn_swarm_on_pool(&pool, relay_swarm);
+
+    dst_swarm
+        .behaviour_mut()
+        .relay
+        .reserve(relay_peer_id, relay_addr);
+
+    pool.run_until(async {
+        // Destination Node reporting listen address via relay, requested by its behaviour.
+        loop {
+            match dst_swarm.select_next_some().await {
+                SwarmEvent::NewListenAddr { address, .. } => {
+                    assert_eq!(address, dst_listen_addr_via_relay);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        loop {
+            let accepted = dst_swarm
+                .behaviour()
+                .relay_events
+                .iter()
+                .any(|e| matches!(e, RelayEvent::ReservationAccepted { .. }));
+            if accepted {
+                break;
+            }
+            dst_swarm.select_next_some().await;
+        }
+    });
+
+    assert!(matches!(
+        dst_swarm.behaviour().relay_events.as_slice(),
+        [
+            RelayEvent::ReservationRequested { peer },
+            RelayEvent::ReservationAccepted { relay, .. },
+        ] if *peer == relay_peer_id && *relay == relay_peer_id
+    ));
+}
+
+#[test]
+fn dst_renews_reservation_via_relay() {
+    let _ = env_logger::try_init();
+
+    let mut pool = LocalPool::new();
+
+    let mut dst_swarm = build_swarm_with_config(
+        Reachability::Firewalled,
+        RelayConfig {
+            reservation_duration: Duration::from_millis(400),
+            ..Default::default()
+        },
+    );
+    let mut relay_swarm = build_swarm(Reachability::Routable, RelayMode::Passive);
+
+    let relay_peer_id = *relay_swarm.local_peer_id();
+
+    let relay_addr: Multiaddr = Protocol::Memory(rand::random::<u64>()).into();
+    relay_swarm.listen_on(relay_addr.clone()).unwrap();
+    spawn_swarm_on_pool(&pool, relay_swarm);
+
+    dst_swarm
+        .behaviour_mut()
+        .relay
+        .reserve(relay_peer_id, relay_addr);
+
+    let accepted = |swarm: &Swarm<CombinedBehaviour>| {
+        swarm
+            .behaviour()
+            .relay_events
+            .iter()
+            .filter_map(|e| match e {
+                RelayEvent::ReservationAccepted { relay, expires_at } => {
+                    assert_eq!(*relay, relay_peer_id);
+                    Some(*expires_at)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    pool.run_until(async {
+        // Destination Node renewing its reservation before it expires.
+        while accepted(&dst_swarm).len() < 2 {
+            dst_swarm.select_next_some().await;
+        }
+    });
+
+    let expirations = accepted(&dst_swarm);
+    assert!(expirations[1] > expirations[0]);
+}
+
 #[test]
 fn src_connect_to_dst_not_listening_via_active_relay() {
     let _ = env_logger::try_init();
@@ -860,6 +1265,170 @@ fn concurrent_connection_same_relay_same_dst() {
     });
 }
 
+#[test]
+fn relay_refuses_circuits_exceeding_limit() {
+    let _ = env_logger::try_init();
+
+    let mut pool = LocalPool::new();
+
+    let mut src_swarm = build_swarm(Reachability::Firewalled, RelayMode::Passive);
+    let mut dst_swarm = build_swarm(Reachability::Routable, RelayMode::Passive);
+    let mut relay_swarm = build_swarm_with_config(
+        Reachability::Routable,
+        RelayConfig {
+            max_circuits_per_peer: Some(1),
+            ..Default::default()
+        },
+    );
+
+    let relay_peer_id = *relay_swarm.local_peer_id();
+    let dst_peer_id = *dst_swarm.local_peer_id();
+
+    let relay_addr: Multiaddr = Protocol::Memory(rand::random::<u64>()).into();
+    let dst_addr_via_relay = relay_addr
+        .clone()
+        .with(Protocol::P2p(relay_peer_id.clone().into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.clone().into()));
+
+    relay_swarm.listen_on(relay_addr.clone()).unwrap();
+    spawn_swarm_on_pool(&pool, relay_swarm);
+
+    let dst_listener = dst_swarm.listen_on(dst_addr_via_relay.clone()).unwrap();
+    // Wait for destination to listen via relay.
+    pool.run_until(async {
+        loop {
+            match dst_swarm.select_next_some().await {
+                SwarmEvent::Dialing(_) => {}
+                SwarmEvent::ConnectionEstablished { .. } => {}
+                SwarmEvent::NewListenAddr { listener_id, .. } if listener_id == dst_listener => {
+                    break
+                }
+                e => panic!("{:?}", e),
+            }
+        }
+    });
+    spawn_swarm_on_pool(&pool, dst_swarm);
+
+    pool.run_until(async move {
+        src_swarm.dial_addr(dst_addr_via_relay.clone()).unwrap();
+        src_swarm.dial_addr(dst_addr_via_relay.clone()).unwrap();
+
+        // Source Node establishing one connection to destination node via Relay, the second
+        // circuit being refused by the Relay.
+        let mut established = false;
+        let mut refused = false;
+        while !(established && refused) {
+            match src_swarm.select_next_some().await {
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == relay_peer_id => {}
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == dst_peer_id => {
+                    assert!(!established, "Expected second circuit to be refused.");
+                    established = true;
+                }
+                SwarmEvent::UnreachableAddr {
+                    address, peer_id, ..
+                } if address == dst_addr_via_relay => {
+                    assert_eq!(peer_id, dst_peer_id);
+                    assert!(!refused, "Expected first circuit to be established.");
+                    refused = true;
+                }
+                SwarmEvent::Behaviour(CombinedEvent::Ping(_)) => {}
+                SwarmEvent::Dialing(peer_id) => {
+                    assert_eq!(peer_id, relay_peer_id);
+                }
+                e => panic!("{:?}", e),
+            }
+        }
+    });
+}
+
+#[test]
+fn relay_refuses_circuits_exceeding_total_limit() {
+    let _ = env_logger::try_init();
+
+    let mut pool = LocalPool::new();
+
+    let mut src_1_swarm = build_swarm(Reachability::Firewalled, RelayMode::Passive);
+    let mut src_2_swarm = build_swarm(Reachability::Firewalled, RelayMode::Passive);
+    let mut dst_swarm = build_swarm(Reachability::Routable, RelayMode::Passive);
+    let mut relay_swarm = build_swarm_with_config(
+        Reachability::Routable,
+        RelayConfig {
+            max_circuits: Some(1),
+            ..Default::default()
+        },
+    );
+
+    let relay_peer_id = *relay_swarm.local_peer_id();
+    let dst_peer_id = *dst_swarm.local_peer_id();
+
+    let relay_addr: Multiaddr = Protocol::Memory(rand::random::<u64>()).into();
+    let dst_addr_via_relay = relay_addr
+        .clone()
+        .with(Protocol::P2p(relay_peer_id.clone().into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.clone().into()));
+
+    relay_swarm.listen_on(relay_addr.clone()).unwrap();
+    spawn_swarm_on_pool(&pool, relay_swarm);
+
+    let dst_listener = dst_swarm.listen_on(dst_addr_via_relay.clone()).unwrap();
+    // Wait for destination to listen via relay.
+    pool.run_until(async {
+        loop {
+            match dst_swarm.select_next_some().await {
+                SwarmEvent::Dialing(_) => {}
+                SwarmEvent::ConnectionEstablished { .. } => {}
+                SwarmEvent::NewListenAddr { listener_id, .. } if listener_id == dst_listener => {
+                    break
+                }
+                e => panic!("{:?}", e),
+            }
+        }
+    });
+    spawn_swarm_on_pool(&pool, dst_swarm);
+
+    // First source node establishing a connection to destination node via Relay.
+    src_1_swarm.dial_addr(dst_addr_via_relay.clone()).unwrap();
+    pool.run_until(async {
+        loop {
+            match src_1_swarm.select_next_some().await {
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == relay_peer_id => {}
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == dst_peer_id => {
+                    break
+                }
+                SwarmEvent::Behaviour(CombinedEvent::Ping(_)) => {}
+                SwarmEvent::Dialing(peer_id) => {
+                    assert_eq!(peer_id, relay_peer_id);
+                }
+                e => panic!("{:?}", e),
+            }
+        }
+    });
+    spawn_swarm_on_pool(&pool, src_1_swarm);
+
+    // Second source node being refused by the Relay, the first circuit still being open.
+    src_2_swarm.dial_addr(dst_addr_via_relay.clone()).unwrap();
+    pool.run_until(async move {
+        loop {
+            match src_2_swarm.select_next_some().await {
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == relay_peer_id => {}
+                SwarmEvent::UnreachableAddr {
+                    address, peer_id, ..
+                } if address == dst_addr_via_relay => {
+                    assert_eq!(peer_id, dst_peer_id);
+                    break;
+                }
+                SwarmEvent::Behaviour(CombinedEvent::Ping(_)) => {}
+                SwarmEvent::Dialing(peer_id) => {
+                    assert_eq!(peer_id, relay_peer_id);
+                }
+                e => panic!("{:?}", e),
+            }
+        }
+    });
+}
+
 /// Yield incoming connection through listener that listens via the relay node used by the
 /// connection. In case the local node does not listen via the specific relay node, but has
 /// registered a listener for all remaining incoming relayed connections, yield the connection via
@@ -1125,6 +1694,8 @@ struct CombinedBehaviour {
 
     #[behaviour(ignore)]
     events: Vec<CombinedEvent>,
+    #[behaviour(ignore)]
+    relay_events: Vec<RelayEvent>,
 }
 
 #[derive(Debug)]
@@ -1174,9 +1745,9 @@ impl NetworkBehaviourEventProcess<IdentifyEvent> for CombinedBehaviour {
     }
 }
 
-impl NetworkBehaviourEventProcess<()> for CombinedBehaviour {
-    fn inject_event(&mut self, _event: ()) {
-        unreachable!();
+impl NetworkBehaviourEventProcess<RelayEvent> for CombinedBehaviour {
+    fn inject_event(&mut self, event: RelayEvent) {
+        self.relay_events.push(event);
     }
 }
 
@@ -1186,10 +1757,8 @@ struct CombinedKeepAliveBehaviour {
     keep_alive: KeepAliveBehaviour,
 }
 
-impl NetworkBehaviourEventProcess<()> for CombinedKeepAliveBehaviour {
-    fn inject_event(&mut self, _event: ()) {
-        unreachable!();
-    }
+impl NetworkBehaviourEventProcess<RelayEvent> for CombinedKeepAliveBehaviour {
+    fn inject_event(&mut self, _event: RelayEvent) {}
 }
 
 impl NetworkBehaviourEventProcess<Void> for CombinedKeepAliveBehaviour {
@@ -1244,6 +1813,19 @@ impl<T: Transport> Transport for Firewall<T> {
 }
 
 fn build_swarm(reachability: Reachability, relay_mode: RelayMode) -> Swarm<CombinedBehaviour> {
+    build_swarm_with_config(
+        reachability,
+        RelayConfig {
+            actively_connect_to_dst_nodes: relay_mode.into(),
+            ..Default::default()
+        },
+    )
+}
+
+fn build_swarm_with_config(
+    reachability: Reachability,
+    relay_config: RelayConfig,
+) -> Swarm<CombinedBehaviour> {
     let local_key = identity::Keypair::generate_ed25519();
     let local_public_key = local_key.public();
     let plaintext = PlainText2Config {
@@ -1258,13 +1840,8 @@ fn build_swarm(reachability: Reachability, relay_mode: RelayMode) -> Swarm<Combi
         Reachability::Routable => EitherTransport::Right(transport),
     };
 
-    let (transport, relay_behaviour) = libp2p_relay::new_transport_and_behaviour(
-        RelayConfig {
-            actively_connect_to_dst_nodes: relay_mode.into(),
-            ..Default::default()
-        },
-        transport,
-    );
+    let (transport, relay_behaviour) =
+        libp2p_relay::new_transport_and_behaviour(relay_config, transport);
 
     let transport = transport
         .upgrade(upgrade::Version::V1)
@@ -1284,6 +1861,7 @@ fn build_swarm(reachability: Reachability, relay_mode: RelayMode) -> Swarm<Combi
             local_public_key.clone(),
         )),
         events: Default::default(),
+        relay_events: Default::default(),
     };
 
     Swarm::new(transport, combined_behaviour, local_peer_id)
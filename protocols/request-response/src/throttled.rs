@@ -659,7 +659,11 @@ where
                 | NetworkBehaviourAction::ReportObservedAddr { address, score } =>
                     NetworkBehaviourAction::ReportObservedAddr { address, score },
                 | NetworkBehaviourAction::CloseConnection { peer_id, connection } =>
-                    NetworkBehaviourAction::CloseConnection { peer_id, connection }
+                    NetworkBehaviourAction::CloseConnection { peer_id, connection },
+                | NetworkBehaviourAction::ReportPeerUnroutable(peer_id) =>
+                    NetworkBehaviourAction::ReportPeerUnroutable(peer_id),
+                | NetworkBehaviourAction::ListenOn(address) =>
+                    NetworkBehaviourAction::ListenOn(address)
             };
 
             return Poll::Ready(event)
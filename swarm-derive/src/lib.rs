@@ -482,6 +482,12 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                     std::task::Poll::Ready(#network_behaviour_action::CloseConnection { peer_id, connection }) => {
                         return std::task::Poll::Ready(#network_behaviour_action::CloseConnection { peer_id, connection });
                     }
+                    std::task::Poll::Ready(#network_behaviour_action::ReportPeerUnroutable(peer_id)) => {
+                        return std::task::Poll::Ready(#network_behaviour_action::ReportPeerUnroutable(peer_id));
+                    }
+                    std::task::Poll::Ready(#network_behaviour_action::ListenOn(address)) => {
+                        return std::task::Poll::Ready(#network_behaviour_action::ListenOn(address));
+                    }
                     std::task::Poll::Pending => break,
                 }
             }
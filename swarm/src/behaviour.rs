@@ -180,6 +180,10 @@ pub trait NetworkBehaviour: Send + 'static {
     ///
     /// This API mimics the API of the `Stream` trait. The method may register the current task in
     /// order to wake it up at a later point in time.
+    ///
+    /// A behaviour that defers work, e.g. from one of the `inject_*` methods or from a method
+    /// called by the user, can keep a clone of [`Context::waker`] and wake it to have the `Swarm`
+    /// poll the behaviour again, instead of returning a spurious event.
     fn poll(&mut self, cx: &mut Context<'_>, params: &mut impl PollParameters)
         -> Poll<NetworkBehaviourAction<<<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent, Self::OutEvent>>;
 }
@@ -309,7 +313,27 @@ pub enum NetworkBehaviourAction<TInEvent, TOutEvent> {
         peer_id: PeerId,
         /// Whether to close a specific or all connections to the given peer.
         connection: CloseConnection,
-    }
+    },
+
+    /// Informs the `Swarm` that the given peer is currently unreachable,
+    /// e.g. because none of its known addresses can be dialed.
+    ///
+    /// The `Swarm` refuses to dial the peer until the TTL configured via
+    /// [`SwarmBuilder::unroutable_peer_ttl`](crate::SwarmBuilder::unroutable_peer_ttl)
+    /// has elapsed. Unlike a ban via
+    /// [`ExpandedSwarm::ban_peer_id`](crate::ExpandedSwarm::ban_peer_id),
+    /// existing connections are not affected and incoming connections are
+    /// still accepted.
+    ReportPeerUnroutable(PeerId),
+
+    /// Instructs the `Swarm` to start listening on the given address.
+    ///
+    /// The behaviour is informed of the new listener through
+    /// [`NetworkBehaviour::inject_new_listener`] and the listening addresses
+    /// are reported as [`SwarmEvent::NewListenAddr`](crate::SwarmEvent::NewListenAddr),
+    /// as for [`ExpandedSwarm::listen_on`](crate::ExpandedSwarm::listen_on).
+    /// If the address is not supported by the transport, the action is ignored.
+    ListenOn(Multiaddr),
 }
 
 impl<TInEvent, TOutEvent> NetworkBehaviourAction<TInEvent, TOutEvent> {
@@ -331,7 +355,11 @@ impl<TInEvent, TOutEvent> NetworkBehaviourAction<TInEvent, TOutEvent> {
             NetworkBehaviourAction::ReportObservedAddr { address, score } =>
                 NetworkBehaviourAction::ReportObservedAddr { address, score },
             NetworkBehaviourAction::CloseConnection { peer_id, connection } =>
-                NetworkBehaviourAction::CloseConnection { peer_id, connection }
+                NetworkBehaviourAction::CloseConnection { peer_id, connection },
+            NetworkBehaviourAction::ReportPeerUnroutable(peer_id) =>
+                NetworkBehaviourAction::ReportPeerUnroutable(peer_id),
+            NetworkBehaviourAction::ListenOn(address) =>
+                NetworkBehaviourAction::ListenOn(address)
         }
     }
 
@@ -349,7 +377,11 @@ impl<TInEvent, TOutEvent> NetworkBehaviourAction<TInEvent, TOutEvent> {
             NetworkBehaviourAction::ReportObservedAddr { address, score } =>
                 NetworkBehaviourAction::ReportObservedAddr { address, score },
             NetworkBehaviourAction::CloseConnection { peer_id, connection } =>
-                NetworkBehaviourAction::CloseConnection { peer_id, connection }
+                NetworkBehaviourAction::CloseConnection { peer_id, connection },
+            NetworkBehaviourAction::ReportPeerUnroutable(peer_id) =>
+                NetworkBehaviourAction::ReportPeerUnroutable(peer_id),
+            NetworkBehaviourAction::ListenOn(address) =>
+                NetworkBehaviourAction::ListenOn(address)
         }
     }
 }
@@ -365,7 +397,7 @@ pub enum NotifyHandler {
 
 /// The available conditions under which a new dialing attempt to
 /// a peer is initiated when requested by [`NetworkBehaviourAction::DialPeer`].
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum DialPeerCondition {
     /// A new dialing attempt is initiated _only if_ the peer is currently
@@ -0,0 +1,128 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::channel::mpsc;
+use libp2p_core::{Multiaddr, PeerId};
+use std::{error, fmt};
+
+/// A command sent to an [`ExpandedSwarm`](crate::ExpandedSwarm) through a [`SwarmHandle`].
+pub(crate) enum SwarmCommand<TBehaviour> {
+    Dial(PeerId),
+    DialAddr(Multiaddr),
+    BanPeerId(PeerId),
+    UnbanPeerId(PeerId),
+    DisconnectPeerId(PeerId),
+    Behaviour(Box<dyn FnOnce(&mut TBehaviour) + Send>),
+}
+
+/// A cloneable handle to an [`ExpandedSwarm`](crate::ExpandedSwarm), obtained via
+/// [`ExpandedSwarm::handle`](crate::ExpandedSwarm::handle).
+///
+/// A `SwarmHandle` allows other tasks to issue commands to a `Swarm` that is
+/// driven elsewhere. Commands are queued and processed the next time the
+/// `Swarm` is polled, in the order in which they were issued. Their outcome is
+/// reported through the usual [`SwarmEvent`](crate::SwarmEvent)s and
+/// [`NetworkBehaviour`](crate::NetworkBehaviour) callbacks.
+///
+/// All methods return [`SwarmClosed`] if the `Swarm` no longer exists.
+pub struct SwarmHandle<TBehaviour> {
+    sender: mpsc::UnboundedSender<SwarmCommand<TBehaviour>>,
+}
+
+impl<TBehaviour> SwarmHandle<TBehaviour> {
+    pub(crate) fn new(sender: mpsc::UnboundedSender<SwarmCommand<TBehaviour>>) -> Self {
+        SwarmHandle { sender }
+    }
+
+    /// Initiates a new dialing attempt to the given peer.
+    ///
+    /// See [`ExpandedSwarm::dial`](crate::ExpandedSwarm::dial).
+    pub fn dial(&self, peer_id: PeerId) -> Result<(), SwarmClosed> {
+        self.send(SwarmCommand::Dial(peer_id))
+    }
+
+    /// Initiates a new dialing attempt to the given address.
+    ///
+    /// See [`ExpandedSwarm::dial_addr`](crate::ExpandedSwarm::dial_addr).
+    pub fn dial_addr(&self, addr: Multiaddr) -> Result<(), SwarmClosed> {
+        self.send(SwarmCommand::DialAddr(addr))
+    }
+
+    /// Bans a peer by its peer ID.
+    ///
+    /// See [`ExpandedSwarm::ban_peer_id`](crate::ExpandedSwarm::ban_peer_id).
+    pub fn ban_peer_id(&self, peer_id: PeerId) -> Result<(), SwarmClosed> {
+        self.send(SwarmCommand::BanPeerId(peer_id))
+    }
+
+    /// Unbans a peer.
+    ///
+    /// See [`ExpandedSwarm::unban_peer_id`](crate::ExpandedSwarm::unban_peer_id).
+    pub fn unban_peer_id(&self, peer_id: PeerId) -> Result<(), SwarmClosed> {
+        self.send(SwarmCommand::UnbanPeerId(peer_id))
+    }
+
+    /// Disconnects a peer by its peer ID, closing all connections to said peer.
+    ///
+    /// See [`ExpandedSwarm::disconnect_peer_id`](crate::ExpandedSwarm::disconnect_peer_id).
+    pub fn disconnect_peer_id(&self, peer_id: PeerId) -> Result<(), SwarmClosed> {
+        self.send(SwarmCommand::DisconnectPeerId(peer_id))
+    }
+
+    /// Runs the given closure on the [`NetworkBehaviour`](crate::NetworkBehaviour)
+    /// of the `Swarm`, e.g. in order to invoke behaviour-specific methods.
+    pub fn with_behaviour<F>(&self, f: F) -> Result<(), SwarmClosed>
+    where
+        F: FnOnce(&mut TBehaviour) + Send + 'static,
+    {
+        self.send(SwarmCommand::Behaviour(Box::new(f)))
+    }
+
+    fn send(&self, command: SwarmCommand<TBehaviour>) -> Result<(), SwarmClosed> {
+        self.sender.unbounded_send(command).map_err(|_| SwarmClosed)
+    }
+}
+
+impl<TBehaviour> Clone for SwarmHandle<TBehaviour> {
+    fn clone(&self) -> Self {
+        SwarmHandle {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<TBehaviour> fmt::Debug for SwarmHandle<TBehaviour> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SwarmHandle").finish()
+    }
+}
+
+/// Error returned by the methods of a [`SwarmHandle`] if the `Swarm` it refers
+/// to has been dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwarmClosed;
+
+impl fmt::Display for SwarmClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The Swarm has been dropped.")
+    }
+}
+
+impl error::Error for SwarmClosed {}
@@ -54,7 +54,9 @@
 //!
 
 mod behaviour;
+mod handle;
 mod registry;
+mod spans;
 #[cfg(test)]
 mod test;
 mod upgrade;
@@ -83,14 +85,21 @@ pub use protocols_handler::{
     OneShotHandlerConfig,
     SubstreamProtocol
 };
+pub use handle::{SwarmClosed, SwarmHandle};
 pub use registry::{AddressScore, AddressRecord, AddAddressResult};
 
+use handle::SwarmCommand;
 use protocols_handler::{
+    InFlightEvents,
+    KeepAliveOverrides,
     NodeHandlerWrapperBuilder,
     NodeHandlerWrapperError,
+    NodeHandlerWrapperEvent,
+    NodeHandlerWrapperInEvent,
 };
 use futures::{
     prelude::*,
+    channel::mpsc,
     executor::ThreadPoolBuilder,
     stream::FusedStream,
 };
@@ -125,10 +134,13 @@ use libp2p_core::{
     upgrade::{ProtocolName},
 };
 use registry::{Addresses, AddressIntoIter};
+use spans::ConnectionSpans;
 use smallvec::SmallVec;
 use std::{error, fmt, io, pin::Pin, task::{Context, Poll}};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::num::{NonZeroU32, NonZeroUsize};
+use std::time::Duration;
+use wasm_timer::{Delay, Instant};
 use upgrade::UpgradeInfoSend as _;
 
 /// Contains the state of the network, plus the way it should behave.
@@ -154,6 +166,11 @@ pub enum SwarmEvent<TBvEv, THandleErr> {
     ConnectionEstablished {
         /// Identity of the peer that we have connected to.
         peer_id: PeerId,
+        /// The connection that has been opened.
+        ///
+        /// For outgoing connections, this is the ID of the dialing attempt,
+        /// see [`ExpandedSwarm::dialing_attempts`].
+        connection: ConnectionId,
         /// Endpoint of the connection that has been opened.
         endpoint: ConnectedPoint,
         /// Number of established connections to this peer, including the one that has just been
@@ -165,6 +182,8 @@ pub enum SwarmEvent<TBvEv, THandleErr> {
     ConnectionClosed {
         /// Identity of the peer that we have connected to.
         peer_id: PeerId,
+        /// The connection that has been closed.
+        connection: ConnectionId,
         /// Endpoint of the connection that has been closed.
         endpoint: ConnectedPoint,
         /// Number of other remaining connections to this same peer.
@@ -173,12 +192,29 @@ pub enum SwarmEvent<TBvEv, THandleErr> {
         /// active close.
         cause: Option<ConnectionError<NodeHandlerWrapperError<THandleErr>>>,
     },
+    /// The [`KeepAlive`] of the [`ProtocolsHandler`] of a connection changed,
+    /// e.g. because the connection became idle and is about to be closed.
+    /// A recomputed deadline of [`KeepAlive::Until`] is not reported.
+    ///
+    /// Only reported if enabled via [`SwarmBuilder::report_keep_alive_changes`].
+    ConnectionKeepAliveChanged {
+        /// Identity of the peer that we are connected to.
+        peer_id: PeerId,
+        /// The connection whose handler changed its keep-alive.
+        connection: ConnectionId,
+        /// The new keep-alive of the handler.
+        keep_alive: KeepAlive,
+    },
     /// A new connection arrived on a listener and is in the process of protocol negotiation.
     ///
     /// A corresponding [`ConnectionEstablished`](SwarmEvent::ConnectionEstablished),
     /// [`BannedPeer`](SwarmEvent::BannedPeer), or
     /// [`IncomingConnectionError`](SwarmEvent::IncomingConnectionError) event will later be
     /// generated for this connection.
+    ///
+    /// Not reported for connections that are immediately rejected because the limit of
+    /// pending incoming connections is reached, see
+    /// [`IncomingConnectionError`](SwarmEvent::IncomingConnectionError).
     IncomingConnection {
         /// Local connection address.
         /// This address has been earlier reported with a [`NewListenAddr`](SwarmEvent::NewListenAddr)
@@ -191,6 +227,9 @@ pub enum SwarmEvent<TBvEv, THandleErr> {
     ///
     /// This can include, for example, an error during the handshake of the encryption layer, or
     /// the connection unexpectedly closed.
+    ///
+    /// Connections rejected because a connection limit is reached, as opposed to failing, are
+    /// reported with [`PendingConnectionError::ConnectionLimit`].
     IncomingConnectionError {
         /// Local connection address.
         /// This address has been earlier reported with a [`NewListenAddr`](SwarmEvent::NewListenAddr)
@@ -268,6 +307,22 @@ pub enum SwarmEvent<TBvEv, THandleErr> {
     /// [`UnreachableAddr`](SwarmEvent::UnreachableAddr) event is reported
     /// with `attempts_remaining` equal to 0.
     Dialing(PeerId),
+    /// The number of connected peers crossed one of the watermarks
+    /// configured via [`SwarmBuilder::connectivity_watermarks`].
+    ConnectivityChanged {
+        /// The number of peers we are connected to.
+        connected_peers: usize,
+        /// The new connectivity of the `Swarm`.
+        state: Connectivity,
+    },
+    /// A dialing attempt to a peer requested by the
+    /// [`NetworkBehaviour`] has been refused because of a [`DialPolicy`].
+    DialRefused {
+        /// The peer that was not dialed.
+        peer_id: PeerId,
+        /// Why the peer was not dialed.
+        policy: DialPolicy,
+    },
 }
 
 /// Contains the state of the network, plus the way it should behave.
@@ -276,12 +331,13 @@ pub enum SwarmEvent<TBvEv, THandleErr> {
 /// progress.
 pub struct ExpandedSwarm<TBehaviour, TInEvent, TOutEvent, THandler>
 where
+    TBehaviour: NetworkBehaviour,
     THandler: IntoProtocolsHandler,
 {
     network: Network<
         transport::Boxed<(PeerId, StreamMuxerBox)>,
-        TInEvent,
-        TOutEvent,
+        NodeHandlerWrapperInEvent<TInEvent>,
+        NodeHandlerWrapperEvent<TOutEvent>,
         NodeHandlerWrapperBuilder<THandler>,
     >,
 
@@ -302,18 +358,93 @@ where
     /// List of nodes for which we deny any incoming connection.
     banned_peers: HashSet<PeerId>,
 
+    /// Nodes reported as unroutable by the behaviour, which we refuse to dial
+    /// until the associated instant.
+    unroutable_peers: HashMap<PeerId, Instant>,
+
+    /// How long a peer reported as unroutable is not dialed.
+    unroutable_peer_ttl: Duration,
+
     /// Pending event to be delivered to connection handlers
     /// (or dropped if the peer disconnected) before the `behaviour`
     /// can be polled again.
-    pending_event: Option<(PeerId, PendingNotifyHandler, TInEvent)>,
+    pending_event: Option<(PeerId, PendingNotifyHandler, NodeHandlerWrapperInEvent<TInEvent>)>,
+
+    /// The maximum duration for which the `pending_event` is held before
+    /// it is dropped, if any.
+    pending_event_timeout: Option<Duration>,
+
+    /// Fires once the `pending_event` has been held for `pending_event_timeout`.
+    pending_event_delay: Option<Delay>,
+
+    /// The events sent to connection handlers that have not yet been processed,
+    /// counted only if `max_in_flight_events` is set.
+    in_flight_events: Option<InFlightEvents>,
+
+    /// The maximum number of `in_flight_events` up to which the `behaviour`
+    /// is polled, if any.
+    max_in_flight_events: Option<NonZeroUsize>,
 
     /// The configured override for substream protocol upgrades, if any.
     substream_upgrade_protocol_override: Option<libp2p_core::upgrade::Version>,
+
+    /// The duration for which idle connections are kept alive.
+    idle_connection_timeout: Duration,
+
+    /// Per-peer overrides of how long idle connections are kept alive.
+    keep_alive_overrides: KeepAliveOverrides,
+
+    /// The protection tags of peers, see [`ExpandedSwarm::protect_peer`].
+    protected_peers: HashMap<PeerId, HashSet<String>>,
+
+    /// Whether [`SwarmEvent::ConnectionKeepAliveChanged`] events are reported.
+    report_keep_alive_changes: bool,
+
+    /// Sender cloned into every [`SwarmHandle`] obtained via [`ExpandedSwarm::handle`].
+    command_sender: mpsc::UnboundedSender<SwarmCommand<TBehaviour>>,
+
+    /// Commands issued through [`SwarmHandle`]s, yet to be processed.
+    command_receiver: mpsc::UnboundedReceiver<SwarmCommand<TBehaviour>>,
+
+    /// The capacity of the `event_buffer` and what to do once it is full,
+    /// if events are buffered at all.
+    event_buffer_config: Option<(NonZeroUsize, EventBufferOverflow)>,
+
+    /// Events that have been produced but not yet been consumed.
+    event_buffer: VecDeque<SwarmEvent<
+        TBehaviour::OutEvent,
+        <THandler::Handler as ProtocolsHandler>::Error,
+    >>,
+
+    /// Limits on the number of concurrent outgoing connection attempts.
+    dial_limits: DialLimits,
+
+    /// Dials that exceeded the `dial_limits`, in the order they were issued.
+    queued_dials: VecDeque<QueuedDial>,
+
+    /// The policy for retrying failed dials, if any.
+    dial_retry_policy: Option<RetryPolicy>,
+
+    /// The retry state of the peers whose dials failed.
+    dial_retries: HashMap<PeerId, DialRetry>,
+
+    /// The low and high watermarks of connected peers, if any.
+    connectivity_watermarks: Option<(usize, usize)>,
+
+    /// The connectivity last reported.
+    connectivity: Connectivity,
+
+    /// How to resolve connections to a peer established in both directions.
+    simultaneous_open_policy: SimultaneousOpenPolicy,
+
+    /// The `tracing` spans of the connections, see the `tracing` feature.
+    connection_spans: ConnectionSpans,
 }
 
 impl<TBehaviour, TInEvent, TOutEvent, THandler> Unpin for
     ExpandedSwarm<TBehaviour, TInEvent, TOutEvent, THandler>
 where
+    TBehaviour: NetworkBehaviour,
     THandler: IntoProtocolsHandler,
 {
 }
@@ -360,20 +491,55 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
     }
 
     /// Initiates a new dialing attempt to the given address.
+    ///
+    /// If the dial concurrency limits configured on the [`SwarmBuilder`] are
+    /// reached, the dialing attempt is queued until they permit it.
     pub fn dial_addr(&mut self, addr: Multiaddr) -> Result<(), DialError> {
+        if !self.is_dial_permitted(&addr) {
+            log::debug!("Dial concurrency limit reached; queueing dial to {:?}.", addr);
+            self.queued_dials.push_back(QueuedDial::Address(addr));
+            return Ok(())
+        }
+
         let handler = self.behaviour.new_handler()
             .into_node_handler_builder()
-            .with_substream_upgrade_protocol_override(self.substream_upgrade_protocol_override);
-        Ok(self.network.dial(&addr, handler).map(|_id| ())?)
+            .with_substream_upgrade_protocol_override(self.substream_upgrade_protocol_override)
+            .with_idle_connection_timeout(self.idle_connection_timeout)
+            .with_keep_alive_overrides(self.keep_alive_overrides.clone())
+            .with_keep_alive_change_reports(self.report_keep_alive_changes);
+        let id = self.network.dial(&addr, handler)?;
+        self.connection_spans.dialing(id, None, &addr);
+        Ok(())
     }
 
     /// Initiates a new dialing attempt to the given peer.
+    ///
+    /// If the dial concurrency limits configured on the [`SwarmBuilder`] are
+    /// reached for the first address of the peer, the dialing attempt is
+    /// queued until they permit it.
     pub fn dial(&mut self, peer_id: &PeerId) -> Result<(), DialError> {
+        self.dial_peer(peer_id, None).map(|_| ())
+    }
+
+    /// Initiates a new dialing attempt to the given peer, queueing it together
+    /// with the `condition` it was requested with if the dial concurrency
+    /// limits are reached.
+    ///
+    /// Returns `Ok(true)` if the dialing attempt has been initiated and
+    /// `Ok(false)` if it has been queued.
+    fn dial_peer(&mut self, peer_id: &PeerId, condition: Option<DialPeerCondition>)
+        -> Result<bool, DialError>
+    {
         if self.banned_peers.contains(peer_id) {
             self.behaviour.inject_dial_failure(peer_id);
             return Err(DialError::Banned)
         }
 
+        if self.is_unroutable(peer_id) {
+            self.behaviour.inject_dial_failure(peer_id);
+            return Err(DialError::Unroutable)
+        }
+
         let self_listening = &self.listened_addrs;
         let mut addrs = self.behaviour.addresses_of_peer(peer_id)
             .into_iter()
@@ -381,13 +547,28 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
 
         let result =
             if let Some(first) = addrs.next() {
+                if !self.is_dial_permitted(&first) {
+                    log::debug!("Dial concurrency limit reached; queueing dial to {:?}.", peer_id);
+                    let dial = QueuedDial::Peer(*peer_id, condition);
+                    if !self.queued_dials.contains(&dial) {
+                        self.queued_dials.push_back(dial);
+                    }
+                    return Ok(false)
+                }
                 let handler = self.behaviour.new_handler()
                     .into_node_handler_builder()
-                    .with_substream_upgrade_protocol_override(self.substream_upgrade_protocol_override);
-                self.network.peer(*peer_id)
-                    .dial(first, addrs, handler)
-                    .map(|_| ())
-                    .map_err(DialError::from)
+                    .with_substream_upgrade_protocol_override(self.substream_upgrade_protocol_override)
+                    .with_idle_connection_timeout(self.idle_connection_timeout)
+                    .with_keep_alive_overrides(self.keep_alive_overrides.clone())
+                    .with_keep_alive_change_reports(self.report_keep_alive_changes);
+                let address = first.clone();
+                match self.network.peer(*peer_id).dial(first, addrs, handler) {
+                    Ok((id, _)) => {
+                        self.connection_spans.dialing(id, Some(peer_id), &address);
+                        Ok(true)
+                    }
+                    Err(error) => Err(DialError::from(error)),
+                }
             } else {
                 Err(DialError::NoAddresses)
             };
@@ -480,7 +661,87 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
         self.banned_peers.remove(&peer_id);
     }
 
-    /// Disconnects a peer by its peer ID, closing all connections to said peer.
+    /// Overrides how long the connections to a peer are kept alive once
+    /// their handler no longer needs them, in place of the configured
+    /// [`SwarmBuilder::idle_connection_timeout`].
+    ///
+    /// E.g. [`KeepAlive::Yes`] keeps idle connections to the peer open
+    /// indefinitely. The override applies to existing and future connections.
+    pub fn set_peer_keep_alive(&mut self, peer_id: PeerId, keep_alive: KeepAlive) {
+        self.keep_alive_overrides.set(peer_id, Some(keep_alive));
+    }
+
+    /// Removes the keep-alive override of a peer set via
+    /// [`ExpandedSwarm::set_peer_keep_alive`].
+    pub fn remove_peer_keep_alive(&mut self, peer_id: &PeerId) {
+        self.keep_alive_overrides.set(*peer_id, None);
+    }
+
+    /// Protects a peer from being trimmed by a connection manager, tagging
+    /// the protection with the given `tag`.
+    ///
+    /// A peer stays protected as long as it has at least one tag, so that
+    /// independent components can protect the same peer. The protection is
+    /// advisory: the `Swarm` itself never trims connections, but components
+    /// doing so are expected to skip peers for which
+    /// [`ExpandedSwarm::is_protected`] returns `true`.
+    pub fn protect_peer(&mut self, peer_id: PeerId, tag: &str) {
+        self.protected_peers.entry(peer_id).or_default().insert(tag.to_owned());
+    }
+
+    /// Removes a protection tag of a peer added via
+    /// [`ExpandedSwarm::protect_peer`].
+    ///
+    /// Returns whether the peer is still protected by other tags.
+    pub fn unprotect_peer(&mut self, peer_id: &PeerId, tag: &str) -> bool {
+        if let Some(tags) = self.protected_peers.get_mut(peer_id) {
+            tags.remove(tag);
+            if !tags.is_empty() {
+                return true
+            }
+            self.protected_peers.remove(peer_id);
+        }
+        false
+    }
+
+    /// Checks whether a peer is protected by at least one tag.
+    pub fn is_protected(&self, peer_id: &PeerId) -> bool {
+        self.protected_peers.contains_key(peer_id)
+    }
+
+    /// Returns the policy because of which dialing the given peer is currently
+    /// refused, if any.
+    pub fn dial_policy(&self, peer_id: &PeerId) -> Option<DialPolicy> {
+        if self.banned_peers.contains(peer_id) {
+            return Some(DialPolicy::Banned)
+        }
+        match self.unroutable_peers.get(peer_id) {
+            Some(until) if *until > Instant::now() => return Some(DialPolicy::Unroutable { until: *until }),
+            _ => {}
+        }
+        match self.dial_retries.get(peer_id) {
+            Some(retry) if retry.backoff.is_some() => Some(DialPolicy::BackingOff { until: retry.due }),
+            _ => None,
+        }
+    }
+
+    /// Checks whether the given peer has been reported as unroutable via
+    /// [`NetworkBehaviourAction::ReportPeerUnroutable`] within the configured
+    /// TTL, forgetting the report once the TTL has elapsed.
+    fn is_unroutable(&mut self, peer_id: &PeerId) -> bool {
+        match self.unroutable_peers.get(peer_id) {
+            Some(until) if *until > Instant::now() => true,
+            Some(_) => {
+                self.unroutable_peers.remove(peer_id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Disconnects a peer by its peer ID, gracefully closing all connections
+    /// to said peer. Unlike [`ExpandedSwarm::ban_peer_id`], the peer may
+    /// connect or be dialed again afterwards.
     ///
     /// Returns `Ok(())` if there was one or more established connections to the peer.
     ///
@@ -491,8 +752,11 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
     /// with [`ProtocolsHandler::connection_keep_alive`] or directly with
     /// [`ProtocolsHandlerEvent::Close`].
     pub fn disconnect_peer_id(&mut self, peer_id: PeerId) -> Result<(), ()> {
-        if let Some(peer) = self.network.peer(peer_id).into_connected() {
-            peer.disconnect();
+        if let Some(mut peer) = self.network.peer(peer_id).into_connected() {
+            let mut connections = peer.connections();
+            while let Some(conn) = connections.next() {
+                conn.start_close();
+            }
             return Ok(());
         }
 
@@ -504,6 +768,24 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
         self.network.is_connected(peer_id)
     }
 
+    /// Returns the [`ConnectionId`]s of the ongoing dialing attempts to a peer.
+    ///
+    /// A connection established by a dialing attempt keeps its
+    /// [`ConnectionId`], as reported by [`SwarmEvent::ConnectionEstablished`],
+    /// so that dials can be correlated with the resulting connections. A
+    /// dialing attempt moving on to the next address of the peer after a
+    /// failure continues with a new [`ConnectionId`].
+    pub fn dialing_attempts(&mut self, peer_id: &PeerId) -> Vec<ConnectionId> {
+        let mut ids = Vec::new();
+        if let Some(mut peer) = self.network.peer(*peer_id).into_dialing() {
+            let mut attempts = peer.attempts();
+            while let Some(attempt) = attempts.next() {
+                ids.push(attempt.id());
+            }
+        }
+        ids
+    }
+
     /// Returns a reference to the provided [`NetworkBehaviour`].
     pub fn behaviour(&self) -> &TBehaviour {
         &self.behaviour
@@ -514,11 +796,259 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
         &mut self.behaviour
     }
 
+    /// Returns a [`SwarmHandle`] through which other tasks can issue commands
+    /// to this `Swarm`.
+    ///
+    /// The commands are processed the next time the `Swarm` is polled.
+    pub fn handle(&self) -> SwarmHandle<TBehaviour> {
+        SwarmHandle::new(self.command_sender.clone())
+    }
+
+    /// Checks whether the dial concurrency limits permit a new dialing
+    /// attempt to the given address.
+    fn is_dial_permitted(&self, addr: &Multiaddr) -> bool {
+        if self.dial_limits.is_unlimited() {
+            return true
+        }
+        let pending = self.network.outgoing_info()
+            .map(|info| info.address)
+            .collect::<SmallVec<[_; 16]>>();
+        self.dial_limits.permits(addr, &pending)
+    }
+
+    /// Checks whether a new dialing attempt to the given peer is to be
+    /// initiated under the given [`DialPeerCondition`].
+    fn is_dial_condition_met(&self, peer_id: &PeerId, condition: DialPeerCondition) -> bool {
+        match condition {
+            DialPeerCondition::Disconnected => self.network.is_disconnected(peer_id),
+            DialPeerCondition::NotDialing => !self.network.is_dialing(peer_id),
+            DialPeerCondition::Always => true,
+        }
+    }
+
+    /// Schedules another dialing attempt to a peer whose addresses all failed,
+    /// as permitted by the configured [`RetryPolicy`].
+    ///
+    /// Returns `false` if no retry is scheduled, in which case the retry
+    /// state of the peer is cleared.
+    fn schedule_dial_retry(&mut self, peer_id: PeerId) -> bool {
+        let policy = match self.dial_retry_policy {
+            Some(policy) => policy,
+            None => return false,
+        };
+        let retry = self.dial_retries.entry(peer_id).or_insert(DialRetry {
+            retries: 0,
+            backoff: None,
+            due: Instant::now(),
+        });
+        if retry.retries >= policy.max_retries {
+            log::debug!("Giving up dialing {:?} after {} retries.", peer_id, retry.retries);
+            self.dial_retries.remove(&peer_id);
+            return false
+        }
+        retry.retries += 1;
+        retry.backoff = Some(Delay::new(policy.backoff));
+        retry.due = Instant::now() + policy.backoff;
+        true
+    }
+
+    /// Retries the queued dials, in order. Those still exceeding the dial
+    /// concurrency limits are queued again. Those requested by the behaviour
+    /// whose [`DialPeerCondition`] is no longer met are dropped and reported
+    /// through [`NetworkBehaviour::inject_dial_failure`].
+    ///
+    /// Returns the peer of a dial requested by the behaviour as soon as it has
+    /// been initiated, leaving the remaining dials for the next call.
+    fn dial_queued(&mut self) -> Option<PeerId> {
+        let len = self.queued_dials.len();
+        for i in 0 .. len {
+            match self.queued_dials.pop_front() {
+                Some(QueuedDial::Address(addr)) => {
+                    if let Err(error) = self.dial_addr(addr) {
+                        log::debug!("Queued dialing attempt failed: {:?}.", error);
+                    }
+                }
+                Some(QueuedDial::Peer(peer_id, None)) => {
+                    let _ = self.dial_peer(&peer_id, None);
+                }
+                Some(QueuedDial::Peer(peer_id, Some(condition))) => {
+                    if !self.is_dial_condition_met(&peer_id, condition) {
+                        log::trace!("Condition for queued dialing attempt to {:?} no longer met: {:?}",
+                            peer_id, condition);
+                        self.behaviour.inject_dial_failure(&peer_id);
+                        continue
+                    }
+                    if let Ok(true) = self.dial_peer(&peer_id, Some(condition)) {
+                        // Keep the dials queued again ahead of those not yet retried.
+                        self.queued_dials.rotate_left(len - i - 1);
+                        return Some(peer_id)
+                    }
+                }
+                None => break,
+            }
+        }
+        None
+    }
+
+    /// Closes one of the connections to a peer if the connection that has just
+    /// been established is in the opposite direction of an existing one, as
+    /// mandated by the [`SimultaneousOpenPolicy`].
+    fn resolve_simultaneous_open(&mut self, peer_id: PeerId, new: ConnectionId) {
+        let policy = self.simultaneous_open_policy;
+        if policy == SimultaneousOpenPolicy::KeepBoth {
+            return
+        }
+        let local_peer_id = *self.network.local_peer_id();
+        let mut peer = match self.network.peer(peer_id).into_connected() {
+            Some(peer) => peer,
+            None => return,
+        };
+        let new_is_dialer = match peer.connection(new) {
+            Some(conn) => conn.endpoint().is_dialer(),
+            None => return,
+        };
+        let mut existing = None;
+        let mut connections = peer.connections();
+        while let Some(conn) = connections.next() {
+            if conn.id() != new && conn.endpoint().is_dialer() != new_is_dialer {
+                existing = Some(conn.id());
+                break
+            }
+        }
+        let existing = match existing {
+            Some(existing) => existing,
+            None => return,
+        };
+        let close = match policy {
+            SimultaneousOpenPolicy::KeepBoth => return,
+            SimultaneousOpenPolicy::KeepOldest => {
+                // Leave closing the newer connection to the peer with the higher `PeerId`.
+                if local_peer_id < peer_id { return } else { new }
+            }
+            SimultaneousOpenPolicy::KeepByPeerIdOrder => {
+                // Keep the connection dialed by the peer with the lower `PeerId`.
+                if new_is_dialer == (local_peer_id < peer_id) { existing } else { new }
+            }
+        };
+        log::debug!("Closing connection {:?} to {:?} opened simultaneously with another.",
+            close, peer_id);
+        if let Some(conn) = peer.connection(close) {
+            conn.start_close();
+        }
+    }
+
+    /// Checks whether the number of connected peers crossed one of the
+    /// connectivity watermarks, returning it together with the new
+    /// connectivity if so.
+    fn update_connectivity(&mut self) -> Option<(usize, Connectivity)> {
+        let (low, high) = self.connectivity_watermarks?;
+        let connected_peers = self.network.info().num_peers();
+        let state = match self.connectivity {
+            Connectivity::Healthy if connected_peers < low => Connectivity::Degraded,
+            Connectivity::Degraded if connected_peers >= high => Connectivity::Healthy,
+            _ => return None,
+        };
+        self.connectivity = state;
+        Some((connected_peers, state))
+    }
+
+    /// Initiates a new dialing attempt to every peer whose dial retry backoff
+    /// has elapsed, unless the peer meanwhile got connected or is being dialed.
+    fn dial_retries_due(&mut self, cx: &mut Context<'_>) {
+        if self.dial_retries.is_empty() {
+            return
+        }
+        let due = self.dial_retries.iter_mut()
+            .filter_map(|(peer_id, retry)| match retry.backoff.as_mut().map(|d| d.poll_unpin(cx)) {
+                Some(Poll::Ready(_)) => {
+                    retry.backoff = None;
+                    Some(*peer_id)
+                }
+                _ => None,
+            })
+            .collect::<SmallVec<[_; 8]>>();
+        for peer_id in due {
+            if !self.network.is_disconnected(&peer_id) {
+                self.dial_retries.remove(&peer_id);
+                continue
+            }
+            log::debug!("Retrying to dial {:?}.", peer_id);
+            if self.dial(&peer_id).is_err() {
+                self.dial_retries.remove(&peer_id);
+            }
+        }
+    }
+
+    /// Processes a command issued through a [`SwarmHandle`].
+    fn handle_command(&mut self, command: SwarmCommand<TBehaviour>) {
+        match command {
+            SwarmCommand::Dial(peer_id) => {
+                let _ = self.dial(&peer_id);
+            }
+            SwarmCommand::DialAddr(addr) => {
+                if let Err(error) = self.dial_addr(addr) {
+                    log::debug!("New dialing attempt via handle failed: {:?}.", error);
+                }
+            }
+            SwarmCommand::BanPeerId(peer_id) => self.ban_peer_id(peer_id),
+            SwarmCommand::UnbanPeerId(peer_id) => self.unban_peer_id(peer_id),
+            SwarmCommand::DisconnectPeerId(peer_id) => {
+                let _ = self.disconnect_peer_id(peer_id);
+            }
+            SwarmCommand::Behaviour(f) => f(&mut self.behaviour),
+        }
+    }
+
     /// Internal function used by everything event-related.
     ///
-    /// Polls the `Swarm` for the next event.
+    /// Polls the `Swarm` for the next event, first filling the event buffer
+    /// if one is configured.
     fn poll_next_event(mut self: Pin<&mut Self>, cx: &mut Context<'_>)
         -> Poll<SwarmEvent<TBehaviour::OutEvent, THandleErr>>
+    {
+        let (capacity, overflow) = match self.event_buffer_config {
+            Some(config) => config,
+            None => return self.poll_network_and_behaviour(cx),
+        };
+
+        // Let the network and the behaviour make progress for as long as they
+        // have events to report, but at most `capacity` times, so that a
+        // `Swarm` that is always ready cannot starve the consumer.
+        for _ in 0..capacity.get() {
+            let is_full = self.event_buffer.len() >= capacity.get();
+            if is_full && overflow == EventBufferOverflow::Backpressure {
+                break
+            }
+            let event = match self.as_mut().poll_network_and_behaviour(cx) {
+                Poll::Ready(event) => event,
+                Poll::Pending => break,
+            };
+            if is_full {
+                match overflow {
+                    EventBufferOverflow::DropOldest => {
+                        log::warn!("Swarm event buffer full, dropping the oldest event.");
+                        self.event_buffer.pop_front();
+                    }
+                    EventBufferOverflow::DropNewest => {
+                        log::warn!("Swarm event buffer full, dropping the newest event.");
+                        continue
+                    }
+                    EventBufferOverflow::Backpressure => unreachable!("checked above; qed"),
+                }
+            }
+            self.event_buffer.push_back(event);
+        }
+
+        match self.event_buffer.pop_front() {
+            Some(event) => Poll::Ready(event),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Polls the network and the behaviour until either of them produces
+    /// an event for the `Swarm`.
+    fn poll_network_and_behaviour(mut self: Pin<&mut Self>, cx: &mut Context<'_>)
+        -> Poll<SwarmEvent<TBehaviour::OutEvent, THandleErr>>
     {
         // We use a `this` variable because the compiler can't mutably borrow multiple times
         // across a `Deref`.
@@ -527,22 +1057,56 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
         loop {
             let mut network_not_ready = false;
 
-            // First let the network make progress.
+            // Process the commands issued through `SwarmHandle`s. The stream never
+            // terminates, since the `Swarm` itself holds a sender.
+            while let Poll::Ready(Some(command)) = this.command_receiver.poll_next_unpin(cx) {
+                this.handle_command(command);
+            }
+
+            // Report connectivity changes due to connections established or closed before.
+            if let Some((connected_peers, state)) = this.update_connectivity() {
+                return Poll::Ready(SwarmEvent::ConnectivityChanged { connected_peers, state })
+            }
+
+            // Initiate the queued dials that are now within the dial concurrency limits.
+            if let Some(peer_id) = this.dial_queued() {
+                return Poll::Ready(SwarmEvent::Dialing(peer_id))
+            }
+
+            // Retry the failed dials whose backoff has elapsed.
+            this.dial_retries_due(cx);
+
+            // Let the network make progress.
             match this.network.poll(cx) {
                 Poll::Pending => network_not_ready = true,
                 Poll::Ready(NetworkEvent::ConnectionEvent { connection, event }) => {
-                    let peer = connection.peer_id();
+                    let peer_id = connection.peer_id();
                     let connection = connection.id();
-                    this.behaviour.inject_event(peer, connection, event);
+                    let _span = this.connection_spans.enter(&connection);
+                    match event {
+                        NodeHandlerWrapperEvent::Custom(event) => {
+                            this.behaviour.inject_event(peer_id, connection, event);
+                        }
+                        NodeHandlerWrapperEvent::KeepAliveChanged(keep_alive) => {
+                            return Poll::Ready(SwarmEvent::ConnectionKeepAliveChanged {
+                                peer_id,
+                                connection,
+                                keep_alive,
+                            })
+                        }
+                    }
                 },
                 Poll::Ready(NetworkEvent::AddressChange { connection, new_endpoint, old_endpoint }) => {
                     let peer = connection.peer_id();
                     let connection = connection.id();
+                    let _span = this.connection_spans.enter(&connection);
                     this.behaviour.inject_address_change(&peer, &connection, &old_endpoint, &new_endpoint);
                 },
                 Poll::Ready(NetworkEvent::ConnectionEstablished { connection, num_established }) => {
                     let peer_id = connection.peer_id();
                     let endpoint = connection.endpoint().clone();
+                    this.connection_spans.established(connection.id(), &peer_id, &endpoint);
+                    let _span = this.connection_spans.enter(&connection.id());
                     if this.banned_peers.contains(&peer_id) {
                         this.network.peer(peer_id)
                             .into_connected()
@@ -553,23 +1117,29 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                             endpoint,
                         });
                     } else {
-                        log::debug!("Connection established: {:?}; Total (peer): {}.",
-                            connection.connected(), num_established);
+                        log::debug!("Connection {:?} established: {:?}; Total (peer): {}.",
+                            connection.id(), connection.connected(), num_established);
+                        this.dial_retries.remove(&peer_id);
                         let endpoint = connection.endpoint().clone();
-                        this.behaviour.inject_connection_established(&peer_id, &connection.id(), &endpoint);
+                        let connection = connection.id();
+                        this.behaviour.inject_connection_established(&peer_id, &connection, &endpoint);
                         if num_established.get() == 1 {
                             this.behaviour.inject_connected(&peer_id);
+                        } else {
+                            this.resolve_simultaneous_open(peer_id, connection);
                         }
                         return Poll::Ready(SwarmEvent::ConnectionEstablished {
-                            peer_id, num_established, endpoint
+                            peer_id, connection, num_established, endpoint
                         });
                     }
                 },
                 Poll::Ready(NetworkEvent::ConnectionClosed { id, connected, error, num_established }) => {
+                    let _span = this.connection_spans.enter(&id);
+                    this.connection_spans.closed(&id);
                     if let Some(error) = error.as_ref() {
-                        log::debug!("Connection {:?} closed: {:?}", connected, error);
+                        log::debug!("Connection {:?} closed: {:?}; {:?}", id, connected, error);
                     } else {
-                        log::debug!("Connection {:?} closed (active close).", connected);
+                        log::debug!("Connection {:?} closed (active close): {:?}", id, connected);
                     }
                     let peer_id = connected.peer_id;
                     let endpoint = connected.endpoint;
@@ -579,6 +1149,7 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                     }
                     return Poll::Ready(SwarmEvent::ConnectionClosed {
                         peer_id,
+                        connection: id,
                         endpoint,
                         cause: error,
                         num_established,
@@ -587,11 +1158,22 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                 Poll::Ready(NetworkEvent::IncomingConnection { connection, .. }) => {
                     let handler = this.behaviour.new_handler()
                         .into_node_handler_builder()
-                        .with_substream_upgrade_protocol_override(this.substream_upgrade_protocol_override);
+                        .with_substream_upgrade_protocol_override(this.substream_upgrade_protocol_override)
+                        .with_idle_connection_timeout(this.idle_connection_timeout)
+                        .with_keep_alive_overrides(this.keep_alive_overrides.clone())
+                        .with_keep_alive_change_reports(this.report_keep_alive_changes);
                     let local_addr = connection.local_addr.clone();
                     let send_back_addr = connection.send_back_addr.clone();
-                    if let Err(e) = this.network.accept(connection, handler) {
-                        log::warn!("Incoming connection rejected: {:?}", e);
+                    match this.network.accept(connection, handler) {
+                        Ok(id) => this.connection_spans.upgrading(id, &local_addr, &send_back_addr),
+                        Err(limit) => {
+                            log::debug!("Incoming connection rejected: {:?}", limit);
+                            return Poll::Ready(SwarmEvent::IncomingConnectionError {
+                                local_addr,
+                                send_back_addr,
+                                error: PendingConnectionError::ConnectionLimit(limit),
+                            });
+                        }
                     }
                     return Poll::Ready(SwarmEvent::IncomingConnection {
                         local_addr,
@@ -642,6 +1224,10 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                 },
                 Poll::Ready(NetworkEvent::IncomingConnectionError { local_addr, send_back_addr, error }) => {
                     log::debug!("Incoming connection failed: {:?}", error);
+                    this.connection_spans.failed(&ConnectedPoint::Listener {
+                        local_addr: local_addr.clone(),
+                        send_back_addr: send_back_addr.clone(),
+                    }, None);
                     return Poll::Ready(SwarmEvent::IncomingConnectionError {
                         local_addr,
                         send_back_addr,
@@ -652,8 +1238,10 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                     log::debug!(
                         "Connection attempt to {:?} via {:?} failed with {:?}. Attempts remaining: {}.",
                         peer_id, multiaddr, error, attempts_remaining);
+                    this.connection_spans.failed(
+                        &ConnectedPoint::Dialer { address: multiaddr.clone() }, Some(&peer_id));
                     this.behaviour.inject_addr_reach_failure(Some(&peer_id), &multiaddr, &error);
-                    if attempts_remaining == 0 {
+                    if attempts_remaining == 0 && !this.schedule_dial_retry(peer_id) {
                         this.behaviour.inject_dial_failure(&peer_id);
                     }
                     return Poll::Ready(SwarmEvent::UnreachableAddr {
@@ -666,6 +1254,7 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                 Poll::Ready(NetworkEvent::UnknownPeerDialError { multiaddr, error, .. }) => {
                     log::debug!("Connection attempt to address {:?} of unknown peer failed with {:?}",
                         multiaddr, error);
+                    this.connection_spans.failed(&ConnectedPoint::Dialer { address: multiaddr.clone() }, None);
                     this.behaviour.inject_addr_reach_failure(None, &multiaddr, &error);
                     return Poll::Ready(SwarmEvent::UnknownPeerUnreachableAddr {
                         address: multiaddr,
@@ -678,7 +1267,10 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
             // the pending event emitted by the behaviour in the previous iteration
             // to the connection handler(s). The pending event must be delivered
             // before polling the behaviour again. If the targeted peer
-            // meanwhie disconnected, the event is discarded.
+            // meanwhie disconnected, the event is discarded. The same happens
+            // if the event could not be delivered within the configured
+            // `pending_event_timeout`, so that a single stuck connection does
+            // not stall the behaviour.
             if let Some((peer_id, handler, event)) = this.pending_event.take() {
                 if let Some(mut peer) = this.network.peer(peer_id).into_connected() {
                     match handler {
@@ -686,22 +1278,45 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                             if let Some(mut conn) = peer.connection(conn_id) {
                                 if let Some(event) = notify_one(&mut conn, event, cx) {
                                     this.pending_event = Some((peer_id, handler, event));
-                                    return Poll::Pending
                                 }
                             },
                         PendingNotifyHandler::Any(ids) => {
                             if let Some((event, ids)) = notify_any(ids, &mut peer, event, cx) {
                                 let handler = PendingNotifyHandler::Any(ids);
                                 this.pending_event = Some((peer_id, handler, event));
-                                return Poll::Pending
                             }
                         }
                     }
                 }
             }
 
+            if let Some((peer_id, ..)) = &this.pending_event {
+                match this.pending_event_delay.as_mut().map(|delay| delay.poll_unpin(cx)) {
+                    Some(Poll::Ready(_)) => {
+                        log::warn!(
+                            "Dropping event for peer {:?}: connection handler not ready in time.",
+                            peer_id);
+                        this.pending_event = None;
+                    }
+                    _ => return Poll::Pending,
+                }
+            }
+            this.pending_event_delay = None;
+
             debug_assert!(this.pending_event.is_none());
 
+            // Apply backpressure to the behaviour while too many of the events
+            // it sent to connection handlers have yet to be processed.
+            if let (Some(max), Some(in_flight)) = (this.max_in_flight_events, &this.in_flight_events) {
+                in_flight.register(cx);
+                if in_flight.count() >= max.get() {
+                    if network_not_ready {
+                        return Poll::Pending
+                    }
+                    continue
+                }
+            }
+
             let behaviour_poll = {
                 let mut parameters = SwarmPollParameters {
                     local_peer_id: &mut this.network.local_peer_id(),
@@ -722,16 +1337,14 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                     let _ = ExpandedSwarm::dial_addr(&mut *this, address);
                 },
                 Poll::Ready(NetworkBehaviourAction::DialPeer { peer_id, condition }) => {
-                    if this.banned_peers.contains(&peer_id) {
+                    if let Some(policy) = this.dial_policy(&peer_id) {
+                        log::debug!("Dialing {:?} refused: {:?}.", peer_id, policy);
                         this.behaviour.inject_dial_failure(&peer_id);
+                        return Poll::Ready(SwarmEvent::DialRefused { peer_id, policy })
                     } else {
-                        let condition_matched = match condition {
-                            DialPeerCondition::Disconnected => this.network.is_disconnected(&peer_id),
-                            DialPeerCondition::NotDialing => !this.network.is_dialing(&peer_id),
-                            DialPeerCondition::Always => true,
-                        };
-                        if condition_matched {
-                            if ExpandedSwarm::dial(this, &peer_id).is_ok() {
+                        if this.is_dial_condition_met(&peer_id, condition) {
+                            // A queued dial is reported once it is initiated.
+                            if let Ok(true) = ExpandedSwarm::dial_peer(this, &peer_id, Some(condition)) {
                                 return Poll::Ready(SwarmEvent::Dialing(peer_id))
                             }
                         } else {
@@ -754,6 +1367,7 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                     }
                 },
                 Poll::Ready(NetworkBehaviourAction::NotifyHandler { peer_id, handler, event }) => {
+                    let event = NodeHandlerWrapperInEvent::new(event, this.in_flight_events.as_ref());
                     if let Some(mut peer) = this.network.peer(peer_id).into_connected() {
                         match handler {
                             NotifyHandler::One(connection) => {
@@ -761,7 +1375,8 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                                     if let Some(event) = notify_one(&mut conn, event, cx) {
                                         let handler = PendingNotifyHandler::One(connection);
                                         this.pending_event = Some((peer_id, handler, event));
-                                        return Poll::Pending
+                                        this.pending_event_delay = this.pending_event_timeout.map(Delay::new);
+                                        continue
                                     }
                                 }
                             }
@@ -770,7 +1385,8 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                                 if let Some((event, ids)) = notify_any(ids, &mut peer, event, cx) {
                                     let handler = PendingNotifyHandler::Any(ids);
                                     this.pending_event = Some((peer_id, handler, event));
-                                    return Poll::Pending
+                                    this.pending_event_delay = this.pending_event_timeout.map(Delay::new);
+                                    continue
                                 }
                             }
                         }
@@ -795,6 +1411,16 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                         }
                     }
                 },
+                Poll::Ready(NetworkBehaviourAction::ReportPeerUnroutable(peer_id)) => {
+                    log::debug!("Peer {:?} reported as unroutable.", peer_id);
+                    let until = Instant::now() + this.unroutable_peer_ttl;
+                    this.unroutable_peers.insert(peer_id, until);
+                },
+                Poll::Ready(NetworkBehaviourAction::ListenOn(address)) => {
+                    if let Err(err) = ExpandedSwarm::listen_on(&mut *this, address.clone()) {
+                        log::debug!("Listening on {} requested by behaviour failed: {:?}.", address, err);
+                    }
+                },
             }
         }
     }
@@ -811,6 +1437,76 @@ enum PendingNotifyHandler {
     Any(SmallVec<[ConnectionId; 10]>),
 }
 
+/// Limits on the number of concurrent outgoing connection attempts.
+///
+/// See [`SwarmBuilder::dial_concurrency_limit`] and
+/// [`SwarmBuilder::transport_dial_concurrency_limit`].
+#[derive(Default)]
+struct DialLimits {
+    /// The limit across all transports, if any.
+    total: Option<NonZeroUsize>,
+    /// The limits for the addresses matching the associated predicate.
+    per_transport: Vec<(fn(&Multiaddr) -> bool, NonZeroUsize)>,
+}
+
+impl DialLimits {
+    fn is_unlimited(&self) -> bool {
+        self.total.is_none() && self.per_transport.is_empty()
+    }
+
+    /// Checks whether a new dialing attempt to `addr` is permitted while
+    /// connection attempts to the `pending` addresses are ongoing.
+    fn permits(&self, addr: &Multiaddr, pending: &[&Multiaddr]) -> bool {
+        if let Some(total) = self.total {
+            if pending.len() >= total.get() {
+                return false
+            }
+        }
+        self.per_transport.iter()
+            .filter(|(is_transport, _)| is_transport(addr))
+            .all(|(is_transport, limit)| {
+                pending.iter().filter(|a| is_transport(a)).count() < limit.get()
+            })
+    }
+}
+
+/// The policy for retrying dials to a peer once all its addresses failed.
+///
+/// See [`SwarmBuilder::dial_retry_policy`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a policy retrying a failed dial up to `max_retries` times,
+    /// each after waiting for `backoff`.
+    pub fn new(max_retries: u32, backoff: Duration) -> Self {
+        RetryPolicy { max_retries, backoff }
+    }
+}
+
+/// The retry state of a peer whose dial failed.
+struct DialRetry {
+    /// The number of retries so far.
+    retries: u32,
+    /// Fires once the next retry is due, if one is scheduled and has not
+    /// yet been initiated.
+    backoff: Option<Delay>,
+    /// When the last scheduled retry is due.
+    due: Instant,
+}
+
+/// A dialing attempt postponed due to the [`DialLimits`].
+#[derive(Debug, PartialEq, Eq)]
+enum QueuedDial {
+    Address(Multiaddr),
+    /// A dial to a peer, with the [`DialPeerCondition`] if it was requested
+    /// by the behaviour.
+    Peer(PeerId, Option<DialPeerCondition>),
+}
+
 /// Notify a single connection of an event.
 ///
 /// Returns `Some` with the given event if the connection is not currently
@@ -962,6 +1658,19 @@ pub struct SwarmBuilder<TBehaviour> {
     behaviour: TBehaviour,
     network_config: NetworkConfig,
     substream_upgrade_protocol_override: Option<libp2p_core::upgrade::Version>,
+    idle_connection_timeout: Duration,
+    report_keep_alive_changes: bool,
+    unroutable_peer_ttl: Duration,
+    pending_event_timeout: Option<Duration>,
+    max_in_flight_events: Option<NonZeroUsize>,
+    event_buffer_config: Option<(NonZeroUsize, EventBufferOverflow)>,
+    dial_limits: DialLimits,
+    dial_retry_policy: Option<RetryPolicy>,
+    connectivity_watermarks: Option<(usize, usize)>,
+    simultaneous_open_policy: SimultaneousOpenPolicy,
+    thread_pool_fallback: bool,
+    executor_configured: bool,
+    require_executor: bool,
 }
 
 impl<TBehaviour> SwarmBuilder<TBehaviour>
@@ -981,6 +1690,19 @@ where TBehaviour: NetworkBehaviour,
             behaviour,
             network_config: Default::default(),
             substream_upgrade_protocol_override: None,
+            idle_connection_timeout: Duration::from_secs(0),
+            report_keep_alive_changes: false,
+            unroutable_peer_ttl: Duration::from_secs(60),
+            pending_event_timeout: None,
+            max_in_flight_events: None,
+            event_buffer_config: None,
+            dial_limits: DialLimits::default(),
+            dial_retry_policy: None,
+            connectivity_watermarks: None,
+            simultaneous_open_policy: SimultaneousOpenPolicy::KeepBoth,
+            thread_pool_fallback: true,
+            executor_configured: false,
+            require_executor: false,
         }
     }
 
@@ -990,6 +1712,36 @@ where TBehaviour: NetworkBehaviour,
     /// [`SwarmBuilder::build`] will try to set up a `ThreadPool`.
     pub fn executor(mut self, e: Box<dyn Executor + Send>) -> Self {
         self.network_config = self.network_config.with_executor(e);
+        self.executor_configured = true;
+        self
+    }
+
+    /// Requires an executor to be configured via [`SwarmBuilder::executor`],
+    /// so that the `Swarm` never sets up a `ThreadPool` of its own.
+    ///
+    /// Building the `Swarm` without an executor then fails with
+    /// [`BuildError::MissingExecutor`].
+    pub fn require_executor(mut self) -> Self {
+        self.require_executor = true;
+        self
+    }
+
+    /// Prevents [`SwarmBuilder::build`] from setting up a `ThreadPool` if no
+    /// executor has been configured.
+    ///
+    /// Without an executor, the background tasks of the connections are
+    /// polled on the task polling the `Swarm`, e.g. on a single-threaded
+    /// runtime or on wasm. No task is ever moved to another thread then.
+    ///
+    /// The tasks are still `Send`, and so are the transport, the stream
+    /// muxer and the [`ProtocolsHandler`]s they own, as required by
+    /// [`transport::Boxed`], [`StreamMuxerBox`] and [`ProtocolsHandler`].
+    /// This bound cannot be lifted for this mode alone: the tasks are stored
+    /// within the `Swarm`, which would no longer be `Send` either. A
+    /// single-threaded executor configured via [`SwarmBuilder::executor`]
+    /// can run them, as any `Send` future can be run on the current thread.
+    pub fn without_executor(mut self) -> Self {
+        self.thread_pool_fallback = false;
         self
     }
 
@@ -1056,54 +1808,331 @@ where TBehaviour: NetworkBehaviour,
         self
     }
 
-    /// Builds a `Swarm` with the current configuration.
-    pub fn build(mut self) -> Swarm<TBehaviour> {
-        let supported_protocols = self.behaviour
-            .new_handler()
-            .inbound_protocol()
-            .protocol_info()
-            .into_iter()
-            .map(|info| info.protocol_name().to_vec())
-            .collect();
+    /// Configures how long a connection is kept alive once all of its
+    /// [`ProtocolsHandler`]s report [`KeepAlive::No`].
+    ///
+    /// The timeout only applies to handlers that defer the decision to
+    /// the `Swarm` by returning [`KeepAlive::No`]. Handlers returning
+    /// [`KeepAlive::Yes`] or [`KeepAlive::Until`] are unaffected. Any activity
+    /// that makes a handler keep the connection alive again cancels the
+    /// timeout, which is restarted once the connection becomes idle anew.
+    ///
+    /// Defaults to zero, i.e. idle connections are closed immediately.
+    pub fn idle_connection_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_connection_timeout = timeout;
+        self
+    }
 
-        // If no executor has been explicitly configured, try to set up a thread pool.
-        let network_cfg = self.network_config.or_else_with_executor(|| {
-            match ThreadPoolBuilder::new()
-                .name_prefix("libp2p-swarm-task-")
-                .create()
-            {
-                Ok(tp) => {
-                    Some(Box::new(move |f| tp.spawn_ok(f)))
-                },
-                Err(err) => {
-                    log::warn!("Failed to create executor thread pool: {:?}", err);
-                    None
-                }
-            }
-        });
+    /// Configures whether changes of the [`KeepAlive`] returned by the
+    /// [`ProtocolsHandler`] of a connection are reported through
+    /// [`SwarmEvent::ConnectionKeepAliveChanged`].
+    ///
+    /// Disabled by default.
+    pub fn report_keep_alive_changes(mut self, enabled: bool) -> Self {
+        self.report_keep_alive_changes = enabled;
+        self
+    }
 
-        let network = Network::new(self.transport, self.local_peer_id, network_cfg);
+    /// Configures for how long the `Swarm` refuses to dial a peer after a
+    /// [`NetworkBehaviour`] reported it via
+    /// [`NetworkBehaviourAction::ReportPeerUnroutable`].
+    ///
+    /// Defaults to 60 seconds.
+    pub fn unroutable_peer_ttl(mut self, ttl: Duration) -> Self {
+        self.unroutable_peer_ttl = ttl;
+        self
+    }
 
-        ExpandedSwarm {
-            network,
-            behaviour: self.behaviour,
-            supported_protocols,
-            listened_addrs: SmallVec::new(),
-            external_addrs: Addresses::default(),
-            banned_peers: HashSet::new(),
-            pending_event: None,
-            substream_upgrade_protocol_override: self.substream_upgrade_protocol_override,
-        }
+    /// Configures the maximum duration for which the `Swarm` waits for a
+    /// [`ProtocolsHandler`] to become ready to receive an event emitted by the
+    /// [`NetworkBehaviour`] via [`NetworkBehaviourAction::NotifyHandler`].
+    ///
+    /// While such an event is pending, the `NetworkBehaviour` is not polled.
+    /// Once the timeout elapses, the event is dropped so that a single
+    /// connection that does not make progress cannot stall the behaviour.
+    ///
+    /// By default there is no timeout.
+    pub fn pending_event_timeout(mut self, timeout: Duration) -> Self {
+        self.pending_event_timeout = Some(timeout);
+        self
     }
-}
 
-/// The possible failures of [`ExpandedSwarm::dial`].
-#[derive(Debug)]
-pub enum DialError {
-    /// The peer is currently banned.
-    Banned,
-    /// The configured limit for simultaneous outgoing connections
-    /// has been reached.
+    /// Configures the maximum number of events emitted by the
+    /// [`NetworkBehaviour`] via [`NetworkBehaviourAction::NotifyHandler`]
+    /// that may be in flight across all connections, i.e. sent but not yet
+    /// processed by their [`ProtocolsHandler`].
+    ///
+    /// While the limit is reached, the `NetworkBehaviour` is not polled. This
+    /// bounds the memory used for buffering events across all connections,
+    /// which is otherwise only bounded per connection by
+    /// [`SwarmBuilder::notify_handler_buffer_size`].
+    ///
+    /// By default there is no limit.
+    pub fn max_in_flight_events(mut self, n: NonZeroUsize) -> Self {
+        self.max_in_flight_events = Some(n);
+        self
+    }
+
+    /// Configures the `Swarm` to buffer up to `capacity` [`SwarmEvent`]s that
+    /// have yet to be consumed.
+    ///
+    /// Without a buffer, the `Swarm` stops making progress as soon as it has
+    /// produced an event, until that event is consumed. With a buffer, every
+    /// poll lets the network and the [`NetworkBehaviour`] make progress until
+    /// they have no more events to report or `capacity` events have been
+    /// produced, so that connections are serviced up to `capacity` events
+    /// ahead of a consumer taking one event at a time. The given
+    /// [`EventBufferOverflow`] policy determines what happens once the buffer
+    /// is full.
+    ///
+    /// The buffer is only filled while the `Swarm` is polled. A consumer that
+    /// stops polling the `Swarm` still stalls it, in which case events of
+    /// connections are only buffered as configured via
+    /// [`SwarmBuilder::connection_event_buffer_size`].
+    ///
+    /// By default no events are buffered.
+    pub fn event_buffer(mut self, capacity: NonZeroUsize, overflow: EventBufferOverflow) -> Self {
+        self.event_buffer_config = Some((capacity, overflow));
+        self
+    }
+
+    /// Limits the number of concurrent outgoing connection attempts across
+    /// all transports.
+    ///
+    /// Dials exceeding the limit are queued and initiated in the order they
+    /// were issued once ongoing attempts have finished. Dials to a peer are
+    /// subject to the limit when initiated, further addresses of the peer
+    /// tried by the same dialing attempt are not.
+    ///
+    /// By default the number of concurrent dials is unlimited.
+    pub fn dial_concurrency_limit(mut self, limit: NonZeroUsize) -> Self {
+        self.dial_limits.total = Some(limit);
+        self
+    }
+
+    /// Configures the `Swarm` to retry dialing a peer once all its addresses
+    /// failed, according to the given [`RetryPolicy`].
+    ///
+    /// Every retry queries [`NetworkBehaviour::addresses_of_peer`] anew.
+    /// [`NetworkBehaviour::inject_dial_failure`] is only called once all
+    /// retries failed. The retry state of a peer is reset once a connection
+    /// to it is established.
+    ///
+    /// By default failed dials are not retried.
+    pub fn dial_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.dial_retry_policy = Some(policy);
+        self
+    }
+
+    /// Configures the `Swarm` to report [`SwarmEvent::ConnectivityChanged`]
+    /// once the number of connected peers drops below `low`, becoming
+    /// [`Connectivity::Degraded`], and once it reaches `high` again, becoming
+    /// [`Connectivity::Healthy`].
+    ///
+    /// The `Swarm` starts out [`Connectivity::Degraded`]. By default no such
+    /// events are reported.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low` is greater than `high`.
+    pub fn connectivity_watermarks(mut self, low: usize, high: usize) -> Self {
+        assert!(low <= high, "The low watermark must not exceed the high watermark.");
+        self.connectivity_watermarks = Some((low, high));
+        self
+    }
+
+    /// Configures how the `Swarm` resolves connections to a peer established
+    /// in both directions, e.g. because both peers dialed each other at the
+    /// same time.
+    ///
+    /// Defaults to [`SimultaneousOpenPolicy::KeepBoth`].
+    pub fn simultaneous_open_policy(mut self, policy: SimultaneousOpenPolicy) -> Self {
+        self.simultaneous_open_policy = policy;
+        self
+    }
+
+    /// Limits the number of concurrent outgoing connection attempts to
+    /// addresses for which `is_transport` returns `true`, e.g. to all
+    /// addresses of a particular transport.
+    ///
+    /// Can be configured for any number of transports, in addition to the
+    /// limit across all transports of [`SwarmBuilder::dial_concurrency_limit`].
+    /// Excess dials are queued in the same way.
+    pub fn transport_dial_concurrency_limit(
+        mut self,
+        is_transport: fn(&Multiaddr) -> bool,
+        limit: NonZeroUsize,
+    ) -> Self {
+        self.dial_limits.per_transport.push((is_transport, limit));
+        self
+    }
+
+    /// Builds a `Swarm` with the current configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an executor is required via [`SwarmBuilder::require_executor`]
+    /// but none has been configured. See [`SwarmBuilder::try_build`].
+    pub fn build(self) -> Swarm<TBehaviour> {
+        match self.try_build() {
+            Ok(swarm) => swarm,
+            Err(err) => panic!("Failed to build swarm: {}", err),
+        }
+    }
+
+    /// Builds a `Swarm` with the current configuration, failing if the
+    /// configuration is incomplete.
+    pub fn try_build(mut self) -> Result<Swarm<TBehaviour>, BuildError> {
+        if self.require_executor && !self.executor_configured {
+            return Err(BuildError::MissingExecutor)
+        }
+
+        let supported_protocols = self.behaviour
+            .new_handler()
+            .inbound_protocol()
+            .protocol_info()
+            .into_iter()
+            .map(|info| info.protocol_name().to_vec())
+            .collect();
+
+        // If no executor has been explicitly configured, try to set up a thread pool.
+        let thread_pool_fallback = self.thread_pool_fallback && !self.require_executor;
+        let network_cfg = self.network_config.or_else_with_executor(|| {
+            if !thread_pool_fallback {
+                return None
+            }
+            match ThreadPoolBuilder::new()
+                .name_prefix("libp2p-swarm-task-")
+                .create()
+            {
+                Ok(tp) => {
+                    Some(Box::new(move |f| tp.spawn_ok(f)))
+                },
+                Err(err) => {
+                    log::warn!("Failed to create executor thread pool: {:?}", err);
+                    None
+                }
+            }
+        });
+
+        let network = Network::new(self.transport, self.local_peer_id, network_cfg);
+        let (command_sender, command_receiver) = mpsc::unbounded();
+
+        Ok(ExpandedSwarm {
+            network,
+            behaviour: self.behaviour,
+            supported_protocols,
+            listened_addrs: SmallVec::new(),
+            external_addrs: Addresses::default(),
+            banned_peers: HashSet::new(),
+            unroutable_peers: HashMap::new(),
+            unroutable_peer_ttl: self.unroutable_peer_ttl,
+            pending_event: None,
+            pending_event_timeout: self.pending_event_timeout,
+            pending_event_delay: None,
+            in_flight_events: self.max_in_flight_events.map(|_| InFlightEvents::default()),
+            max_in_flight_events: self.max_in_flight_events,
+            substream_upgrade_protocol_override: self.substream_upgrade_protocol_override,
+            idle_connection_timeout: self.idle_connection_timeout,
+            keep_alive_overrides: KeepAliveOverrides::default(),
+            protected_peers: HashMap::new(),
+            report_keep_alive_changes: self.report_keep_alive_changes,
+            command_sender,
+            command_receiver,
+            event_buffer_config: self.event_buffer_config,
+            event_buffer: VecDeque::new(),
+            dial_limits: self.dial_limits,
+            queued_dials: VecDeque::new(),
+            dial_retry_policy: self.dial_retry_policy,
+            dial_retries: HashMap::new(),
+            connectivity_watermarks: self.connectivity_watermarks,
+            connectivity: Connectivity::Degraded,
+            simultaneous_open_policy: self.simultaneous_open_policy,
+            connection_spans: ConnectionSpans::default(),
+        })
+    }
+}
+
+/// What the `Swarm` does with new events once its event buffer is full.
+///
+/// See [`SwarmBuilder::event_buffer`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EventBufferOverflow {
+    /// Stop polling the network and the [`NetworkBehaviour`] until there is
+    /// room in the buffer again. No events are lost.
+    Backpressure,
+    /// Drop the oldest event in the buffer to make room for the new one.
+    DropOldest,
+    /// Drop the new event, keeping the buffer as is.
+    DropNewest,
+}
+
+/// The connectivity of the `Swarm`, in terms of the watermarks configured via
+/// [`SwarmBuilder::connectivity_watermarks`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Connectivity {
+    /// The number of connected peers reached the high watermark and has not
+    /// dropped below the low watermark since.
+    Healthy,
+    /// The number of connected peers dropped below the low watermark, or has
+    /// not reached the high watermark yet.
+    Degraded,
+}
+
+/// How the `Swarm` resolves connections to a peer established in both
+/// directions.
+///
+/// See [`SwarmBuilder::simultaneous_open_policy`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SimultaneousOpenPolicy {
+    /// Keep all connections.
+    KeepBoth,
+    /// Close a connection established in the opposite direction of an
+    /// existing one.
+    ///
+    /// Since both peers may disagree on which connection is older, only the
+    /// peer with the higher [`PeerId`] closes a connection, so that both
+    /// peers arrive at the same connection.
+    KeepOldest,
+    /// Keep the connection dialed by the peer with the lower [`PeerId`],
+    /// closing the one in the opposite direction.
+    ///
+    /// Both peers arrive at the same connection, given they both apply this
+    /// policy.
+    KeepByPeerIdOrder,
+}
+
+/// The reasons for which the `Swarm` refuses to dial a peer.
+///
+/// See [`ExpandedSwarm::dial_policy`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DialPolicy {
+    /// The peer is banned via [`ExpandedSwarm::ban_peer_id`].
+    Banned,
+    /// The peer has been reported as unroutable via
+    /// [`NetworkBehaviourAction::ReportPeerUnroutable`].
+    Unroutable {
+        /// When the peer may be dialed again.
+        until: Instant,
+    },
+    /// Dialing the peer failed and the `Swarm` retries it as per the
+    /// [`RetryPolicy`] configured via [`SwarmBuilder::dial_retry_policy`].
+    BackingOff {
+        /// When the next dialing attempt is due.
+        until: Instant,
+    },
+}
+
+/// The possible failures of [`ExpandedSwarm::dial`].
+#[derive(Debug)]
+pub enum DialError {
+    /// The peer is currently banned.
+    Banned,
+    /// The peer has recently been reported as unroutable via
+    /// [`NetworkBehaviourAction::ReportPeerUnroutable`].
+    Unroutable,
+    /// The configured limit for simultaneous outgoing connections
+    /// has been reached.
     ConnectionLimit(ConnectionLimit),
     /// The address given for dialing is invalid.
     InvalidAddress(Multiaddr),
@@ -1127,7 +2156,8 @@ impl fmt::Display for DialError {
             DialError::ConnectionLimit(err) => write!(f, "Dial error: {}", err),
             DialError::NoAddresses => write!(f, "Dial error: no addresses for peer."),
             DialError::InvalidAddress(a) => write!(f, "Dial error: invalid address: {}", a),
-            DialError::Banned => write!(f, "Dial error: peer is banned.")
+            DialError::Banned => write!(f, "Dial error: peer is banned."),
+            DialError::Unroutable => write!(f, "Dial error: peer is unroutable.")
         }
     }
 }
@@ -1138,14 +2168,51 @@ impl error::Error for DialError {
             DialError::ConnectionLimit(err) => Some(err),
             DialError::InvalidAddress(_) => None,
             DialError::NoAddresses => None,
-            DialError::Banned => None
+            DialError::Banned => None,
+            DialError::Unroutable => None
+        }
+    }
+}
+
+/// The possible failures of [`SwarmBuilder::try_build`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    /// An executor is required via [`SwarmBuilder::require_executor`] but
+    /// none has been configured.
+    MissingExecutor,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingExecutor => write!(f, "Build error: no executor configured."),
         }
     }
 }
 
+impl error::Error for BuildError {}
+
 /// Dummy implementation of [`NetworkBehaviour`] that doesn't do anything.
-#[derive(Clone, Default)]
+///
+/// The connections of a `DummyBehaviour` are kept alive as configured via
+/// [`DummyBehaviour::with_keep_alive`], by default [`KeepAlive::No`].
+#[derive(Clone)]
 pub struct DummyBehaviour {
+    keep_alive: KeepAlive,
+}
+
+impl DummyBehaviour {
+    /// Creates a `DummyBehaviour` whose connection handlers return the given
+    /// [`KeepAlive`].
+    pub fn with_keep_alive(keep_alive: KeepAlive) -> Self {
+        DummyBehaviour { keep_alive }
+    }
+}
+
+impl Default for DummyBehaviour {
+    fn default() -> Self {
+        DummyBehaviour::with_keep_alive(KeepAlive::No)
+    }
 }
 
 impl NetworkBehaviour for DummyBehaviour {
@@ -1153,7 +2220,7 @@ impl NetworkBehaviour for DummyBehaviour {
     type OutEvent = void::Void;
 
     fn new_handler(&mut self) -> Self::ProtocolsHandler {
-        protocols_handler::DummyProtocolsHandler::default()
+        protocols_handler::DummyProtocolsHandler { keep_alive: self.keep_alive }
     }
 
     fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
@@ -1191,6 +2258,7 @@ mod tests {
         transport
     };
     use libp2p_noise as noise;
+    use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
     use super::*;
 
     // Test execution state.
@@ -1206,6 +2274,21 @@ mod tests {
         T::OutEvent: Clone,
         O: Send + 'static
     {
+        new_test_swarm_builder(handler_proto).build()
+    }
+
+    fn new_test_swarm_builder<T, O>(handler_proto: T) -> SwarmBuilder<CallTraceBehaviour<MockBehaviour<T, O>>>
+    where
+        T: ProtocolsHandler + Clone,
+        T::OutEvent: Clone,
+        O: Send + 'static
+    {
+        let (peer_id, transport) = new_test_transport();
+        let behaviour = CallTraceBehaviour::new(MockBehaviour::new(handler_proto));
+        SwarmBuilder::new(transport, behaviour, peer_id)
+    }
+
+    fn new_test_transport() -> (PeerId, transport::Boxed<(PeerId, StreamMuxerBox)>) {
         let id_keys = identity::Keypair::generate_ed25519();
         let pubkey = id_keys.public();
         let noise_keys = noise::Keypair::<noise::X25519Spec>::new().into_authentic(&id_keys).unwrap();
@@ -1214,8 +2297,7 @@ mod tests {
             .authenticate(noise::NoiseConfig::xx(noise_keys).into_authenticated())
             .multiplex(libp2p_mplex::MplexConfig::new())
             .boxed();
-        let behaviour = CallTraceBehaviour::new(MockBehaviour::new(handler_proto));
-        SwarmBuilder::new(transport, behaviour, pubkey.into()).build()
+        (pubkey.into(), transport)
     }
 
     fn swarms_connected<TBehaviour>(
@@ -1344,7 +2426,8 @@ mod tests {
     ///
     /// The test expects both behaviours to be notified via pairs of
     /// inject_connected / inject_disconnected as well as
-    /// inject_connection_established / inject_connection_closed calls.
+    /// inject_connection_established / inject_connection_closed calls,
+    /// with the disconnecting peer's connections being closed gracefully.
     #[test]
     fn test_swarm_disconnect() {
         // Since the test does not try to open any substreams, we can
@@ -1374,6 +2457,9 @@ mod tests {
             loop {
                 let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
                 let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+                if let Poll::Ready(SwarmEvent::ConnectionClosed { cause, .. }) = &poll2 {
+                    assert!(cause.is_none());
+                }
                 match state {
                     State::Connecting => {
                         if swarms_connected(&swarm1, &swarm2, num_connections) {
@@ -1565,4 +2651,1612 @@ mod tests {
             }
         }))
     }
+
+    /// Establishes a connection between two peers whose handlers do not
+    /// keep the connection alive, with the swarms configured to keep idle
+    /// connections for some time.
+    ///
+    /// The test expects the connection to be closed, but not before the
+    /// configured idle timeout has elapsed.
+    #[test]
+    fn test_idle_connection_timeout() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::No };
+        let idle_timeout = Duration::from_millis(500);
+
+        let mut swarm1 = new_test_swarm_builder::<_, ()>(handler_proto.clone())
+            .idle_connection_timeout(idle_timeout)
+            .build();
+        let mut swarm2 = new_test_swarm_builder::<_, ()>(handler_proto)
+            .idle_connection_timeout(idle_timeout)
+            .build();
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+
+        let start = std::time::Instant::now();
+        swarm1.dial_addr(addr2).unwrap();
+
+        let mut connected = false;
+
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                if !connected {
+                    connected = swarms_connected(&swarm1, &swarm2, 1);
+                } else if [&swarm1, &swarm2].iter().all(|s| {
+                    s.behaviour.inject_connection_closed.len() == 1
+                }) {
+                    assert!(start.elapsed() >= idle_timeout);
+                    return Poll::Ready(())
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }))
+    }
+
+    /// Establishes connections from a swarm whose handlers never keep them
+    /// alive to two other swarms, one of which is configured to be kept
+    /// alive indefinitely and the other only for a short time.
+    ///
+    /// The test expects only the latter connection to be closed, once its
+    /// keep-alive has elapsed.
+    #[test]
+    fn test_peer_keep_alive() {
+        let keep_alive = Duration::from_millis(200);
+
+        let mut swarm1 = new_test_swarm::<_, ()>(DummyProtocolsHandler { keep_alive: KeepAlive::No });
+        let mut swarm2 = new_test_swarm::<_, ()>(DummyProtocolsHandler { keep_alive: KeepAlive::Yes });
+        let mut swarm3 = new_test_swarm::<_, ()>(DummyProtocolsHandler { keep_alive: KeepAlive::Yes });
+
+        let peer2 = *Swarm::local_peer_id(&swarm2);
+        let peer3 = *Swarm::local_peer_id(&swarm3);
+        let start = std::time::Instant::now();
+        swarm1.set_peer_keep_alive(peer2, KeepAlive::Yes);
+        swarm1.set_peer_keep_alive(peer3, KeepAlive::Until(Instant::now() + keep_alive));
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        let addr3: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm3.listen_on(addr3.clone()).unwrap();
+        swarm1.dial_addr(addr2).unwrap();
+        swarm1.dial_addr(addr3).unwrap();
+
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+                let poll3 = Swarm::poll_next_event(Pin::new(&mut swarm3), cx);
+
+                if let Some((peer_id, ..)) = swarm1.behaviour.inject_connection_closed.first() {
+                    assert_eq!(peer_id, &peer3);
+                    assert!(start.elapsed() >= keep_alive);
+                    assert!(swarm1.is_connected(&peer2));
+                    return Poll::Ready(())
+                }
+
+                if poll1.is_pending() && poll2.is_pending() && poll3.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }))
+    }
+
+    /// A [`ProtocolsHandler`] that keeps the connection alive until it has
+    /// been polled twice, then until a deadline recomputed on every poll for
+    /// two more polls.
+    #[derive(Clone, Default)]
+    struct KeepAliveOnceHandler {
+        polls: usize,
+    }
+
+    impl ProtocolsHandler for KeepAliveOnceHandler {
+        type InEvent = void::Void;
+        type OutEvent = void::Void;
+        type Error = void::Void;
+        type InboundProtocol = upgrade::DeniedUpgrade;
+        type OutboundProtocol = upgrade::DeniedUpgrade;
+        type OutboundOpenInfo = void::Void;
+        type InboundOpenInfo = ();
+
+        fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+            SubstreamProtocol::new(upgrade::DeniedUpgrade, ())
+        }
+
+        fn inject_fully_negotiated_inbound(&mut self, _: void::Void, _: ()) {}
+
+        fn inject_fully_negotiated_outbound(&mut self, _: void::Void, _: void::Void) {}
+
+        fn inject_event(&mut self, _: void::Void) {}
+
+        fn inject_dial_upgrade_error(&mut self, _: void::Void, _: ProtocolsHandlerUpgrErr<void::Void>) {}
+
+        fn connection_keep_alive(&self) -> KeepAlive {
+            match self.polls {
+                0 | 1 => KeepAlive::Yes,
+                2 | 3 => KeepAlive::Until(Instant::now() + Duration::from_secs(10)),
+                _ => KeepAlive::No,
+            }
+        }
+
+        fn poll(&mut self, cx: &mut Context<'_>) -> Poll<
+            ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent, Self::Error>
+        > {
+            self.polls += 1;
+            if self.polls < 4 {
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }
+    }
+
+    /// Establishes a connection whose handler on one side stops keeping it
+    /// alive shortly afterwards.
+    ///
+    /// The test expects each change to be reported through a
+    /// [`SwarmEvent::ConnectionKeepAliveChanged`] for that connection, but
+    /// not the recomputed deadlines of [`KeepAlive::Until`].
+    #[test]
+    fn test_connection_keep_alive_changed() {
+        let mut swarm1 = new_test_swarm_builder::<_, ()>(KeepAliveOnceHandler::default())
+            .report_keep_alive_changes(true)
+            .build();
+        let mut swarm2 = new_test_swarm::<_, ()>(DummyProtocolsHandler { keep_alive: KeepAlive::Yes });
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm1.dial_addr(addr2).unwrap();
+        let peer2 = *Swarm::local_peer_id(&swarm2);
+
+        let mut changes = Vec::new();
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                if let Poll::Ready(SwarmEvent::ConnectionKeepAliveChanged {
+                    peer_id, connection, keep_alive
+                }) = poll1 {
+                    assert_eq!(peer_id, peer2);
+                    let (_, established, _) = &swarm1.behaviour.inject_connection_established[0];
+                    assert_eq!(&connection, established);
+                    changes.push(keep_alive);
+                    if keep_alive == KeepAlive::No {
+                        assert_eq!(changes.len(), 2);
+                        assert!(matches!(changes[0], KeepAlive::Until(_)));
+                        return Poll::Ready(())
+                    }
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }))
+    }
+
+    /// Establishes a connection whose handler on one side keeps it alive
+    /// until a deadline and then not at all, with that swarm configured to
+    /// keep idle connections for a second.
+    ///
+    /// The test expects the connection to be closed once the idle timeout
+    /// has elapsed, rather than right away or once the deadline of the
+    /// handler has elapsed.
+    #[test]
+    fn test_keep_alive_deadline_falls_back_to_idle_timeout() {
+        let idle_timeout = Duration::from_secs(1);
+        let mut swarm1 = new_test_swarm_builder::<_, ()>(KeepAliveOnceHandler::default())
+            .idle_connection_timeout(idle_timeout)
+            .build();
+        let mut swarm2 = new_test_swarm::<_, ()>(DummyProtocolsHandler { keep_alive: KeepAlive::Yes });
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+
+        let start = std::time::Instant::now();
+        swarm1.dial_addr(addr2).unwrap();
+
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                if let Poll::Ready(SwarmEvent::ConnectionClosed { .. }) = poll1 {
+                    assert!(start.elapsed() >= idle_timeout);
+                    assert!(start.elapsed() < Duration::from_secs(10));
+                    return Poll::Ready(())
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }))
+    }
+
+    /// Issues a dialing attempt through a [`SwarmHandle`] from another thread
+    /// while the swarms are being polled.
+    ///
+    /// The test expects the connection to be established.
+    #[test]
+    fn test_dial_via_handle() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone());
+        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto);
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+
+        let handle = swarm1.handle();
+        let dialer = std::thread::spawn(move || handle.dial_addr(addr2));
+
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                if swarms_connected(&swarm1, &swarm2, 1) {
+                    return Poll::Ready(())
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }));
+
+        assert_eq!(dialer.join().unwrap(), Ok(()));
+    }
+
+    /// Connects two swarms without an executor, driving all connection
+    /// tasks on the single thread polling the swarms.
+    #[test]
+    fn test_connect_without_executor() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm_builder::<_, ()>(handler_proto.clone())
+            .without_executor()
+            .build();
+        let mut swarm2 = new_test_swarm_builder::<_, ()>(handler_proto)
+            .without_executor()
+            .build();
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm1.dial_addr(addr2).unwrap();
+
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                if swarms_connected(&swarm1, &swarm2, 1) {
+                    return Poll::Ready(())
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }));
+    }
+
+    /// Builds swarms requiring an executor, with and without configuring one.
+    ///
+    /// The test expects building to fail without an executor.
+    #[test]
+    fn test_require_executor() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let result = new_test_swarm_builder::<_, ()>(handler_proto.clone())
+            .require_executor()
+            .try_build();
+        assert!(matches!(result, Err(BuildError::MissingExecutor)));
+
+        let pool = ThreadPoolBuilder::new().create().unwrap();
+        let result = new_test_swarm_builder::<_, ()>(handler_proto)
+            .require_executor()
+            .executor(Box::new(move |f| pool.spawn_ok(f)))
+            .try_build();
+        assert!(result.is_ok());
+    }
+
+    /// Drives the behaviour of a `Swarm` through [`ExpandedSwarm::behaviour_mut`]
+    /// and inspects it through [`ExpandedSwarm::behaviour`].
+    ///
+    /// The test expects the action set through the accessor to be returned
+    /// from the behaviour's `poll`.
+    #[test]
+    fn test_behaviour_accessors() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+        let mut swarm = new_test_swarm::<_, ()>(handler_proto);
+
+        let peer = PeerId::random();
+        swarm.behaviour_mut().inner().next_action =
+            Some(NetworkBehaviourAction::ReportPeerUnroutable(peer));
+        executor::block_on(future::poll_fn(|cx| {
+            let _ = Swarm::poll_next_event(Pin::new(&mut swarm), cx);
+            Poll::Ready(())
+        }));
+
+        assert!(swarm.behaviour().poll > 0);
+        assert!(swarm.behaviour_mut().inner().next_action.is_none());
+        assert!(matches!(swarm.dial_policy(&peer), Some(DialPolicy::Unroutable { .. })));
+    }
+
+    /// Has the behaviour of a `Swarm` request a listener on a new address.
+    ///
+    /// The test expects the listener to be started and its address to be
+    /// reported as a `NewListenAddr` event.
+    #[test]
+    fn test_behaviour_listen_on() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+        let mut swarm = new_test_swarm::<_, ()>(handler_proto);
+
+        let addr: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm.behaviour.inner().next_action =
+            Some(NetworkBehaviourAction::ListenOn(addr.clone()));
+
+        let listener = executor::block_on(future::poll_fn(|cx| {
+            loop {
+                match Swarm::poll_next_event(Pin::new(&mut swarm), cx) {
+                    Poll::Ready(SwarmEvent::NewListenAddr { listener_id, address }) => {
+                        assert_eq!(address, addr);
+                        return Poll::Ready(listener_id)
+                    }
+                    Poll::Ready(e) => panic!("Unexpected event: {:?}", e),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+        }));
+
+        assert_eq!(swarm.behaviour.inject_new_listener, vec![listener]);
+        assert_eq!(swarm.behaviour.inject_new_listen_addr, vec![(listener, addr.clone())]);
+        assert_eq!(swarm.listeners().collect::<Vec<_>>(), vec![&addr]);
+    }
+
+    /// Connects a peer to two others and protects one of them, after which
+    /// all connections to unprotected peers are trimmed.
+    ///
+    /// The test expects only the connection to the unprotected peer to be
+    /// closed.
+    #[test]
+    fn test_protected_peer_is_not_trimmed() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone());
+        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto.clone());
+        let mut swarm3 = new_test_swarm::<_, ()>(handler_proto);
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        let addr3: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm3.listen_on(addr3.clone()).unwrap();
+        swarm1.dial_addr(addr2).unwrap();
+        swarm1.dial_addr(addr3).unwrap();
+
+        let swarm2_id = *swarm2.local_peer_id();
+        let swarm3_id = *swarm3.local_peer_id();
+
+        swarm1.protect_peer(swarm2_id, "a");
+        swarm1.protect_peer(swarm2_id, "b");
+        assert!(swarm1.unprotect_peer(&swarm2_id, "a"));
+        assert!(swarm1.is_protected(&swarm2_id));
+        assert!(!swarm1.is_protected(&swarm3_id));
+
+        let mut trimmed = false;
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+                let poll3 = Swarm::poll_next_event(Pin::new(&mut swarm3), cx);
+
+                if !trimmed && swarm1.behaviour.inject_connection_established.len() == 2 {
+                    for peer in &[swarm2_id, swarm3_id] {
+                        if !swarm1.is_protected(peer) {
+                            swarm1.disconnect_peer_id(*peer).unwrap();
+                        }
+                    }
+                    trimmed = true;
+                    continue
+                }
+
+                if trimmed && !swarm1.behaviour.inject_connection_closed.is_empty() {
+                    assert_eq!(swarm1.behaviour.inject_connection_closed.len(), 1);
+                    assert_eq!(swarm1.behaviour.inject_connection_closed[0].0, swarm3_id);
+                    assert!(swarm1.is_connected(&swarm2_id));
+                    assert!(!swarm1.is_connected(&swarm3_id));
+                    return Poll::Ready(())
+                }
+
+                if poll1.is_pending() && poll2.is_pending() && poll3.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }));
+    }
+
+    /// Establishes a connection between two peers using [`DummyBehaviour`]s
+    /// whose handlers keep connections alive until a given instant.
+    ///
+    /// The test expects the connection to be closed once that instant has
+    /// passed.
+    #[test]
+    fn test_dummy_behaviour_keep_alive() {
+        let timeout = Duration::from_millis(100);
+        let start = Instant::now();
+
+        let new_swarm = || {
+            let (peer_id, transport) = new_test_transport();
+            let behaviour = DummyBehaviour::with_keep_alive(KeepAlive::Until(start + timeout));
+            SwarmBuilder::new(transport, behaviour, peer_id).build()
+        };
+        let mut swarm1 = new_swarm();
+        let mut swarm2 = new_swarm();
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm1.dial_addr(addr2).unwrap();
+
+        let mut established = false;
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                match poll1 {
+                    Poll::Ready(SwarmEvent::ConnectionEstablished { .. }) => established = true,
+                    Poll::Ready(SwarmEvent::ConnectionClosed { .. }) => {
+                        assert!(established);
+                        assert!(start.elapsed() >= timeout);
+                        return Poll::Ready(())
+                    }
+                    _ => {}
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }));
+    }
+
+    /// Establishes a connection between two peers, after which the dialer's
+    /// behaviour requests another dial with [`DialPeerCondition::Always`].
+    ///
+    /// The test expects a second connection to the already connected peer
+    /// to be established.
+    #[test]
+    fn test_dial_peer_condition_always() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone());
+        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto);
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+
+        let swarm2_id = *swarm2.local_peer_id();
+        swarm1.behaviour.inner().addresses.insert(swarm2_id, vec![addr2.clone()]);
+        swarm1.dial_addr(addr2).unwrap();
+
+        let mut redialed = false;
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                if !redialed && swarms_connected(&swarm1, &swarm2, 1) {
+                    swarm1.behaviour.inner().next_action = Some(NetworkBehaviourAction::DialPeer {
+                        peer_id: swarm2_id,
+                        condition: DialPeerCondition::Always,
+                    });
+                    redialed = true;
+                    continue
+                }
+
+                if redialed && swarms_connected(&swarm1, &swarm2, 2) {
+                    return Poll::Ready(())
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }));
+    }
+
+    /// Dials two addresses with a dial concurrency limit of one.
+    ///
+    /// The test expects the second dial to be queued until the first
+    /// connection is established, after which it is initiated as well.
+    #[test]
+    fn test_dial_concurrency_limit() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm_builder::<_, ()>(handler_proto.clone())
+            .dial_concurrency_limit(NonZeroUsize::new(1).unwrap())
+            .build();
+        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto);
+
+        let addr2a: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        let addr2b: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2a.clone()).unwrap();
+        swarm2.listen_on(addr2b.clone()).unwrap();
+
+        swarm1.dial_addr(addr2a).unwrap();
+        swarm1.dial_addr(addr2b.clone()).unwrap();
+        assert_eq!(swarm1.network.info().connection_counters().num_pending_outgoing(), 1);
+        assert_eq!(swarm1.queued_dials, vec![QueuedDial::Address(addr2b)]);
+
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                let pending = swarm1.network.info().connection_counters().num_pending_outgoing();
+                assert!(pending <= 1);
+                match swarm1.behaviour.inject_connection_established.len() {
+                    0 => assert_eq!(swarm1.queued_dials.len(), 1),
+                    1 => {}
+                    _ => {
+                        assert!(swarm1.queued_dials.is_empty());
+                        return Poll::Ready(())
+                    }
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }))
+    }
+
+    /// Lets the behaviour request a dial while the dial concurrency limit
+    /// of one is reached.
+    ///
+    /// The test expects the dial to be queued with its [`DialPeerCondition`]
+    /// and to be reported as [`SwarmEvent::Dialing`] only once it is initiated.
+    #[test]
+    fn test_dial_concurrency_limit_dial_peer() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm_builder::<_, ()>(handler_proto.clone())
+            .dial_concurrency_limit(NonZeroUsize::new(1).unwrap())
+            .build();
+        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto.clone());
+        let mut swarm3 = new_test_swarm::<_, ()>(handler_proto);
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        let addr3: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm3.listen_on(addr3.clone()).unwrap();
+
+        let swarm3_id = *swarm3.local_peer_id();
+        swarm1.dial_addr(addr2).unwrap();
+        swarm1.behaviour.inner().addresses.insert(swarm3_id, vec![addr3]);
+        swarm1.behaviour.inner().next_action = Some(NetworkBehaviourAction::DialPeer {
+            peer_id: swarm3_id,
+            condition: DialPeerCondition::Disconnected,
+        });
+
+        let queued_dial = QueuedDial::Peer(swarm3_id, Some(DialPeerCondition::Disconnected));
+        let mut queued = false;
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+                let poll3 = Swarm::poll_next_event(Pin::new(&mut swarm3), cx);
+
+                match poll1 {
+                    Poll::Ready(SwarmEvent::Dialing(peer_id)) => {
+                        assert_eq!(peer_id, swarm3_id);
+                        assert!(queued);
+                        assert_eq!(swarm1.dialing_attempts(&peer_id).len(), 1);
+                        return Poll::Ready(())
+                    }
+                    Poll::Ready(_) => {}
+                    Poll::Pending => if poll2.is_pending() && poll3.is_pending() {
+                        return Poll::Pending
+                    }
+                }
+
+                if swarm1.queued_dials.contains(&queued_dial) {
+                    queued = true;
+                }
+            }
+        }))
+    }
+
+    /// Lets the behaviour request a dial to a peer while the dial concurrency
+    /// limit of one is reached, the peer then connecting to the local node.
+    ///
+    /// The test expects the queued dial to be dropped, since its
+    /// [`DialPeerCondition`] is no longer met, and to be reported to the
+    /// behaviour as a dial failure.
+    #[test]
+    fn test_dial_concurrency_limit_dropped_dial_peer() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm_builder::<_, ()>(handler_proto.clone())
+            .dial_concurrency_limit(NonZeroUsize::new(1).unwrap())
+            .build();
+        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto.clone());
+        let mut swarm3 = new_test_swarm::<_, ()>(handler_proto);
+
+        let addr1: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        let addr3: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm1.listen_on(addr1.clone()).unwrap();
+        swarm2.listen_on(addr2.clone()).unwrap();
+
+        // Swarm 2 is never polled, so that the dial to it keeps the limit reached.
+        let swarm3_id = *swarm3.local_peer_id();
+        swarm1.dial_addr(addr2).unwrap();
+        swarm1.behaviour.inner().addresses.insert(swarm3_id, vec![addr3]);
+        swarm1.behaviour.inner().next_action = Some(NetworkBehaviourAction::DialPeer {
+            peer_id: swarm3_id,
+            condition: DialPeerCondition::Disconnected,
+        });
+        swarm3.dial_addr(addr1).unwrap();
+
+        let queued_dial = QueuedDial::Peer(swarm3_id, Some(DialPeerCondition::Disconnected));
+        let mut queued = false;
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll3 = Swarm::poll_next_event(Pin::new(&mut swarm3), cx);
+
+                if swarm1.queued_dials.contains(&queued_dial) {
+                    queued = true;
+                }
+
+                if swarm1.behaviour.inject_dial_failure.contains(&swarm3_id) {
+                    assert!(queued);
+                    assert!(swarm1.queued_dials.is_empty());
+                    assert!(swarm1.is_connected(&swarm3_id));
+                    assert!(swarm1.dialing_attempts(&swarm3_id).is_empty());
+                    return Poll::Ready(())
+                }
+
+                if poll1.is_pending() && poll3.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }))
+    }
+
+    /// Lets two peers applying the given [`SimultaneousOpenPolicy`] dial each
+    /// other at the same time until a single connection remains on both sides.
+    ///
+    /// Returns whether the remaining connection was dialed by the peer with the
+    /// lower `PeerId` and whether it is the connection that was established
+    /// first at the peer with the higher `PeerId`.
+    fn simultaneous_open(policy: SimultaneousOpenPolicy) -> (bool, bool) {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm_builder::<_, ()>(handler_proto.clone())
+            .simultaneous_open_policy(policy)
+            .build();
+        let mut swarm2 = new_test_swarm_builder::<_, ()>(handler_proto)
+            .simultaneous_open_policy(policy)
+            .build();
+
+        let addr1: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm1.listen_on(addr1.clone()).unwrap();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm1.dial_addr(addr2).unwrap();
+        swarm2.dial_addr(addr1).unwrap();
+
+        let swarm1_id = *swarm1.local_peer_id();
+        let swarm2_id = *swarm2.local_peer_id();
+        let swarm1_lower = swarm1_id < swarm2_id;
+
+        let mut first1 = None;
+        let mut first2 = None;
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                if let Poll::Ready(SwarmEvent::ConnectionEstablished { connection, .. }) = poll1 {
+                    first1.get_or_insert(connection);
+                }
+                if let Poll::Ready(SwarmEvent::ConnectionEstablished { connection, .. }) = poll2 {
+                    first2.get_or_insert(connection);
+                }
+
+                let settled = [&swarm1, &swarm2].iter().all(|swarm| {
+                    let info = swarm.network.info();
+                    let counters = info.connection_counters();
+                    counters.num_pending() == 0 && counters.num_established() == 1
+                });
+                if settled {
+                    let mut peer1 = swarm1.network.peer(swarm2_id).into_connected().unwrap();
+                    let remaining1 = peer1.some_connection();
+                    let swarm1_dialed = remaining1.endpoint().is_dialer();
+                    let remaining1 = remaining1.id();
+                    let mut peer2 = swarm2.network.peer(swarm1_id).into_connected().unwrap();
+                    let remaining2 = peer2.some_connection().id();
+                    let first_at_higher = if swarm1_lower {
+                        Some(remaining2) == first2
+                    } else {
+                        Some(remaining1) == first1
+                    };
+                    return Poll::Ready((swarm1_dialed == swarm1_lower, first_at_higher))
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }))
+    }
+
+    /// Lets two peers dial each other at the same time, both resolving
+    /// simultaneous opens by [`SimultaneousOpenPolicy::KeepByPeerIdOrder`].
+    ///
+    /// The test expects a single connection to remain, dialed by the peer
+    /// with the lower `PeerId`.
+    #[test]
+    fn test_simultaneous_open_policy() {
+        assert!(simultaneous_open(SimultaneousOpenPolicy::KeepByPeerIdOrder).0);
+    }
+
+    /// Lets two peers dial each other at the same time, both resolving
+    /// simultaneous opens by [`SimultaneousOpenPolicy::KeepOldest`].
+    ///
+    /// The test expects both peers to agree on a single remaining connection,
+    /// the one established first at the peer with the higher `PeerId`.
+    #[test]
+    fn test_simultaneous_open_policy_keep_oldest() {
+        assert!(simultaneous_open(SimultaneousOpenPolicy::KeepOldest).1);
+    }
+
+    /// Connects a peer with connectivity watermarks of two to two other
+    /// peers, then disconnects one of them.
+    ///
+    /// The test expects the connectivity to be reported as healthy once both
+    /// peers are connected and as degraded after the disconnect.
+    #[test]
+    fn test_connectivity_watermarks() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm_builder::<_, ()>(handler_proto.clone())
+            .connectivity_watermarks(2, 2)
+            .build();
+        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto.clone());
+        let mut swarm3 = new_test_swarm::<_, ()>(handler_proto);
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        let addr3: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm3.listen_on(addr3.clone()).unwrap();
+        swarm1.dial_addr(addr2).unwrap();
+        swarm1.dial_addr(addr3).unwrap();
+
+        let swarm3_id = *swarm3.local_peer_id();
+
+        let mut states = Vec::new();
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+                let poll3 = Swarm::poll_next_event(Pin::new(&mut swarm3), cx);
+                match Swarm::poll_next_event(Pin::new(&mut swarm1), cx) {
+                    Poll::Ready(SwarmEvent::ConnectivityChanged { connected_peers, state }) => {
+                        states.push((connected_peers, state));
+                        match state {
+                            Connectivity::Healthy => swarm1.disconnect_peer_id(swarm3_id).unwrap(),
+                            Connectivity::Degraded => {
+                                assert_eq!(states, vec![
+                                    (2, Connectivity::Healthy),
+                                    (1, Connectivity::Degraded),
+                                ]);
+                                return Poll::Ready(())
+                            }
+                        }
+                    }
+                    Poll::Ready(_) => {}
+                    Poll::Pending => if poll2.is_pending() && poll3.is_pending() {
+                        return Poll::Pending
+                    }
+                }
+            }
+        }));
+    }
+
+    /// Dials a peer and checks that the resulting connection is reported
+    /// with the [`ConnectionId`] of the dialing attempt.
+    #[test]
+    fn test_dialing_attempt_connection_id() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone());
+        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto);
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+
+        let swarm2_id = *swarm2.local_peer_id();
+        swarm1.behaviour.inner().addresses.insert(swarm2_id, vec![addr2]);
+        swarm1.behaviour.inner().next_action = Some(NetworkBehaviourAction::DialPeer {
+            peer_id: swarm2_id,
+            condition: DialPeerCondition::Disconnected,
+        });
+
+        let mut dialing = None;
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+                match Swarm::poll_next_event(Pin::new(&mut swarm1), cx) {
+                    Poll::Ready(SwarmEvent::Dialing(peer_id)) => {
+                        let ids = swarm1.dialing_attempts(&peer_id);
+                        assert_eq!(ids.len(), 1);
+                        dialing = Some(ids[0]);
+                    }
+                    Poll::Ready(SwarmEvent::ConnectionEstablished { peer_id, connection, .. }) => {
+                        assert_eq!(peer_id, swarm2_id);
+                        assert_eq!(Some(connection), dialing);
+                        assert_eq!(swarm1.behaviour.inject_connection_established[0].1, connection);
+                        assert!(swarm1.dialing_attempts(&peer_id).is_empty());
+                        return Poll::Ready(())
+                    }
+                    Poll::Ready(_) => {}
+                    Poll::Pending => if poll2.is_pending() {
+                        return Poll::Pending
+                    }
+                }
+            }
+        }));
+    }
+
+    /// Dials a peer that does not accept any pending incoming connection.
+    ///
+    /// The test expects the listener to report the incoming connection as
+    /// rejected due to the connection limit.
+    #[test]
+    fn test_incoming_connection_limit_reached() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone());
+        let mut swarm2 = new_test_swarm_builder::<_, ()>(handler_proto)
+            .connection_limits(ConnectionLimits::default().with_max_pending_incoming(Some(0)))
+            .build();
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm1.dial_addr(addr2.clone()).unwrap();
+
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                match Swarm::poll_next_event(Pin::new(&mut swarm2), cx) {
+                    Poll::Ready(SwarmEvent::IncomingConnectionError { local_addr, error, .. }) => {
+                        assert_eq!(local_addr, addr2);
+                        match error {
+                            PendingConnectionError::ConnectionLimit(limit) => {
+                                assert_eq!(limit.limit, 0);
+                            }
+                            e => panic!("Unexpected error: {:?}", e),
+                        }
+                        return Poll::Ready(())
+                    }
+                    Poll::Ready(SwarmEvent::IncomingConnection { .. }) => {
+                        panic!("Rejected connection reported as incoming.")
+                    }
+                    Poll::Ready(_) => {}
+                    Poll::Pending => if poll1.is_pending() {
+                        return Poll::Pending
+                    }
+                }
+            }
+        }));
+    }
+
+    /// Dials a peer at an address nobody listens on, with a retry policy
+    /// permitting two retries.
+    ///
+    /// The test expects the dial to be attempted three times in total before
+    /// the dial failure is reported to the behaviour.
+    #[test]
+    fn test_dial_retry_policy() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm = new_test_swarm_builder::<_, ()>(handler_proto)
+            .dial_retry_policy(RetryPolicy::new(2, Duration::from_millis(10)))
+            .build();
+
+        let peer = PeerId::random();
+        let addr: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm.behaviour.inner().addresses.insert(peer, vec![addr]);
+        swarm.dial(&peer).unwrap();
+
+        let mut backing_off = 0;
+        executor::block_on(future::poll_fn(|cx| {
+            loop {
+                let poll = Swarm::poll_next_event(Pin::new(&mut swarm), cx);
+                if !swarm.behaviour.inject_dial_failure.is_empty() {
+                    return Poll::Ready(())
+                }
+                if let Poll::Ready(SwarmEvent::UnreachableAddr { .. }) = poll {
+                    assert!(matches!(swarm.dial_policy(&peer), Some(DialPolicy::BackingOff { .. })));
+                    backing_off += 1;
+                }
+                if poll.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }));
+
+        assert_eq!(swarm.behaviour.addresses_of_peer, vec![peer; 3]);
+        assert_eq!(swarm.behaviour.inject_addr_reach_failure.len(), 3);
+        assert_eq!(swarm.behaviour.inject_dial_failure, vec![peer]);
+        assert!(swarm.dial_retries.is_empty());
+        assert_eq!(backing_off, 2);
+        assert_eq!(swarm.dial_policy(&peer), None);
+    }
+
+    /// Reports a peer as unroutable from the behaviour and checks that dialing
+    /// it is refused until the configured TTL has elapsed.
+    #[test]
+    fn test_report_peer_unroutable() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+        let ttl = Duration::from_millis(200);
+
+        let mut swarm = new_test_swarm_builder::<_, ()>(handler_proto)
+            .unroutable_peer_ttl(ttl)
+            .build();
+
+        let peer = PeerId::random();
+        let addr: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm.behaviour.inner().addresses.insert(peer, vec![addr]);
+        swarm.behaviour.inner().next_action =
+            Some(NetworkBehaviourAction::ReportPeerUnroutable(peer));
+
+        executor::block_on(future::poll_fn(|cx| {
+            let _ = Swarm::poll_next_event(Pin::new(&mut swarm), cx);
+            Poll::Ready(())
+        }));
+
+        assert!(matches!(swarm.dial(&peer), Err(DialError::Unroutable)));
+        assert_eq!(swarm.behaviour.inject_dial_failure, vec![peer]);
+
+        std::thread::sleep(ttl);
+        assert!(swarm.dial(&peer).is_ok());
+    }
+
+    /// Requests dialing a banned and an unroutable peer from the behaviour.
+    ///
+    /// The test expects both dials to be refused with a
+    /// [`SwarmEvent::DialRefused`] stating the respective [`DialPolicy`].
+    #[test]
+    fn test_dial_refused() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+        let mut swarm = new_test_swarm::<_, ()>(handler_proto);
+
+        let banned = PeerId::random();
+        let unroutable = PeerId::random();
+        swarm.ban_peer_id(banned);
+        swarm.behaviour.inner().next_action =
+            Some(NetworkBehaviourAction::ReportPeerUnroutable(unroutable));
+        executor::block_on(future::poll_fn(|cx| {
+            let _ = Swarm::poll_next_event(Pin::new(&mut swarm), cx);
+            Poll::Ready(())
+        }));
+
+        assert_eq!(swarm.dial_policy(&banned), Some(DialPolicy::Banned));
+        let until = match swarm.dial_policy(&unroutable) {
+            Some(DialPolicy::Unroutable { until }) => until,
+            p => panic!("Unexpected dial policy: {:?}", p),
+        };
+        assert_eq!(swarm.dial_policy(&PeerId::random()), None);
+
+        let expected = [(banned, DialPolicy::Banned), (unroutable, DialPolicy::Unroutable { until })];
+        for (peer, expected_policy) in expected.iter() {
+            swarm.behaviour.inner().next_action = Some(NetworkBehaviourAction::DialPeer {
+                peer_id: *peer,
+                condition: DialPeerCondition::Disconnected,
+            });
+            let event = executor::block_on(future::poll_fn(|cx| {
+                Poll::Ready(Swarm::poll_next_event(Pin::new(&mut swarm), cx))
+            }));
+            match event {
+                Poll::Ready(SwarmEvent::DialRefused { peer_id, policy }) => {
+                    assert_eq!(&peer_id, peer);
+                    assert_eq!(&policy, expected_policy);
+                }
+                e => panic!("Unexpected event: {:?}", e),
+            }
+        }
+        assert_eq!(swarm.behaviour.inject_dial_failure, vec![banned, unroutable]);
+    }
+
+    /// A behaviour whose deferred work completes outside of `poll` and which
+    /// wakes itself through the waker of the last `poll` invocation.
+    #[derive(Default)]
+    struct SelfWakingBehaviour {
+        done: Arc<AtomicBool>,
+        worker: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl NetworkBehaviour for SelfWakingBehaviour {
+        type ProtocolsHandler = DummyProtocolsHandler;
+        type OutEvent = ();
+
+        fn new_handler(&mut self) -> Self::ProtocolsHandler {
+            DummyProtocolsHandler::default()
+        }
+
+        fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+            Vec::new()
+        }
+
+        fn inject_connected(&mut self, _: &PeerId) {}
+
+        fn inject_disconnected(&mut self, _: &PeerId) {}
+
+        fn inject_event(&mut self, _: PeerId, _: ConnectionId,
+            _: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent) {}
+
+        fn poll(&mut self, cx: &mut Context<'_>, _: &mut impl PollParameters) ->
+            Poll<NetworkBehaviourAction<<Self::ProtocolsHandler as
+            ProtocolsHandler>::InEvent, Self::OutEvent>>
+        {
+            if self.done.load(Ordering::SeqCst) {
+                return Poll::Ready(NetworkBehaviourAction::GenerateEvent(()))
+            }
+            if self.worker.is_none() {
+                let done = self.done.clone();
+                let waker = cx.waker().clone();
+                self.worker = Some(std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_millis(50));
+                    done.store(true, Ordering::SeqCst);
+                    waker.wake();
+                }));
+            }
+            Poll::Pending
+        }
+    }
+
+    /// Polls a `Swarm` whose behaviour only becomes ready once woken from
+    /// another thread.
+    ///
+    /// The test expects the `Swarm` to be polled again after the wake-up and
+    /// to yield the event of the behaviour.
+    #[test]
+    fn test_behaviour_wakes_itself() {
+        let (peer_id, transport) = new_test_transport();
+        let mut swarm = SwarmBuilder::new(transport, SelfWakingBehaviour::default(), peer_id)
+            .build();
+
+        match executor::block_on(swarm.next()) {
+            Some(SwarmEvent::Behaviour(())) => {}
+            e => panic!("Unexpected event: {:?}", e),
+        }
+    }
+
+    /// A handler that blocks its connection task on every received event
+    /// for as long as `blocker` is locked.
+    #[derive(Clone)]
+    struct BlockingProtocolsHandler {
+        blocker: Arc<Mutex<()>>,
+    }
+
+    impl ProtocolsHandler for BlockingProtocolsHandler {
+        type InEvent = ();
+        type OutEvent = void::Void;
+        type Error = void::Void;
+        type InboundProtocol = upgrade::DeniedUpgrade;
+        type OutboundProtocol = upgrade::DeniedUpgrade;
+        type OutboundOpenInfo = void::Void;
+        type InboundOpenInfo = ();
+
+        fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+            SubstreamProtocol::new(upgrade::DeniedUpgrade, ())
+        }
+
+        fn inject_fully_negotiated_inbound(&mut self, _: void::Void, _: Self::InboundOpenInfo) {}
+
+        fn inject_fully_negotiated_outbound(&mut self, _: void::Void, _: Self::OutboundOpenInfo) {}
+
+        fn inject_event(&mut self, _: Self::InEvent) {
+            drop(self.blocker.lock());
+        }
+
+        fn inject_dial_upgrade_error(&mut self, _: Self::OutboundOpenInfo, _: ProtocolsHandlerUpgrErr<void::Void>) {}
+
+        fn connection_keep_alive(&self) -> KeepAlive {
+            KeepAlive::Yes
+        }
+
+        fn poll(&mut self, _: &mut Context<'_>) -> Poll<
+            ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent, Self::Error>,
+        > {
+            Poll::Pending
+        }
+    }
+
+    /// A behaviour that sends a number of events to the handler of the first
+    /// connected peer and then generates an event of its own.
+    struct NotifyingBehaviour {
+        handler: BlockingProtocolsHandler,
+        peer: Option<PeerId>,
+        remaining: usize,
+    }
+
+    impl NetworkBehaviour for NotifyingBehaviour {
+        type ProtocolsHandler = BlockingProtocolsHandler;
+        type OutEvent = ();
+
+        fn new_handler(&mut self) -> Self::ProtocolsHandler {
+            self.handler.clone()
+        }
+
+        fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+            Vec::new()
+        }
+
+        fn inject_connected(&mut self, peer: &PeerId) {
+            self.peer = Some(*peer);
+        }
+
+        fn inject_disconnected(&mut self, _: &PeerId) {}
+
+        fn inject_event(&mut self, _: PeerId, _: ConnectionId, e: void::Void) {
+            void::unreachable(e)
+        }
+
+        fn poll(&mut self, _: &mut Context<'_>, _: &mut impl PollParameters) ->
+            Poll<NetworkBehaviourAction<(), ()>>
+        {
+            match self.peer {
+                Some(peer_id) if self.remaining > 0 => {
+                    self.remaining -= 1;
+                    Poll::Ready(NetworkBehaviourAction::NotifyHandler {
+                        peer_id,
+                        handler: NotifyHandler::Any,
+                        event: (),
+                    })
+                }
+                Some(_) => Poll::Ready(NetworkBehaviourAction::GenerateEvent(())),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    /// Sends more events to a connection handler than it can take, while the
+    /// handler never becomes ready again, and checks that the behaviour still
+    /// makes progress once the pending event timeout elapses.
+    #[test]
+    fn test_pending_event_timeout() {
+        let blocker = Arc::new(Mutex::new(()));
+        let _guard = blocker.lock().unwrap();
+
+        let (peer_id, transport) = new_test_transport();
+        let behaviour = NotifyingBehaviour {
+            handler: BlockingProtocolsHandler { blocker: blocker.clone() },
+            peer: None,
+            remaining: 5,
+        };
+        let mut swarm1 = SwarmBuilder::new(transport, behaviour, peer_id)
+            .notify_handler_buffer_size(NonZeroUsize::new(1).unwrap())
+            .pending_event_timeout(Duration::from_millis(50))
+            .build();
+        let mut swarm2 = new_test_swarm::<_, ()>(DummyProtocolsHandler { keep_alive: KeepAlive::Yes });
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm1.dial_addr(addr2).unwrap();
+
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                if let Poll::Ready(SwarmEvent::Behaviour(())) = poll1 {
+                    return Poll::Ready(())
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    return Poll::Pending
+                }
+            }
+        }));
+    }
+
+    /// Floods a connection handler that blocks on every event with events,
+    /// with a limit on the number of events in flight, and checks that the
+    /// behaviour is only polled again once the backlog drains.
+    #[test]
+    fn test_max_in_flight_events() {
+        let blocker = Arc::new(Mutex::new(()));
+        let mut guard = Some(blocker.lock().unwrap());
+
+        let num_events = 100;
+        let max_in_flight = 4;
+
+        let (peer_id, transport) = new_test_transport();
+        let behaviour = NotifyingBehaviour {
+            handler: BlockingProtocolsHandler { blocker: blocker.clone() },
+            peer: None,
+            remaining: num_events,
+        };
+        let mut swarm1 = SwarmBuilder::new(transport, behaviour, peer_id)
+            .notify_handler_buffer_size(NonZeroUsize::new(num_events).unwrap())
+            .max_in_flight_events(NonZeroUsize::new(max_in_flight).unwrap())
+            .build();
+        let mut swarm2 = new_test_swarm::<_, ()>(DummyProtocolsHandler { keep_alive: KeepAlive::Yes });
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        swarm1.dial_addr(addr2).unwrap();
+
+        executor::block_on(future::poll_fn(move |cx| {
+            loop {
+                let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                assert!(swarm1.in_flight_events.as_ref().unwrap().count() <= max_in_flight);
+
+                if let Poll::Ready(SwarmEvent::Behaviour(())) = poll1 {
+                    assert!(guard.is_none());
+                    return Poll::Ready(())
+                }
+
+                if poll1.is_pending() && poll2.is_pending() {
+                    if guard.is_some() && swarm1.behaviour.peer.is_some() {
+                        // The backlog is full and the handler is blocked.
+                        assert_eq!(swarm1.behaviour.remaining, num_events - max_in_flight);
+                        guard = None;
+                        continue
+                    }
+                    return Poll::Pending
+                }
+            }
+        }));
+    }
+
+    /// A behaviour that is always ready and numbers the events it generates.
+    #[derive(Default)]
+    struct CountingBehaviour {
+        generated: usize,
+    }
+
+    impl NetworkBehaviour for CountingBehaviour {
+        type ProtocolsHandler = DummyProtocolsHandler;
+        type OutEvent = usize;
+
+        fn new_handler(&mut self) -> Self::ProtocolsHandler {
+            DummyProtocolsHandler::default()
+        }
+
+        fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+            Vec::new()
+        }
+
+        fn inject_connected(&mut self, _: &PeerId) {}
+
+        fn inject_disconnected(&mut self, _: &PeerId) {}
+
+        fn inject_event(&mut self, _: PeerId, _: ConnectionId,
+            _: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent) {}
+
+        fn poll(&mut self, _: &mut Context<'_>, _: &mut impl PollParameters) ->
+            Poll<NetworkBehaviourAction<<Self::ProtocolsHandler as
+            ProtocolsHandler>::InEvent, Self::OutEvent>>
+        {
+            let event = self.generated;
+            self.generated += 1;
+            Poll::Ready(NetworkBehaviourAction::GenerateEvent(event))
+        }
+    }
+
+    #[test]
+    fn test_event_buffer() {
+        fn poll_twice(overflow: EventBufferOverflow) -> (Vec<usize>, usize) {
+            let (peer_id, transport) = new_test_transport();
+            let mut swarm = SwarmBuilder::new(transport, CountingBehaviour::default(), peer_id)
+                .event_buffer(NonZeroUsize::new(4).unwrap(), overflow)
+                .build();
+
+            let events = executor::block_on(future::poll_fn(|cx| {
+                let mut events = Vec::new();
+                for _ in 0..2 {
+                    match Swarm::poll_next_event(Pin::new(&mut swarm), cx) {
+                        Poll::Ready(SwarmEvent::Behaviour(n)) => events.push(n),
+                        e => panic!("Unexpected event: {:?}", e),
+                    }
+                }
+                Poll::Ready(events)
+            }));
+            (events, swarm.behaviour.generated)
+        }
+
+        // The behaviour keeps making progress while the consumer only takes a
+        // single event, until the buffer is full.
+        let (events, generated) = poll_twice(EventBufferOverflow::Backpressure);
+        assert_eq!(events, vec![0, 1]);
+        assert_eq!(generated, 5);
+
+        // With a dropping policy, the behaviour is polled `capacity` times on
+        // every poll of the `Swarm`, regardless of the buffer being full.
+        let (events, generated) = poll_twice(EventBufferOverflow::DropOldest);
+        assert_eq!(events, vec![0, 4]);
+        assert_eq!(generated, 8);
+
+        let (events, generated) = poll_twice(EventBufferOverflow::DropNewest);
+        assert_eq!(events, vec![0, 1]);
+        assert_eq!(generated, 8);
+    }
+
+    /// A handler that emits a number of events and then idles.
+    #[derive(Clone)]
+    struct EmittingProtocolsHandler {
+        remaining: usize,
+    }
+
+    impl ProtocolsHandler for EmittingProtocolsHandler {
+        type InEvent = void::Void;
+        type OutEvent = ();
+        type Error = void::Void;
+        type InboundProtocol = upgrade::DeniedUpgrade;
+        type OutboundProtocol = upgrade::DeniedUpgrade;
+        type OutboundOpenInfo = void::Void;
+        type InboundOpenInfo = ();
+
+        fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+            SubstreamProtocol::new(upgrade::DeniedUpgrade, ())
+        }
+
+        fn inject_fully_negotiated_inbound(&mut self, _: void::Void, _: Self::InboundOpenInfo) {}
+
+        fn inject_fully_negotiated_outbound(&mut self, _: void::Void, _: Self::OutboundOpenInfo) {}
+
+        fn inject_event(&mut self, e: Self::InEvent) {
+            void::unreachable(e)
+        }
+
+        fn inject_dial_upgrade_error(&mut self, _: Self::OutboundOpenInfo, _: ProtocolsHandlerUpgrErr<void::Void>) {}
+
+        fn connection_keep_alive(&self) -> KeepAlive {
+            KeepAlive::Yes
+        }
+
+        fn poll(&mut self, _: &mut Context<'_>) -> Poll<
+            ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent, Self::Error>,
+        > {
+            if self.remaining == 0 {
+                return Poll::Pending
+            }
+            self.remaining -= 1;
+            Poll::Ready(ProtocolsHandlerEvent::Custom(()))
+        }
+    }
+
+    /// A behaviour that generates an event for every event of its handlers.
+    struct ForwardingBehaviour {
+        handler: EmittingProtocolsHandler,
+        received: usize,
+        generated: usize,
+    }
+
+    impl NetworkBehaviour for ForwardingBehaviour {
+        type ProtocolsHandler = EmittingProtocolsHandler;
+        type OutEvent = ();
+
+        fn new_handler(&mut self) -> Self::ProtocolsHandler {
+            self.handler.clone()
+        }
+
+        fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+            Vec::new()
+        }
+
+        fn inject_connected(&mut self, _: &PeerId) {}
+
+        fn inject_disconnected(&mut self, _: &PeerId) {}
+
+        fn inject_event(&mut self, _: PeerId, _: ConnectionId, _: ()) {
+            self.received += 1;
+        }
+
+        fn poll(&mut self, _: &mut Context<'_>, _: &mut impl PollParameters) ->
+            Poll<NetworkBehaviourAction<void::Void, ()>>
+        {
+            if self.generated == self.received {
+                return Poll::Pending
+            }
+            self.generated += 1;
+            Poll::Ready(NetworkBehaviourAction::GenerateEvent(()))
+        }
+    }
+
+    /// Lets a connection handler emit a number of events, each of which the
+    /// behaviour reports, while the consumer takes a single event from the
+    /// `Swarm` on every poll.
+    ///
+    /// The test expects the `Swarm` to service the connection ahead of the
+    /// consumer by as many events as fit into the event buffer, and not at
+    /// all without a buffer.
+    #[test]
+    fn test_event_buffer_slow_consumer() {
+        fn serviced_ahead(num_events: usize, capacity: Option<NonZeroUsize>) -> Vec<usize> {
+            let (peer_id, transport) = new_test_transport();
+            let behaviour = ForwardingBehaviour {
+                handler: EmittingProtocolsHandler { remaining: num_events },
+                received: 0,
+                generated: 0,
+            };
+            let mut builder = SwarmBuilder::new(transport, behaviour, peer_id).without_executor();
+            if let Some(capacity) = capacity {
+                builder = builder.event_buffer(capacity, EventBufferOverflow::Backpressure);
+            }
+            let mut swarm1 = builder.build();
+            let mut swarm2 = new_test_swarm::<_, ()>(DummyProtocolsHandler { keep_alive: KeepAlive::Yes });
+
+            let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+            swarm2.listen_on(addr2.clone()).unwrap();
+            swarm1.dial_addr(addr2).unwrap();
+
+            let mut ahead = Vec::new();
+            executor::block_on(future::poll_fn(|cx| {
+                loop {
+                    let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                    let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+
+                    if let Poll::Ready(SwarmEvent::Behaviour(())) = poll1 {
+                        ahead.push(swarm1.behaviour.received - (ahead.len() + 1));
+                        if ahead.len() == num_events {
+                            return Poll::Ready(())
+                        }
+                    }
+
+                    if poll1.is_pending() && poll2.is_pending() {
+                        return Poll::Pending
+                    }
+                }
+            }));
+            ahead
+        }
+
+        let num_events = 20;
+        assert_eq!(serviced_ahead(num_events, None), vec![0; num_events]);
+
+        // Every poll refills the buffer, of which the consumer takes one event.
+        let capacity = 4;
+        let expected = (1..=num_events)
+            .map(|consumed| std::cmp::min(capacity - 1, num_events - consumed))
+            .collect::<Vec<_>>();
+        assert_eq!(serviced_ahead(num_events, NonZeroUsize::new(capacity)), expected);
+    }
+
+    /// The `id` field of a span and the messages of the events recorded in it.
+    #[cfg(feature = "tracing")]
+    type CapturedSpan = (String, Vec<String>);
+
+    /// A `tracing` subscriber that captures every span with its events.
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct CaptureSubscriber {
+        spans: Arc<Mutex<Vec<CapturedSpan>>>,
+        entered: Mutex<Vec<tracing::span::Id>>,
+    }
+
+    #[cfg(feature = "tracing")]
+    struct FieldVisitor(&'static str, Option<String>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::field::Visit for FieldVisitor {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+            if field.name() == self.0 {
+                self.1 = Some(format!("{:?}", value));
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for CaptureSubscriber {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut visitor = FieldVisitor("id", None);
+            attrs.record(&mut visitor);
+            let mut spans = self.spans.lock().unwrap();
+            spans.push((visitor.1.unwrap_or_default(), Vec::new()));
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut visitor = FieldVisitor("message", None);
+            event.record(&mut visitor);
+            let span = event.parent().cloned()
+                .or_else(|| self.entered.lock().unwrap().last().cloned());
+            if let (Some(span), Some(message)) = (span, visitor.1) {
+                self.spans.lock().unwrap()[span.into_u64() as usize - 1].1.push(message);
+            }
+        }
+
+        fn enter(&self, id: &tracing::span::Id) {
+            self.entered.lock().unwrap().push(id.clone());
+        }
+
+        fn exit(&self, _: &tracing::span::Id) {
+            self.entered.lock().unwrap().pop();
+        }
+    }
+
+    /// Connects two peers and closes the connection.
+    ///
+    /// The test expects the events of every phase of the connection to be
+    /// recorded in one `connection` span of its `ConnectionId`, on both sides.
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_connection_spans() {
+        let handler_proto = DummyProtocolsHandler { keep_alive: KeepAlive::Yes };
+
+        let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone());
+        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto);
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        let swarm2_id = *swarm2.local_peer_id();
+
+        let subscriber = CaptureSubscriber::default();
+        let spans = subscriber.spans.clone();
+        let (connection1, connection2) = tracing::subscriber::with_default(subscriber, || {
+            swarm1.dial_addr(addr2).unwrap();
+            let mut connections = (None, None);
+            let mut closed = (false, false);
+            executor::block_on(future::poll_fn(move |cx| {
+                loop {
+                    let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+                    let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+                    match poll1 {
+                        Poll::Ready(SwarmEvent::ConnectionEstablished { connection, .. }) => {
+                            connections.0 = Some(connection);
+                            swarm1.disconnect_peer_id(swarm2_id).unwrap();
+                        }
+                        Poll::Ready(SwarmEvent::ConnectionClosed { .. }) => closed.0 = true,
+                        _ => {}
+                    }
+                    match poll2 {
+                        Poll::Ready(SwarmEvent::ConnectionEstablished { connection, .. }) =>
+                            connections.1 = Some(connection),
+                        Poll::Ready(SwarmEvent::ConnectionClosed { .. }) => closed.1 = true,
+                        _ => {}
+                    }
+                    if closed == (true, true) {
+                        return Poll::Ready((connections.0.unwrap(), connections.1.unwrap()))
+                    }
+                    if poll1.is_pending() && poll2.is_pending() {
+                        return Poll::Pending
+                    }
+                }
+            }))
+        });
+
+        let spans = spans.lock().unwrap();
+        assert_eq!(spans.len(), 2);
+        let expected = |connection: ConnectionId, first: &str| {
+            (format!("{:?}", connection), vec![
+                first.to_string(),
+                "Connection established".to_string(),
+                "Connection closed".to_string(),
+            ])
+        };
+        assert!(spans.contains(&expected(connection1, "Dialing")));
+        assert!(spans.contains(&expected(connection2, "Upgrading incoming connection")));
+    }
 }
@@ -63,7 +63,14 @@ use wasm_timer::Instant;
 pub use dummy::DummyProtocolsHandler;
 pub use map_in::MapInEvent;
 pub use map_out::MapOutEvent;
-pub use node_handler::{NodeHandlerWrapper, NodeHandlerWrapperBuilder, NodeHandlerWrapperError};
+pub use node_handler::{
+    NodeHandlerWrapper,
+    NodeHandlerWrapperBuilder,
+    NodeHandlerWrapperError,
+    NodeHandlerWrapperEvent,
+    NodeHandlerWrapperInEvent,
+};
+pub(crate) use node_handler::{InFlightEvents, KeepAliveOverrides};
 pub use one_shot::{OneShotHandler, OneShotHandlerConfig};
 pub use select::{IntoProtocolsHandlerSelect, ProtocolsHandlerSelect};
 
@@ -29,8 +29,10 @@ use crate::protocols_handler::{
 
 use futures::prelude::*;
 use futures::stream::FuturesUnordered;
+use futures::task::AtomicWaker;
 use libp2p_core::{
     Multiaddr,
+    PeerId,
     Connected,
     connection::{
         ConnectionHandler,
@@ -42,7 +44,16 @@ use libp2p_core::{
     muxing::StreamMuxerBox,
     upgrade::{self, InboundUpgradeApply, OutboundUpgradeApply, UpgradeError}
 };
-use std::{error, fmt, pin::Pin, task::Context, task::Poll, time::Duration};
+use std::{
+    collections::HashMap,
+    error,
+    fmt,
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
 use wasm_timer::{Delay, Instant};
 
 /// Prototype for a `NodeHandlerWrapper`.
@@ -51,6 +62,12 @@ pub struct NodeHandlerWrapperBuilder<TIntoProtoHandler> {
     handler: TIntoProtoHandler,
     /// The substream upgrade protocol override, if any.
     substream_upgrade_protocol_override: Option<upgrade::Version>,
+    /// The duration for which an idle connection is kept alive.
+    idle_connection_timeout: Duration,
+    /// The per-peer overrides of the keep-alive of idle connections.
+    keep_alive_overrides: KeepAliveOverrides,
+    /// Whether changes of the keep-alive of the handler are reported.
+    report_keep_alive_changes: bool,
 }
 
 impl<TIntoProtoHandler> NodeHandlerWrapperBuilder<TIntoProtoHandler>
@@ -62,6 +79,9 @@ where
         NodeHandlerWrapperBuilder {
             handler,
             substream_upgrade_protocol_override: None,
+            idle_connection_timeout: Duration::from_secs(0),
+            keep_alive_overrides: KeepAliveOverrides::default(),
+            report_keep_alive_changes: false,
         }
     }
 
@@ -72,6 +92,21 @@ where
         self.substream_upgrade_protocol_override = version;
         self
     }
+
+    pub(crate) fn with_idle_connection_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_connection_timeout = timeout;
+        self
+    }
+
+    pub(crate) fn with_keep_alive_overrides(mut self, overrides: KeepAliveOverrides) -> Self {
+        self.keep_alive_overrides = overrides;
+        self
+    }
+
+    pub(crate) fn with_keep_alive_change_reports(mut self, enabled: bool) -> Self {
+        self.report_keep_alive_changes = enabled;
+        self
+    }
 }
 
 impl<TIntoProtoHandler, TProtoHandler> IntoConnectionHandler
@@ -91,6 +126,12 @@ where
             unique_dial_upgrade_id: 0,
             shutdown: Shutdown::None,
             substream_upgrade_protocol_override: self.substream_upgrade_protocol_override,
+            idle_connection_timeout: self.idle_connection_timeout,
+            peer_id: connected.peer_id,
+            keep_alive_overrides: self.keep_alive_overrides,
+            report_keep_alive_changes: self.report_keep_alive_changes,
+            keep_alive: None,
+            keep_alive_changed: None,
         }
     }
 }
@@ -123,6 +164,20 @@ where
     shutdown: Shutdown,
     /// The substream upgrade protocol override, if any.
     substream_upgrade_protocol_override: Option<upgrade::Version>,
+    /// The duration for which the connection is kept alive after the handler
+    /// returned [`KeepAlive::No`].
+    idle_connection_timeout: Duration,
+    /// The remote peer of the connection.
+    peer_id: PeerId,
+    /// The per-peer overrides of the keep-alive of idle connections.
+    keep_alive_overrides: KeepAliveOverrides,
+    /// Whether changes of the keep-alive of the handler are reported.
+    report_keep_alive_changes: bool,
+    /// The last [`KeepAlive`] returned by the underlying handler, if any.
+    keep_alive: Option<KeepAlive>,
+    /// A change of the [`KeepAlive`] of the underlying handler that is yet
+    /// to be reported.
+    keep_alive_changed: Option<KeepAlive>,
 }
 
 struct SubstreamUpgrade<UserData, Upgrade> {
@@ -167,6 +222,94 @@ where
 }
 
 
+/// Per-peer overrides of the [`KeepAlive`] of idle connections, shared between
+/// the `Swarm` and the [`NodeHandlerWrapper`]s of all its connections.
+///
+/// An override applies whenever the underlying handler of a connection to the
+/// peer returns [`KeepAlive::No`], taking the place of the idle connection
+/// timeout.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct KeepAliveOverrides(Arc<Mutex<HashMap<PeerId, KeepAliveOverride>>>);
+
+#[derive(Debug)]
+struct KeepAliveOverride {
+    keep_alive: KeepAlive,
+    /// The tasks of the idle connections that consulted the override, woken up
+    /// when it changes so that their planned shutdown is updated.
+    wakers: Vec<Waker>,
+}
+
+impl KeepAliveOverrides {
+    /// Sets or, if `keep_alive` is `None`, removes the override for a peer.
+    pub(crate) fn set(&self, peer_id: PeerId, keep_alive: Option<KeepAlive>) {
+        let wakers = {
+            let mut overrides = self.0.lock().expect("Lock is never poisoned.");
+            match keep_alive {
+                Some(keep_alive) => {
+                    let entry = overrides.entry(peer_id).or_insert_with(|| KeepAliveOverride {
+                        keep_alive,
+                        wakers: Vec::new(),
+                    });
+                    entry.keep_alive = keep_alive;
+                    std::mem::take(&mut entry.wakers)
+                }
+                None => overrides.remove(&peer_id).map(|o| o.wakers).unwrap_or_default(),
+            }
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Returns the override for a peer, if any, registering the current task
+    /// to be woken up once the override changes.
+    fn get(&self, peer_id: &PeerId, cx: &mut Context<'_>) -> Option<KeepAlive> {
+        let mut overrides = self.0.lock().expect("Lock is never poisoned.");
+        let entry = overrides.get_mut(peer_id)?;
+        if !entry.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            entry.wakers.push(cx.waker().clone());
+        }
+        Some(entry.keep_alive)
+    }
+}
+
+/// The number of events sent to the [`NodeHandlerWrapper`]s of a `Swarm` that
+/// have not yet been injected into the underlying handlers, shared between the
+/// `Swarm` and all its connections.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InFlightEvents(Arc<InFlightEventsInner>);
+
+#[derive(Debug, Default)]
+struct InFlightEventsInner {
+    count: AtomicUsize,
+    /// The task of the `Swarm`, woken up whenever an event is processed.
+    waker: AtomicWaker,
+}
+
+impl InFlightEvents {
+    /// Returns the current number of events in flight.
+    pub(crate) fn count(&self) -> usize {
+        self.0.count.load(Ordering::SeqCst)
+    }
+
+    /// Registers the current task to be woken up once the number of events
+    /// in flight decreases.
+    pub(crate) fn register(&self, cx: &mut Context<'_>) {
+        self.0.waker.register(cx.waker());
+    }
+}
+
+/// Counts an event as in flight for as long as it exists.
+#[derive(Debug)]
+struct InFlightEvent(InFlightEvents);
+
+impl Drop for InFlightEvent {
+    fn drop(&mut self) {
+        (self.0).0.count.fetch_sub(1, Ordering::SeqCst);
+        (self.0).0.waker.wake();
+    }
+}
+
 /// The options for a planned connection & handler shutdown.
 ///
 /// A shutdown is planned anew based on the the return value of
@@ -182,7 +325,41 @@ enum Shutdown {
     /// A shut down is planned as soon as possible.
     Asap,
     /// A shut down is planned for when a `Delay` has elapsed.
-    Later(Delay, Instant)
+    Later(Delay, Instant),
+    /// A shut down is planned for when the idle connection timeout has elapsed.
+    Idle(Delay),
+}
+
+/// Event sent to the `NodeHandlerWrapper`.
+///
+/// If events in flight are counted, the event is counted as in flight until
+/// it has been injected into the underlying handler or dropped, e.g. because
+/// the connection closed.
+#[derive(Debug)]
+pub struct NodeHandlerWrapperInEvent<TInEvent> {
+    event: TInEvent,
+    in_flight: Option<InFlightEvent>,
+}
+
+impl<TInEvent> NodeHandlerWrapperInEvent<TInEvent> {
+    /// Wraps an event for the underlying handler, counting it in `in_flight`,
+    /// if given.
+    pub(crate) fn new(event: TInEvent, in_flight: Option<&InFlightEvents>) -> Self {
+        let in_flight = in_flight.map(|in_flight| {
+            in_flight.0.count.fetch_add(1, Ordering::SeqCst);
+            InFlightEvent(in_flight.clone())
+        });
+        NodeHandlerWrapperInEvent { event, in_flight }
+    }
+}
+
+/// Event generated by the `NodeHandlerWrapper`.
+#[derive(Debug)]
+pub enum NodeHandlerWrapperEvent<TOutEvent> {
+    /// An event generated by the underlying handler.
+    Custom(TOutEvent),
+    /// The [`KeepAlive`] returned by the underlying handler changed.
+    KeepAliveChanged(KeepAlive),
 }
 
 /// Error generated by the `NodeHandlerWrapper`.
@@ -229,8 +406,8 @@ impl<TProtoHandler> ConnectionHandler for NodeHandlerWrapper<TProtoHandler>
 where
     TProtoHandler: ProtocolsHandler,
 {
-    type InEvent = TProtoHandler::InEvent;
-    type OutEvent = TProtoHandler::OutEvent;
+    type InEvent = NodeHandlerWrapperInEvent<TProtoHandler::InEvent>;
+    type OutEvent = NodeHandlerWrapperEvent<TProtoHandler::OutEvent>;
     type Error = NodeHandlerWrapperError<TProtoHandler::Error>;
     type Substream = Substream<StreamMuxerBox>;
     // The first element of the tuple is the unique upgrade identifier
@@ -288,7 +465,9 @@ where
     }
 
     fn inject_event(&mut self, event: Self::InEvent) {
+        let NodeHandlerWrapperInEvent { event, in_flight } = event;
         self.handler.inject_event(event);
+        drop(in_flight);
     }
 
     fn inject_address_change(&mut self, new_address: &Multiaddr) {
@@ -298,6 +477,12 @@ where
     fn poll(&mut self, cx: &mut Context<'_>) -> Poll<
         Result<ConnectionHandlerEvent<Self::OutboundOpenInfo, Self::OutEvent>, Self::Error>
     > {
+        if let Some(keep_alive) = self.keep_alive_changed.take() {
+            return Poll::Ready(Ok(ConnectionHandlerEvent::Custom(
+                NodeHandlerWrapperEvent::KeepAliveChanged(keep_alive)
+            )));
+        }
+
         while let Poll::Ready(Some((user_data, res))) = self.negotiating_in.poll_next_unpin(cx) {
             match res {
                 Ok(upgrade) => self.handler.inject_fully_negotiated_inbound(upgrade, user_data),
@@ -318,20 +503,42 @@ where
 
         // Ask the handler whether it wants the connection (and the handler itself)
         // to be kept alive, which determines the planned shutdown, if any.
-        match (&mut self.shutdown, self.handler.connection_keep_alive()) {
+        // An idle connection is kept alive as overridden for the peer, if at all.
+        let handler_keep_alive = self.handler.connection_keep_alive();
+        if self.report_keep_alive_changes {
+            // A deadline of `KeepAlive::Until` that is merely recomputed is
+            // not reported as a change.
+            let changed = |k: KeepAlive| mem::discriminant(&k) != mem::discriminant(&handler_keep_alive);
+            if self.keep_alive.map_or(false, changed) {
+                self.keep_alive_changed = Some(handler_keep_alive);
+            }
+            self.keep_alive = Some(handler_keep_alive);
+        }
+        let keep_alive = match handler_keep_alive {
+            KeepAlive::No => self.keep_alive_overrides.get(&self.peer_id, cx).unwrap_or(KeepAlive::No),
+            keep_alive => keep_alive,
+        };
+        match (&mut self.shutdown, keep_alive) {
             (Shutdown::Later(timer, deadline), KeepAlive::Until(t)) =>
                 if *deadline != t {
                     *deadline = t;
                     timer.reset_at(t)
                 },
             (_, KeepAlive::Until(t)) => self.shutdown = Shutdown::Later(Delay::new_at(t), t),
-            (_, KeepAlive::No) => self.shutdown = Shutdown::Asap,
+            (_, KeepAlive::No) if self.idle_connection_timeout == Duration::from_secs(0) =>
+                self.shutdown = Shutdown::Asap,
+            // The idle timeout is already ticking, let it run out.
+            (Shutdown::Idle(_), KeepAlive::No) => {},
+            (_, KeepAlive::No) =>
+                self.shutdown = Shutdown::Idle(Delay::new(self.idle_connection_timeout)),
             (_, KeepAlive::Yes) => self.shutdown = Shutdown::None
         };
 
         match poll_result {
             Poll::Ready(ProtocolsHandlerEvent::Custom(event)) => {
-                return Poll::Ready(Ok(ConnectionHandlerEvent::Custom(event)));
+                return Poll::Ready(Ok(ConnectionHandlerEvent::Custom(
+                    NodeHandlerWrapperEvent::Custom(event)
+                )));
             }
             Poll::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol }) => {
                 let id = self.unique_dial_upgrade_id;
@@ -347,16 +554,23 @@ where
             Poll::Pending => (),
         };
 
+        if let Some(keep_alive) = self.keep_alive_changed.take() {
+            return Poll::Ready(Ok(ConnectionHandlerEvent::Custom(
+                NodeHandlerWrapperEvent::KeepAliveChanged(keep_alive)
+            )));
+        }
+
         // Check if the connection (and handler) should be shut down.
         // As long as we're still negotiating substreams, shutdown is always postponed.
         if self.negotiating_in.is_empty() && self.negotiating_out.is_empty() {
             match self.shutdown {
                 Shutdown::None => {},
                 Shutdown::Asap => return Poll::Ready(Err(NodeHandlerWrapperError::KeepAliveTimeout)),
-                Shutdown::Later(ref mut delay, _) => match Future::poll(Pin::new(delay), cx) {
-                    Poll::Ready(_) => return Poll::Ready(Err(NodeHandlerWrapperError::KeepAliveTimeout)),
-                    Poll::Pending => {}
-                }
+                Shutdown::Later(ref mut delay, _) | Shutdown::Idle(ref mut delay) =>
+                    match Future::poll(Pin::new(delay), cx) {
+                        Poll::Ready(_) => return Poll::Ready(Err(NodeHandlerWrapperError::KeepAliveTimeout)),
+                        Poll::Pending => {}
+                    }
             }
         }
 
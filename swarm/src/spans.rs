@@ -0,0 +1,144 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! `tracing` spans around the lifecycle of connections.
+//!
+//! With the `tracing` feature enabled, every connection of a `Swarm` gets a
+//! `connection` span, keyed by its [`ConnectionId`] and, once known, the
+//! [`PeerId`] of the remote. The span is created when the connection is
+//! dialed or accepted, records an event for each phase of the connection
+//! and is entered while the events of the connection are processed, until
+//! the connection is closed. Without the feature, all of this is a no-op.
+
+use libp2p_core::{connection::ConnectionId, ConnectedPoint, Multiaddr, PeerId};
+#[cfg(feature = "tracing")]
+use std::collections::HashMap;
+
+/// The `connection` spans of the connections of a `Swarm`.
+#[derive(Default)]
+pub(crate) struct ConnectionSpans {
+    #[cfg(feature = "tracing")]
+    spans: HashMap<ConnectionId, ConnectionSpan>,
+}
+
+/// The `connection` span of a single connection.
+#[cfg(feature = "tracing")]
+struct ConnectionSpan {
+    span: tracing::Span,
+    /// The endpoint and the expected peer, while the connection is pending.
+    pending: Option<(ConnectedPoint, Option<PeerId>)>,
+}
+
+/// Guard of an entered `connection` span, exiting the span when dropped.
+pub(crate) struct Entered {
+    #[cfg(feature = "tracing")]
+    _span: Option<tracing::span::EnteredSpan>,
+}
+
+#[cfg(feature = "tracing")]
+impl ConnectionSpans {
+    /// Creates the span of a new pending connection.
+    fn pending(&mut self, id: ConnectionId, endpoint: ConnectedPoint, peer: Option<PeerId>)
+        -> &tracing::Span
+    {
+        let span = tracing::debug_span!("connection", id = ?id, peer = tracing::field::Empty);
+        if let Some(peer) = peer.as_ref() {
+            span.record("peer", tracing::field::debug(peer));
+        }
+        &self.spans.entry(id)
+            .or_insert(ConnectionSpan { span, pending: Some((endpoint, peer)) })
+            .span
+    }
+
+    /// Records that the connection `id` is being dialed on `address`,
+    /// possibly to the given `peer`.
+    pub(crate) fn dialing(&mut self, id: ConnectionId, peer: Option<&PeerId>, address: &Multiaddr) {
+        let endpoint = ConnectedPoint::Dialer { address: address.clone() };
+        let span = self.pending(id, endpoint, peer.copied());
+        tracing::debug!(parent: span, address = %address, "Dialing");
+    }
+
+    /// Records that the incoming connection `id` is being upgraded.
+    pub(crate) fn upgrading(&mut self, id: ConnectionId, local_addr: &Multiaddr, send_back_addr: &Multiaddr) {
+        let endpoint = ConnectedPoint::Listener {
+            local_addr: local_addr.clone(),
+            send_back_addr: send_back_addr.clone(),
+        };
+        let span = self.pending(id, endpoint, None);
+        tracing::debug!(parent: span, send_back_addr = %send_back_addr, "Upgrading incoming connection");
+    }
+
+    /// Records that the pending connection to `endpoint` failed.
+    ///
+    /// Pending connections that fail are not reported with their
+    /// [`ConnectionId`], hence their span is looked up by the endpoint and
+    /// the expected peer.
+    pub(crate) fn failed(&mut self, endpoint: &ConnectedPoint, peer: Option<&PeerId>) {
+        let id = self.spans.iter()
+            .find(|(_, s)| match &s.pending {
+                Some((e, p)) => e == endpoint && p.as_ref() == peer,
+                None => false,
+            })
+            .map(|(id, _)| *id);
+        if let Some(s) = id.and_then(|id| self.spans.remove(&id)) {
+            tracing::debug!(parent: &s.span, "Connection failed");
+        }
+    }
+
+    /// Records that the connection `id` to `peer` has been established.
+    pub(crate) fn established(&mut self, id: ConnectionId, peer: &PeerId, endpoint: &ConnectedPoint) {
+        let s = self.spans.entry(id).or_insert_with(|| ConnectionSpan {
+            span: tracing::debug_span!("connection", id = ?id, peer = tracing::field::Empty),
+            pending: None,
+        });
+        s.span.record("peer", tracing::field::debug(peer));
+        s.pending = None;
+        tracing::debug!(parent: &s.span, endpoint = ?endpoint, "Connection established");
+    }
+
+    /// Enters the span of the connection `id` until the returned guard is dropped.
+    pub(crate) fn enter(&self, id: &ConnectionId) -> Entered {
+        Entered { _span: self.spans.get(id).map(|s| s.span.clone().entered()) }
+    }
+
+    /// Records that the connection `id` has been closed, discarding its span.
+    pub(crate) fn closed(&mut self, id: &ConnectionId) {
+        if let Some(s) = self.spans.remove(id) {
+            tracing::debug!(parent: &s.span, "Connection closed");
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl ConnectionSpans {
+    pub(crate) fn dialing(&mut self, _: ConnectionId, _: Option<&PeerId>, _: &Multiaddr) {}
+
+    pub(crate) fn upgrading(&mut self, _: ConnectionId, _: &Multiaddr, _: &Multiaddr) {}
+
+    pub(crate) fn failed(&mut self, _: &ConnectedPoint, _: Option<&PeerId>) {}
+
+    pub(crate) fn established(&mut self, _: ConnectionId, _: &PeerId, _: &ConnectedPoint) {}
+
+    pub(crate) fn enter(&self, _: &ConnectionId) -> Entered {
+        Entered {}
+    }
+
+    pub(crate) fn closed(&mut self, _: &ConnectionId) {}
+}
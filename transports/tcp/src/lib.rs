@@ -810,6 +810,71 @@ mod tests {
         test("/ip6/::1/tcp/0".parse().unwrap());
     }
 
+    #[test]
+    fn upgrade_reports_socket_addresses() {
+        env_logger::try_init().ok();
+
+        async fn listener<T: Provider>(addr: Multiaddr, mut ready_tx: mpsc::Sender<Multiaddr>)
+            -> (Multiaddr, Multiaddr)
+        {
+            let tcp = GenTcpConfig::<T>::new();
+            let mut listener = tcp.listen_on(addr).unwrap();
+            loop {
+                match listener.next().await.unwrap().unwrap() {
+                    ListenerEvent::NewAddress(listen_addr) => {
+                        ready_tx.send(listen_addr).await.unwrap();
+                    }
+                    ListenerEvent::Upgrade { upgrade, local_addr, remote_addr } => {
+                        upgrade.await.unwrap();
+                        return (local_addr, remote_addr)
+                    }
+                    e => panic!("Unexpected listener event: {:?}", e),
+                }
+            }
+        }
+
+        async fn dialer<T: Provider>(mut ready_rx: mpsc::Receiver<Multiaddr>) -> T::Stream {
+            let addr = ready_rx.next().await.unwrap();
+            let tcp = GenTcpConfig::<T>::new();
+            tcp.dial(addr).unwrap().await.unwrap()
+        }
+
+        fn to_multiaddr(addr: SocketAddr) -> Multiaddr {
+            ip_to_multiaddr(addr.ip(), addr.port())
+        }
+
+        fn test(addr: Multiaddr) {
+            #[cfg(feature = "async-io")]
+            {
+                let (ready_tx, ready_rx) = mpsc::channel(1);
+                let listener = listener::<async_io::Tcp>(addr.clone(), ready_tx);
+                let dialer = dialer::<async_io::Tcp>(ready_rx);
+                let listener = async_std::task::spawn(listener);
+                let socket = async_std::task::block_on(dialer);
+                let (local_addr, remote_addr) = async_std::task::block_on(listener);
+                assert_eq!(local_addr, to_multiaddr(socket.get_ref().peer_addr().unwrap()));
+                assert_eq!(remote_addr, to_multiaddr(socket.get_ref().local_addr().unwrap()));
+            }
+
+            #[cfg(feature = "tokio")]
+            {
+                let (ready_tx, ready_rx) = mpsc::channel(1);
+                let listener = listener::<tokio::Tcp>(addr.clone(), ready_tx);
+                let dialer = dialer::<tokio::Tcp>(ready_rx);
+                let rt = tokio_crate::runtime::Builder::new_current_thread().enable_io().build().unwrap();
+                let tasks = tokio_crate::task::LocalSet::new();
+                let listener = tasks.spawn_local(listener);
+                let socket = tasks.block_on(&rt, dialer);
+                let (local_addr, remote_addr) = tasks.block_on(&rt, listener).unwrap();
+                assert_eq!(local_addr, to_multiaddr(socket.0.peer_addr().unwrap()));
+                assert_eq!(remote_addr, to_multiaddr(socket.0.local_addr().unwrap()));
+            }
+        }
+
+        test("/ip4/127.0.0.1/tcp/0".parse().unwrap());
+        test("/ip6/::1/tcp/0".parse().unwrap());
+    }
+
     #[test]
     fn wildcard_expansion() {
         env_logger::try_init().ok();